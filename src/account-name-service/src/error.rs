@@ -0,0 +1,143 @@
+use name_normalization::NameError;
+use name_service_common::error::CommonError;
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    log,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+/// Errors returned by the account name service, surfaced to clients as
+/// `ProgramError::Custom(code)` where code is the variant index
+#[derive(Clone, Copy, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum NameServiceError {
+    #[error("Invalid payment account")]
+    InvalidPaymentAccount,
+    #[error("Invalid registry account")]
+    InvalidRegistry,
+    #[error("Registry is not initialized")]
+    RegistryNotInitialized,
+    #[error("Invalid counter account")]
+    InvalidCounterAccount,
+    #[error("Name can not be empty")]
+    EmptyName,
+    #[error("Record account does not match the name")]
+    InvalidRecordAddress,
+    #[error("Reverse account does not match the address")]
+    InvalidReverseAddress,
+    #[error("Account must be owned by program")]
+    IncorrectAccountOwner,
+    #[error("Account data field is insufficient")]
+    AccountDataTooSmall,
+    #[error("Name is already registered")]
+    NameAlreadyRegistered,
+    #[error("Name is not registered")]
+    NameNotRegistered,
+    #[error("You are not owner of this name")]
+    NotNameOwner,
+    #[error("Insufficient funds to pay registration fee")]
+    InsufficientFunds,
+    #[error("Config account does not match the program")]
+    InvalidConfigAddress,
+    #[error("Config is already initialized")]
+    ConfigAlreadyInitialized,
+    #[error("Config is not initialized")]
+    ConfigNotInitialized,
+    #[error("You are not admin of this program")]
+    NotAdmin,
+    #[error("Name is not valid")]
+    InvalidName,
+    #[error("Parent name does not accept new subdomains")]
+    SubdomainsClosed,
+    #[error("Text record account does not match the name and key")]
+    InvalidTextAddress,
+    #[error("Text value is not valid")]
+    InvalidTextValue,
+    #[error("Address record account does not match the name and coin type")]
+    InvalidChainAddressRecord,
+    #[error("Address is not valid for the coin type")]
+    InvalidChainAddress,
+    #[error("Commitment account does not match the owner and commitment")]
+    InvalidCommitmentAddress,
+    #[error("Commitment is already made")]
+    CommitmentAlreadyExists,
+    #[error("Commitment not found")]
+    CommitmentNotFound,
+    #[error("Commitment is too new to be revealed")]
+    CommitmentTooNew,
+    #[error("Commitment has expired")]
+    CommitmentExpired,
+    #[error("Commitment has not expired yet")]
+    CommitmentNotExpired,
+    #[error("Name has expired")]
+    NameExpired,
+    #[error("Name does not expire")]
+    NameDoesNotExpire,
+    #[error("Account does not belong to the name")]
+    InvalidNameAccount,
+    #[error("Account balance is below rent exemption")]
+    NotRentExempt,
+    #[error("Lamports arithmetic overflowed")]
+    Overflow,
+    #[error("Beneficiary shares must add up to 10000 basis points")]
+    InvalidBeneficiaries,
+    #[error("Beneficiary accounts do not match the config")]
+    InvalidBeneficiaryAccount,
+    #[error("Program is paused")]
+    ProgramPaused,
+    #[error("Reserved name account does not match the name")]
+    InvalidReservedAddress,
+    #[error("Name is reserved")]
+    NameReserved,
+    #[error("Name is not reserved")]
+    NameNotReserved,
+    #[error("Only the deployer can initialize the config")]
+    NotDeployer,
+}
+impl From<NameError> for NameServiceError {
+    fn from(e: NameError) -> Self {
+        match e {
+            NameError::Empty => NameServiceError::EmptyName,
+            _ => NameServiceError::InvalidName,
+        }
+    }
+}
+impl From<CommonError> for NameServiceError {
+    fn from(e: CommonError) -> Self {
+        match e {
+            CommonError::InvalidPaymentAccount => NameServiceError::InvalidPaymentAccount,
+            CommonError::IncorrectAccountOwner => NameServiceError::IncorrectAccountOwner,
+            CommonError::AccountDataTooSmall => NameServiceError::AccountDataTooSmall,
+            CommonError::InvalidConfigAddress => NameServiceError::InvalidConfigAddress,
+            CommonError::ConfigNotInitialized => NameServiceError::ConfigNotInitialized,
+            CommonError::NotAdmin => NameServiceError::NotAdmin,
+            CommonError::Overflow => NameServiceError::Overflow,
+            CommonError::InvalidBeneficiaries => NameServiceError::InvalidBeneficiaries,
+            CommonError::InvalidBeneficiaryAccount => NameServiceError::InvalidBeneficiaryAccount,
+            CommonError::ProgramPaused => NameServiceError::ProgramPaused,
+            CommonError::InvalidReservedAddress => NameServiceError::InvalidReservedAddress,
+            CommonError::NameReserved => NameServiceError::NameReserved,
+            CommonError::NameNotReserved => NameServiceError::NameNotReserved,
+        }
+    }
+}
+impl From<NameServiceError> for ProgramError {
+    fn from(e: NameServiceError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+impl<T> DecodeError<T> for NameServiceError {
+    fn type_of() -> &'static str {
+        "NameServiceError"
+    }
+}
+impl PrintProgramError for NameServiceError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        log::sol_log(&self.to_string());
+    }
+}
@@ -0,0 +1,648 @@
+use arrayref::{array_ref, array_refs};
+use name_service_common::{
+    pack_beneficiaries, unpack_beneficiaries, unpack_fees, Beneficiary, BENEFICIARIES_SIZE,
+    FEE_TIERS, MAX_BENEFICIARIES,
+};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use solana_sdk::{program_error::ProgramError, pubkey::Pubkey};
+
+/// Register and CreateSubdomain payload, encoded as the address, one byte of
+/// name length and the name itself, so the name can be at most 255 bytes
+/// before normalization
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Instruction {
+    pub account_address: Pubkey,
+    pub name: Vec<u8>,
+}
+/// Keys of the text records a name can have
+#[derive(Clone, Copy, Debug, Eq, FromPrimitive, PartialEq)]
+#[repr(u8)]
+pub enum TextKey {
+    Url,
+    Email,
+    Twitter,
+    Github,
+    Avatar,
+    Description,
+}
+/// Instructions supported by the program, the first byte of instruction
+/// data selects the variant. Instructions that register or change names
+/// fail while the program is paused, see `is_pausable`. Unless the config
+/// account is listed already it has to follow the accounts listed below.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NameInstruction {
+    /// Register a new name revealing an earlier `Commit` of the payer, the
+    /// payer becomes its owner and gets the commitment rent back
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config account
+    /// 1. `[writable]` Treasury, derived from the program id
+    /// 2. `[]` Proxy-pointer registry set in the config
+    /// 3. `[writable]` Counter, the counter entry of the registry
+    /// 4. `[writable]` Name record, derived from the name
+    /// 5. `[writable]` Reverse record, derived from the registered address,
+    ///    written only if that address signed as any of the accounts
+    /// 6. `[writable, signer]` Payer
+    /// 7. `[]` System program
+    /// 8. `[]` Rent sysvar
+    /// 9. `[writable]` Commitment account
+    /// 10. `[]` Clock sysvar
+    /// 11. `[]` Reserved name account, derived from the name hash
+    /// 12. `[signer]` Authority of the reserved name, read only when the
+    ///     name is reserved
+    Register {
+        instruction: Instruction,
+        salt: [u8; 32],
+    },
+    /// Hand a name over to a new owner
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Name record
+    /// 1. `[signer]` Current owner
+    Transfer { new_owner: Pubkey },
+    /// Point a name at a new address, keeping its index
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Name record
+    /// 1. `[signer]` Owner
+    /// 2. `[writable]` Reverse record of the current address
+    /// 3. `[writable]` Reverse record of the new address, written only if that
+    ///    address signed as any of the accounts
+    /// 4. `[writable, signer]` Payer
+    /// 5. `[]` System program
+    /// 6. `[]` Rent sysvar
+    UpdateAddress { new_address: Pubkey },
+    /// Create the program config and treasury, the signer becomes the admin.
+    /// `registry` is the proxy-pointer registry the counter is read from.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Config account, derived from the program id
+    /// 1. `[writable, signer]` Admin, has to be the deployer key
+    /// 2. `[]` System program
+    /// 3. `[]` Rent sysvar
+    /// 4. `[writable]` Treasury, derived from the program id
+    InitializeConfig {
+        beneficiaries: [Beneficiary; MAX_BENEFICIARIES],
+        fees: [u64; FEE_TIERS],
+        registry: Pubkey,
+    },
+    /// Replace admin, beneficiaries, fees and registry of the program config
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Config account
+    /// 1. `[signer]` Current admin
+    UpdateConfig {
+        admin: Pubkey,
+        beneficiaries: [Beneficiary; MAX_BENEFICIARIES],
+        fees: [u64; FEE_TIERS],
+        registry: Pubkey,
+    },
+    /// Create `name.parent` under a registered parent, the payer becomes its
+    /// owner. Free for the parent owner, anyone else pays the parent's
+    /// subdomain fee to the parent owner.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Parent record
+    /// 1. `[writable]` Parent owner
+    /// 2. `[writable]` Subdomain record, derived from the parent and name
+    /// 3. `[writable]` Reverse record, derived from the registered address,
+    ///    written only if that address signed as any of the accounts
+    /// 4. `[writable, signer]` Payer
+    /// 5. `[]` System program
+    /// 6. `[]` Rent sysvar
+    /// 7. `[]` Clock sysvar
+    CreateSubdomain(Instruction),
+    /// Set the fee others pay to create subdomains, zero allows only the owner
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Name record
+    /// 1. `[signer]` Owner of the name
+    SetSubdomainFee { fee: u64 },
+    /// Set a text record of a name, the owner pays for a new record account.
+    /// The value is tagged with the owner and goes stale when the name
+    /// changes hands.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Name record
+    /// 1. `[writable, signer]` Owner of the name
+    /// 2. `[writable]` Text record, derived from the name record and key
+    /// 3. `[]` System program
+    /// 4. `[]` Rent sysvar
+    SetText { key: TextKey, value: Vec<u8> },
+    /// Remove the value of a text record
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Name record
+    /// 1. `[signer]` Owner of the name
+    /// 2. `[writable]` Text record
+    ClearText { key: TextKey },
+    /// Set the address of a name on the chain with SLIP-44 `coin_type`, the
+    /// owner pays for a new record account. The address is tagged with the
+    /// owner and goes stale when the name changes hands.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Name record
+    /// 1. `[writable, signer]` Owner of the name
+    /// 2. `[writable]` Address record, derived from the name record and coin type
+    /// 3. `[]` System program
+    /// 4. `[]` Rent sysvar
+    SetChainAddress { coin_type: u32, address: Vec<u8> },
+    /// Remove the address of a name on the chain with `coin_type`
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Name record
+    /// 1. `[signer]` Owner of the name
+    /// 2. `[writable]` Address record
+    ClearChainAddress { coin_type: u32 },
+    /// Commit to `hash(name || salt || owner)` ahead of `Register`, so the
+    /// name stays hidden until it is revealed
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Commitment account, derived from the owner and commitment
+    /// 1. `[writable, signer]` Future owner of the name
+    /// 2. `[]` System program
+    /// 3. `[]` Rent sysvar
+    /// 4. `[]` Clock sysvar
+    Commit { commitment: [u8; 32] },
+    /// Close a commitment that was not revealed in time, returning its rent
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Commitment account
+    /// 1. `[writable, signer]` Owner of the commitment
+    /// 2. `[]` Clock sysvar
+    RefundCommitment,
+    /// Extend a name by another registration period, anyone can pay the fee
+    /// until it expires and only the owner during the grace period.
+    /// Subdomains are extended to the current expiry of their parent for free.
+    /// Works while the program is paused.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config account
+    /// 1. `[writable]` Treasury, derived from the program id
+    /// 2. `[writable]` Name record
+    /// 3. `[writable, signer]` Payer
+    /// 4. `[]` System program
+    /// 5. `[]` Clock sysvar
+    /// 6. `[]` Parent record, only for subdomains
+    Renew,
+    /// Delete a name, its reverse lookup when it still points at the name
+    /// and any listed text and address records, their lamports go to the
+    /// destination
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Name record
+    /// 1. `[signer]` Owner of the name
+    /// 2. `[writable]` Destination
+    /// 3. `[writable]` Reverse record of the registered address
+    /// 4. `[writable]` Optional text and address records of the name
+    Close,
+    /// Split the treasury balance above rent between the beneficiaries by
+    /// their shares, anyone can call it
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config account
+    /// 1. `[writable]` Treasury, derived from the program id
+    /// 2. `[]` Rent sysvar
+    /// 3. `[writable]` Beneficiaries with a share, in config order
+    Withdraw,
+    /// Stop or resume registrations and updates of names
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Config account
+    /// 1. `[signer]` Admin
+    SetPaused { paused: bool },
+    /// Reserve the name hashing to `name_hash`, see `reserved_name_hash`.
+    /// Only `authority` can register it afterwards, reserving it again
+    /// replaces the authority
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config account
+    /// 1. `[writable, signer]` Admin, funds the reserved name account
+    /// 2. `[writable]` Reserved name account, derived from the name hash
+    /// 3. `[]` System program
+    /// 4. `[]` Rent sysvar
+    ReserveName {
+        name_hash: [u8; 32],
+        authority: Pubkey,
+    },
+    /// Make a reserved name available to everyone again
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config account
+    /// 1. `[signer]` Admin
+    /// 2. `[writable]` Reserved name account
+    /// 3. `[writable]` Destination of the reserved name account lamports
+    ReleaseName { name_hash: [u8; 32] },
+}
+impl NameInstruction {
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let instruction = match tag {
+            0 => {
+                if rest.len() < 32 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                NameInstruction::Register {
+                    salt: *array_ref![rest, 0, 32],
+                    instruction: unpack_instruction(&rest[32..])?,
+                }
+            }
+            1 => NameInstruction::Transfer {
+                new_owner: unpack_pubkey(rest)?,
+            },
+            2 => NameInstruction::UpdateAddress {
+                new_address: unpack_pubkey(rest)?,
+            },
+            3 => {
+                if rest.len() != BENEFICIARIES_SIZE + 8 * FEE_TIERS + 32 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let (beneficiaries, fees, registry) = array_refs![
+                    array_ref![rest, 0, BENEFICIARIES_SIZE + 8 * FEE_TIERS + 32],
+                    BENEFICIARIES_SIZE,
+                    8 * FEE_TIERS,
+                    32
+                ];
+                NameInstruction::InitializeConfig {
+                    beneficiaries: unpack_beneficiaries(beneficiaries),
+                    fees: unpack_fees(fees),
+                    registry: Pubkey::new_from_array(*registry),
+                }
+            }
+            4 => {
+                if rest.len() != 32 + BENEFICIARIES_SIZE + 8 * FEE_TIERS + 32 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let (admin, beneficiaries, fees, registry) = array_refs![
+                    array_ref![rest, 0, 32 + BENEFICIARIES_SIZE + 8 * FEE_TIERS + 32],
+                    32,
+                    BENEFICIARIES_SIZE,
+                    8 * FEE_TIERS,
+                    32
+                ];
+                NameInstruction::UpdateConfig {
+                    admin: Pubkey::new_from_array(*admin),
+                    beneficiaries: unpack_beneficiaries(beneficiaries),
+                    fees: unpack_fees(fees),
+                    registry: Pubkey::new_from_array(*registry),
+                }
+            }
+            5 => NameInstruction::CreateSubdomain(unpack_instruction(rest)?),
+            6 => {
+                if rest.len() != 8 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                NameInstruction::SetSubdomainFee {
+                    fee: u64::from_le_bytes(*array_ref![rest, 0, 8]),
+                }
+            }
+            7 => {
+                if rest.len() < 2 || rest.len() != 2 + rest[1] as usize {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                NameInstruction::SetText {
+                    key: unpack_text_key(rest[0])?,
+                    value: rest[2..].to_vec(),
+                }
+            }
+            8 => {
+                if rest.len() != 1 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                NameInstruction::ClearText {
+                    key: unpack_text_key(rest[0])?,
+                }
+            }
+            9 => {
+                if rest.len() < 5 || rest.len() != 5 + rest[4] as usize {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                NameInstruction::SetChainAddress {
+                    coin_type: u32::from_le_bytes(*array_ref![rest, 0, 4]),
+                    address: rest[5..].to_vec(),
+                }
+            }
+            10 => {
+                if rest.len() != 4 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                NameInstruction::ClearChainAddress {
+                    coin_type: u32::from_le_bytes(*array_ref![rest, 0, 4]),
+                }
+            }
+            11 => {
+                if rest.len() != 32 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                NameInstruction::Commit {
+                    commitment: *array_ref![rest, 0, 32],
+                }
+            }
+            12 if rest.is_empty() => NameInstruction::RefundCommitment,
+            13 if rest.is_empty() => NameInstruction::Renew,
+            14 if rest.is_empty() => NameInstruction::Close,
+            15 if rest.is_empty() => NameInstruction::Withdraw,
+            16 => match rest {
+                [0] => NameInstruction::SetPaused { paused: false },
+                [1] => NameInstruction::SetPaused { paused: true },
+                _ => return Err(ProgramError::InvalidInstructionData),
+            },
+            17 => {
+                if rest.len() != 64 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let (name_hash, authority) = array_refs![array_ref![rest, 0, 64], 32, 32];
+                NameInstruction::ReserveName {
+                    name_hash: *name_hash,
+                    authority: Pubkey::new_from_array(*authority),
+                }
+            }
+            18 => {
+                if rest.len() != 32 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                NameInstruction::ReleaseName {
+                    name_hash: *array_ref![rest, 0, 32],
+                }
+            }
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+        Ok(instruction)
+    }
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(1 + 32 + BENEFICIARIES_SIZE + 8 * FEE_TIERS);
+        match self {
+            NameInstruction::Register { instruction, salt } => {
+                buf.push(0);
+                buf.extend_from_slice(salt);
+                pack_instruction(instruction, &mut buf);
+            }
+            NameInstruction::Transfer { new_owner } => {
+                buf.push(1);
+                buf.extend_from_slice(&new_owner.to_bytes());
+            }
+            NameInstruction::UpdateAddress { new_address } => {
+                buf.push(2);
+                buf.extend_from_slice(&new_address.to_bytes());
+            }
+            NameInstruction::InitializeConfig {
+                beneficiaries,
+                fees,
+                registry,
+            } => {
+                buf.push(3);
+                let mut beneficiaries_dst = [0u8; BENEFICIARIES_SIZE];
+                pack_beneficiaries(beneficiaries, &mut beneficiaries_dst);
+                buf.extend_from_slice(&beneficiaries_dst);
+                for fee in fees {
+                    buf.extend_from_slice(&fee.to_le_bytes());
+                }
+                buf.extend_from_slice(&registry.to_bytes());
+            }
+            NameInstruction::UpdateConfig {
+                admin,
+                beneficiaries,
+                fees,
+                registry,
+            } => {
+                buf.push(4);
+                buf.extend_from_slice(&admin.to_bytes());
+                let mut beneficiaries_dst = [0u8; BENEFICIARIES_SIZE];
+                pack_beneficiaries(beneficiaries, &mut beneficiaries_dst);
+                buf.extend_from_slice(&beneficiaries_dst);
+                for fee in fees {
+                    buf.extend_from_slice(&fee.to_le_bytes());
+                }
+                buf.extend_from_slice(&registry.to_bytes());
+            }
+            NameInstruction::CreateSubdomain(instruction) => {
+                buf.push(5);
+                pack_instruction(instruction, &mut buf);
+            }
+            NameInstruction::SetSubdomainFee { fee } => {
+                buf.push(6);
+                buf.extend_from_slice(&fee.to_le_bytes());
+            }
+            NameInstruction::SetText { key, value } => {
+                buf.push(7);
+                buf.push(*key as u8);
+                buf.push(value.len() as u8);
+                buf.extend_from_slice(value);
+            }
+            NameInstruction::ClearText { key } => {
+                buf.push(8);
+                buf.push(*key as u8);
+            }
+            NameInstruction::SetChainAddress { coin_type, address } => {
+                buf.push(9);
+                buf.extend_from_slice(&coin_type.to_le_bytes());
+                buf.push(address.len() as u8);
+                buf.extend_from_slice(address);
+            }
+            NameInstruction::ClearChainAddress { coin_type } => {
+                buf.push(10);
+                buf.extend_from_slice(&coin_type.to_le_bytes());
+            }
+            NameInstruction::Commit { commitment } => {
+                buf.push(11);
+                buf.extend_from_slice(commitment);
+            }
+            NameInstruction::RefundCommitment => buf.push(12),
+            NameInstruction::Renew => buf.push(13),
+            NameInstruction::Close => buf.push(14),
+            NameInstruction::Withdraw => buf.push(15),
+            NameInstruction::SetPaused { paused } => {
+                buf.push(16);
+                buf.push(*paused as u8);
+            }
+            NameInstruction::ReserveName {
+                name_hash,
+                authority,
+            } => {
+                buf.push(17);
+                buf.extend_from_slice(name_hash);
+                buf.extend_from_slice(&authority.to_bytes());
+            }
+            NameInstruction::ReleaseName { name_hash } => {
+                buf.push(18);
+                buf.extend_from_slice(name_hash);
+            }
+        }
+        buf
+    }
+    /// Whether the instruction is refused while the program is paused.
+    /// Admin instructions, ones that only release accounts and renewals keep
+    /// working, names expire during a pause too.
+    pub fn is_pausable(&self) -> bool {
+        !matches!(
+            self,
+            NameInstruction::InitializeConfig { .. }
+                | NameInstruction::UpdateConfig { .. }
+                | NameInstruction::SetPaused { .. }
+                | NameInstruction::ReserveName { .. }
+                | NameInstruction::ReleaseName { .. }
+                | NameInstruction::ClearText { .. }
+                | NameInstruction::ClearChainAddress { .. }
+                | NameInstruction::RefundCommitment
+                | NameInstruction::Renew
+                | NameInstruction::Close
+                | NameInstruction::Withdraw
+        )
+    }
+}
+fn unpack_instruction(input: &[u8]) -> Result<Instruction, ProgramError> {
+    if input.len() < 33 || input.len() != 33 + input[32] as usize {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(Instruction {
+        account_address: Pubkey::new(&input[..32]),
+        name: input[33..].to_vec(),
+    })
+}
+
+fn pack_instruction(instruction: &Instruction, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&instruction.account_address.to_bytes());
+    buf.push(instruction.name.len() as u8);
+    buf.extend_from_slice(&instruction.name);
+}
+
+fn unpack_pubkey(input: &[u8]) -> Result<Pubkey, ProgramError> {
+    if input.len() != 32 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(Pubkey::new(input))
+}
+
+fn unpack_text_key(input: u8) -> Result<TextKey, ProgramError> {
+    TextKey::from_u8(input).ok_or(ProgramError::InvalidInstructionData)
+}
+
+// tests
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let instructions = vec![
+            NameInstruction::Register {
+                instruction: Instruction {
+                    account_address: Pubkey::new_unique(),
+                    name: b"name that we want to regsiter 12".to_vec(),
+                },
+                salt: [3; 32],
+            },
+            NameInstruction::Register {
+                instruction: Instruction {
+                    account_address: Pubkey::new_unique(),
+                    name: "a name longer than thirty two bytes \u{1f980}"
+                        .as_bytes()
+                        .to_vec(),
+                },
+                salt: [4; 32],
+            },
+            NameInstruction::Transfer {
+                new_owner: Pubkey::new_unique(),
+            },
+            NameInstruction::UpdateAddress {
+                new_address: Pubkey::new_unique(),
+            },
+            NameInstruction::InitializeConfig {
+                beneficiaries: [
+                    Beneficiary {
+                        address: Pubkey::new_unique(),
+                        share: 7_000,
+                    },
+                    Beneficiary {
+                        address: Pubkey::new_unique(),
+                        share: 3_000,
+                    },
+                    Beneficiary::default(),
+                    Beneficiary::default(),
+                ],
+                fees: [5, 4, 3, 2, 1],
+                registry: Pubkey::new_unique(),
+            },
+            NameInstruction::CreateSubdomain(Instruction {
+                account_address: Pubkey::new_unique(),
+                name: b"pay".to_vec(),
+            }),
+            NameInstruction::SetSubdomainFee { fee: 42 },
+            NameInstruction::SetText {
+                key: TextKey::Github,
+                value: b"Nebula-Wallet".to_vec(),
+            },
+            NameInstruction::ClearText {
+                key: TextKey::Description,
+            },
+            NameInstruction::SetChainAddress {
+                coin_type: 60,
+                address: vec![7; 20],
+            },
+            NameInstruction::ClearChainAddress { coin_type: 0 },
+            NameInstruction::Commit {
+                commitment: [9; 32],
+            },
+            NameInstruction::SetPaused { paused: true },
+            NameInstruction::ReserveName {
+                name_hash: [3; 32],
+                authority: Pubkey::new_unique(),
+            },
+            NameInstruction::ReleaseName { name_hash: [3; 32] },
+            NameInstruction::UpdateConfig {
+                admin: Pubkey::new_unique(),
+                beneficiaries: [Beneficiary {
+                    address: Pubkey::new_unique(),
+                    share: 2_500,
+                }; MAX_BENEFICIARIES],
+                fees: [42; FEE_TIERS],
+                registry: Pubkey::new_unique(),
+            },
+        ];
+        for instruction in instructions {
+            let packed = instruction.pack();
+            assert_eq!(NameInstruction::unpack(&packed).unwrap(), instruction);
+            // Truncated payloads are rejected
+            assert_eq!(
+                NameInstruction::unpack(&packed[..packed.len() - 1]),
+                Err(ProgramError::InvalidInstructionData)
+            );
+        }
+        assert_eq!(
+            NameInstruction::unpack(&[]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            NameInstruction::unpack(&[255]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            NameInstruction::unpack(&[12]).unwrap(),
+            NameInstruction::RefundCommitment
+        );
+        assert_eq!(
+            NameInstruction::unpack(&[13]).unwrap(),
+            NameInstruction::Renew
+        );
+        assert_eq!(
+            NameInstruction::unpack(&[15]).unwrap(),
+            NameInstruction::Withdraw
+        );
+        assert_eq!(
+            NameInstruction::unpack(&[16, 2]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            NameInstruction::unpack(&[12, 0]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        // Unknown text keys are rejected
+        assert_eq!(
+            NameInstruction::unpack(&[8, 6]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+}
@@ -1,69 +1,174 @@
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use name_normalization::{
+    name_extension_len, normalize_name, pack_name, unpack_name, MAX_NAME_EXTENSION_LEN,
+    MAX_NAME_LENGTH, PADDED_NAME_SIZE,
+};
+use name_service_common::{
+    config::ServiceConfig, create_pda_account, find_config_address, find_treasury_address,
+    pack_beneficiaries, pack_fees, unpack_beneficiaries, unpack_fees, Beneficiary,
+    BENEFICIARIES_SIZE, CONFIG_SEED, DEPLOYER_ADDRESS, FEE_TIERS, MAX_BENEFICIARIES, TREASURY_SEED,
+};
+use num_traits::FromPrimitive;
 use solana_program::{
+    clock::Clock,
     entrypoint,
     entrypoint::ProgramResult,
+    hash::{hash, hashv},
     log,
+    program::invoke,
     program_pack::{IsInitialized, Pack, Sealed},
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
 };
 use solana_sdk::{
     account_info::{next_account_info, AccountInfo},
-    program_error::ProgramError,
+    program_error::{PrintProgramError, ProgramError},
     pubkey::Pubkey,
 };
-use std::{str::from_utf8, str::FromStr};
-
-static PAYMENT_ACCOUNT_ADDRESS: &'static str = "Gsun7cGFrSUm3N8TEBq7Uu9xz4c9cE4pKdbtETQiSgZX";
-static COUNTER_POINTER_ADDRESS: &'static str = "H4EsZEY38B5sRwDKuHoXehwPMYZ2EVHMs6M6tixTyEd6";
-static REGISTRATION_FEE: u64 = 1_000_000_000;
-const STORAGE_DATA_SIZE: usize = 73;
-const INSTRUCTION_DATA_SIZE: usize = 64;
-const POINTER_DATA_SIZE: usize = 33;
+use std::str::{from_utf8, FromStr};
+
+pub mod error;
+pub mod instruction;
+use error::NameServiceError;
+use instruction::{Instruction, NameInstruction, TextKey};
+
+/// Program the registry set in the config has to belong to
+static PROXY_POINTER_PROGRAM_ID: &str = "D8FHfdT4EKFabSU967WbDoox594NWJB2iTnJAhAvDvkQ";
+/// Records from before names had owners
+const BASELINE_STORAGE_DATA_SIZE: usize = 73;
+const LEGACY_STORAGE_DATA_SIZE: usize = 105;
+const STORAGE_DATA_SIZE: usize = 153;
+/// Fields following the legacy ones in `AccountRecord`
+const RECORD_EXTRA_SIZE: usize = STORAGE_DATA_SIZE - LEGACY_STORAGE_DATA_SIZE;
+/// Seconds a registration or renewal lasts
+pub const REGISTRATION_PERIOD: i64 = 365 * 24 * 60 * 60;
+/// Seconds after expiry during which only the owner can renew
+pub const GRACE_PERIOD: i64 = 30 * 24 * 60 * 60;
+/// Entries of a proxy-pointer registry, see `RegistryKey` there
+const REGISTRY_KEYS: usize = 4;
+/// Index of the counter entry in the registry
+const REGISTRY_COUNTER: usize = 0;
+/// Replaced entries a proxy-pointer registry keeps, see `HISTORY_LENGTH` there
+const REGISTRY_HISTORY_LENGTH: usize = 8;
+const REGISTRY_DATA_SIZE: usize = 83 + 32 * REGISTRY_KEYS + 49 * REGISTRY_HISTORY_LENGTH;
+const REVERSE_DATA_SIZE: usize = 73;
+const CONFIG_DATA_SIZE: usize = 66 + BENEFICIARIES_SIZE + 8 * FEE_TIERS;
+const NAME_SEED: &[u8] = b"name";
+const REVERSE_SEED: &[u8] = b"reverse";
+const TEXT_SEED: &[u8] = b"text";
+/// Longest text record value in bytes
+pub const MAX_TEXT_LENGTH: usize = 255;
+const TEXT_DATA_SIZE: usize = 67 + MAX_TEXT_LENGTH;
+const ADDRESS_SEED: &[u8] = b"addr";
+const COMMITMENT_SEED: &[u8] = b"commit";
+const COMMITMENT_DATA_SIZE: usize = 41;
+/// Slots that have to pass between `Commit` and `Register`
+pub const MIN_COMMITMENT_AGE: u64 = 1;
+/// Slots after which a commitment can only be refunded, about a day
+pub const MAX_COMMITMENT_AGE: u64 = 216_000;
+/// Longest address record value in bytes
+pub const MAX_CHAIN_ADDRESS_LENGTH: usize = 64;
+const CHAIN_ADDRESS_DATA_SIZE: usize = 70 + MAX_CHAIN_ADDRESS_LENGTH;
+/// SLIP-44 coin types with address validation
+pub const COIN_TYPE_BTC: u32 = 0;
+pub const COIN_TYPE_LTC: u32 = 2;
+pub const COIN_TYPE_DOGE: u32 = 3;
+pub const COIN_TYPE_ETH: u32 = 60;
+pub const COIN_TYPE_ETC: u32 = 61;
+pub const COIN_TYPE_SOL: u32 = 501;
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
-pub struct Pointer {
-    pub token_address: Pubkey,
+pub struct Registry {
     pub is_initialized: bool,
+    pub authority: Pubkey,
+    pub entries: [Pubkey; REGISTRY_KEYS],
 }
-impl Sealed for Pointer {}
-impl IsInitialized for Pointer {
+impl Sealed for Registry {}
+impl IsInitialized for Registry {
     fn is_initialized(&self) -> bool {
         self.is_initialized
     }
 }
 // Copypasta from proxy-pointer
 // error: linking with cc failed: exit code: 1 when using as dependency
-impl Pack for Pointer {
-    const LEN: usize = POINTER_DATA_SIZE;
+// Only the entries are read, history and pending update follow them
+impl Pack for Registry {
+    const LEN: usize = REGISTRY_DATA_SIZE;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, POINTER_DATA_SIZE];
-        let (token_address, is_initialized) = array_refs![src, 32, 1];
-        let token_address = Pubkey::new_from_array(*token_address);
+        let src = array_ref![src, 0, REGISTRY_DATA_SIZE];
+        let (is_initialized, authority, entries_src, _) = array_refs![
+            src,
+            1,
+            32,
+            32 * REGISTRY_KEYS,
+            REGISTRY_DATA_SIZE - 33 - 32 * REGISTRY_KEYS
+        ];
         let is_initialized = match is_initialized {
             [0] => false,
             [1] => true,
             _ => return Err(ProgramError::InvalidAccountData),
         };
-        Ok(Pointer {
-            token_address,
+        let mut entries = [Pubkey::default(); REGISTRY_KEYS];
+        for (entry, src) in entries.iter_mut().zip(entries_src.chunks_exact(32)) {
+            *entry = Pubkey::new(src);
+        }
+        Ok(Registry {
             is_initialized,
+            authority: Pubkey::new_from_array(*authority),
+            entries,
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, POINTER_DATA_SIZE];
-        let (token_address_dst, is_initialized_dst) = mut_array_refs![dst, 32, 1];
-        let &Pointer {
-            ref token_address,
+        let dst = array_mut_ref![dst, 0, REGISTRY_DATA_SIZE];
+        let (is_initialized_dst, authority_dst, entries_dst, _) = mut_array_refs![
+            dst,
+            1,
+            32,
+            32 * REGISTRY_KEYS,
+            REGISTRY_DATA_SIZE - 33 - 32 * REGISTRY_KEYS
+        ];
+        let &Registry {
             is_initialized,
+            ref authority,
+            ref entries,
         } = self;
         is_initialized_dst[0] = is_initialized as u8;
-        *token_address_dst = token_address.to_bytes();
+        *authority_dst = authority.to_bytes();
+        for (entry, dst) in entries.iter().zip(entries_dst.chunks_exact_mut(32)) {
+            dst.copy_from_slice(&entry.to_bytes());
+        }
     }
 }
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
+// Records from before owners end after `index`, records from before
+// subdomains end after `owner`, newer ones continue with the subdomain and
+// expiry fields and then the rest of names longer than the padded head
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct AccountRecord {
     pub account_address: Pubkey,
-    pub name: [u8; 32],
+    pub name: Vec<u8>,
     pub is_initialized: bool,
     pub index: u64,
+    pub owner: Pubkey,
+    /// Record of the parent name, default for top level names
+    pub parent: Pubkey,
+    /// Lamports others pay the owner to create a subdomain, zero when only
+    /// the owner can create them
+    pub subdomain_fee: u64,
+    /// Unix timestamp the name expires at, zero for names that never expire
+    pub expires_at: i64,
+}
+impl AccountRecord {
+    /// Size of the account holding this record
+    pub fn packed_len(&self) -> usize {
+        STORAGE_DATA_SIZE + name_extension_len(&self.name)
+    }
+    /// Name stopped resolving and has to be renewed
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at != 0 && now > self.expires_at
+    }
+    /// Name can be registered, either never was or its grace period is over
+    pub fn is_available(&self, now: i64) -> bool {
+        !self.is_initialized || (self.expires_at != 0 && now > self.expires_at + GRACE_PERIOD)
+    }
 }
 impl Sealed for AccountRecord {}
 impl IsInitialized for AccountRecord {
@@ -74,8 +179,30 @@ impl IsInitialized for AccountRecord {
 impl Pack for AccountRecord {
     const LEN: usize = STORAGE_DATA_SIZE;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, STORAGE_DATA_SIZE];
-        let (account_address, name, is_initialized, index) = array_refs![src, 32, 32, 1, 8];
+        // Only sizes records ever had, anything else would be misread
+        let owner = match src.len() {
+            BASELINE_STORAGE_DATA_SIZE => Pubkey::default(),
+            len if len == LEGACY_STORAGE_DATA_SIZE || len >= STORAGE_DATA_SIZE => {
+                Pubkey::new_from_array(*array_ref![src, BASELINE_STORAGE_DATA_SIZE, 32])
+            }
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let (parent, subdomain_fee, expires_at, extension) = if src.len() < STORAGE_DATA_SIZE {
+            (Pubkey::default(), 0, 0, &[][..])
+        } else {
+            let extra = array_ref![src, LEGACY_STORAGE_DATA_SIZE, RECORD_EXTRA_SIZE];
+            let (parent, subdomain_fee, expires_at) = array_refs![extra, 32, 8, 8];
+            (
+                Pubkey::new_from_array(*parent),
+                u64::from_le_bytes(*subdomain_fee),
+                i64::from_le_bytes(*expires_at),
+                &src[STORAGE_DATA_SIZE..],
+            )
+        };
+        let src = array_ref![src, 0, BASELINE_STORAGE_DATA_SIZE];
+        let (account_address, name, is_initialized, index) =
+            array_refs![src, 32, PADDED_NAME_SIZE, 1, 8];
+        let name = unpack_name(name, extension).ok_or(ProgramError::InvalidAccountData)?;
         let account_address = Pubkey::new_from_array(*account_address);
         let is_initialized = match is_initialized {
             [0] => false,
@@ -85,60 +212,344 @@ impl Pack for AccountRecord {
         let index = u64::from_le_bytes(*index);
         Ok(AccountRecord {
             account_address,
-            name: *name,
+            name,
             is_initialized,
             index,
+            owner,
+            parent,
+            subdomain_fee,
+            expires_at,
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, STORAGE_DATA_SIZE];
+        let (dst, rest) = dst.split_at_mut(BASELINE_STORAGE_DATA_SIZE);
+        let dst = array_mut_ref![dst, 0, BASELINE_STORAGE_DATA_SIZE];
         let (account_address_dst, name_dst, is_initialized_dst, index_dst) =
-            mut_array_refs![dst, 32, 32, 1, 8];
+            mut_array_refs![dst, 32, PADDED_NAME_SIZE, 1, 8];
 
         let &AccountRecord {
             ref account_address,
-            name,
+            ref name,
             is_initialized,
             index,
+            ref owner,
+            ref parent,
+            subdomain_fee,
+            expires_at,
         } = self;
         is_initialized_dst[0] = is_initialized as u8;
         *account_address_dst = account_address.to_bytes();
-        *name_dst = name;
         *index_dst = index.to_le_bytes();
+        // Records from before owners and subdomains keep their size
+        if rest.is_empty() {
+            pack_name(name, name_dst, rest);
+            return;
+        }
+        let (owner_dst, rest) = rest.split_at_mut(32);
+        owner_dst.copy_from_slice(&owner.to_bytes());
+        if rest.is_empty() {
+            pack_name(name, name_dst, rest);
+            return;
+        }
+        let (extra_dst, extension_dst) = rest.split_at_mut(RECORD_EXTRA_SIZE);
+        let extra_dst = array_mut_ref![extra_dst, 0, RECORD_EXTRA_SIZE];
+        let (parent_dst, subdomain_fee_dst, expires_at_dst) = mut_array_refs![extra_dst, 32, 8, 8];
+        *parent_dst = parent.to_bytes();
+        *subdomain_fee_dst = subdomain_fee.to_le_bytes();
+        *expires_at_dst = expires_at.to_le_bytes();
+        pack_name(name, name_dst, extension_dst);
     }
 }
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
-pub struct Instruction {
+// Reverse lookup, keyed by the registered address. Long names continue
+// after the fixed fields like in `AccountRecord`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReverseRecord {
     pub account_address: Pubkey,
-    pub name: [u8; 32],
+    pub name: Vec<u8>,
+    pub is_initialized: bool,
+    pub index: u64,
+}
+impl ReverseRecord {
+    /// Size of the account holding this record
+    pub fn packed_len(&self) -> usize {
+        REVERSE_DATA_SIZE + name_extension_len(&self.name)
+    }
 }
-impl Sealed for Instruction {}
-impl IsInitialized for Instruction {
+impl Sealed for ReverseRecord {}
+impl IsInitialized for ReverseRecord {
     fn is_initialized(&self) -> bool {
-        true
+        self.is_initialized
     }
 }
-impl Pack for Instruction {
-    const LEN: usize = INSTRUCTION_DATA_SIZE;
+impl Pack for ReverseRecord {
+    const LEN: usize = REVERSE_DATA_SIZE;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, INSTRUCTION_DATA_SIZE];
-        let (account_address, name) = array_refs![src, 32, 32];
+        if src.len() < REVERSE_DATA_SIZE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let extension = &src[REVERSE_DATA_SIZE..];
+        let src = array_ref![src, 0, REVERSE_DATA_SIZE];
+        let (account_address, name, is_initialized, index) =
+            array_refs![src, 32, PADDED_NAME_SIZE, 1, 8];
+        let name = unpack_name(name, extension).ok_or(ProgramError::InvalidAccountData)?;
         let account_address = Pubkey::new_from_array(*account_address);
-        Ok(Instruction {
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let index = u64::from_le_bytes(*index);
+        Ok(ReverseRecord {
             account_address,
-            name: *name,
+            name,
+            is_initialized,
+            index,
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, INSTRUCTION_DATA_SIZE];
-        let (account_address_dst, name_dst) = mut_array_refs![dst, 32, 32];
+        let (dst, extension_dst) = dst.split_at_mut(REVERSE_DATA_SIZE);
+        let dst = array_mut_ref![dst, 0, REVERSE_DATA_SIZE];
+        let (account_address_dst, name_dst, is_initialized_dst, index_dst) =
+            mut_array_refs![dst, 32, PADDED_NAME_SIZE, 1, 8];
 
-        let &Instruction {
+        let &ReverseRecord {
             ref account_address,
-            name,
+            ref name,
+            is_initialized,
+            index,
         } = self;
+        is_initialized_dst[0] = is_initialized as u8;
         *account_address_dst = account_address.to_bytes();
-        *name_dst = name;
+        pack_name(name, name_dst, extension_dst);
+        *index_dst = index.to_le_bytes();
+    }
+}
+// Text value of a name under one key, stored as one byte of length and the
+// value padded to `MAX_TEXT_LENGTH`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextRecord {
+    pub is_initialized: bool,
+    /// Name record this value belongs to
+    pub record: Pubkey,
+    /// Owner of the name when the value was set, it is stale once the name
+    /// has another owner
+    pub owner: Pubkey,
+    pub key: TextKey,
+    pub value: Vec<u8>,
+}
+impl Sealed for TextRecord {}
+impl IsInitialized for TextRecord {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for TextRecord {
+    const LEN: usize = TEXT_DATA_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, TEXT_DATA_SIZE];
+        let (is_initialized, record, owner, key, length, value) =
+            array_refs![src, 1, 32, 32, 1, 1, MAX_TEXT_LENGTH];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let key = TextKey::from_u8(key[0]).ok_or(ProgramError::InvalidAccountData)?;
+        Ok(TextRecord {
+            is_initialized,
+            record: Pubkey::new_from_array(*record),
+            owner: Pubkey::new_from_array(*owner),
+            key,
+            value: value[..length[0] as usize].to_vec(),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, TEXT_DATA_SIZE];
+        let (is_initialized_dst, record_dst, owner_dst, key_dst, length_dst, value_dst) =
+            mut_array_refs![dst, 1, 32, 32, 1, 1, MAX_TEXT_LENGTH];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        *record_dst = self.record.to_bytes();
+        *owner_dst = self.owner.to_bytes();
+        key_dst[0] = self.key as u8;
+        length_dst[0] = self.value.len() as u8;
+        *value_dst = [0; MAX_TEXT_LENGTH];
+        value_dst[..self.value.len()].copy_from_slice(&self.value);
+    }
+}
+// Address of a name on another chain, stored as one byte of length and the
+// address padded to `MAX_CHAIN_ADDRESS_LENGTH`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChainAddressRecord {
+    pub is_initialized: bool,
+    /// Name record this address belongs to
+    pub record: Pubkey,
+    /// Owner of the name when the address was set, it is stale once the
+    /// name has another owner
+    pub owner: Pubkey,
+    /// SLIP-44 coin type of the chain
+    pub coin_type: u32,
+    pub address: Vec<u8>,
+}
+impl Sealed for ChainAddressRecord {}
+impl IsInitialized for ChainAddressRecord {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for ChainAddressRecord {
+    const LEN: usize = CHAIN_ADDRESS_DATA_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, CHAIN_ADDRESS_DATA_SIZE];
+        let (is_initialized, record, owner, coin_type, length, address) =
+            array_refs![src, 1, 32, 32, 4, 1, MAX_CHAIN_ADDRESS_LENGTH];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let address = address
+            .get(..length[0] as usize)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        Ok(ChainAddressRecord {
+            is_initialized,
+            record: Pubkey::new_from_array(*record),
+            owner: Pubkey::new_from_array(*owner),
+            coin_type: u32::from_le_bytes(*coin_type),
+            address: address.to_vec(),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, CHAIN_ADDRESS_DATA_SIZE];
+        let (is_initialized_dst, record_dst, owner_dst, coin_type_dst, length_dst, address_dst) =
+            mut_array_refs![dst, 1, 32, 32, 4, 1, MAX_CHAIN_ADDRESS_LENGTH];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        *record_dst = self.record.to_bytes();
+        *owner_dst = self.owner.to_bytes();
+        *coin_type_dst = self.coin_type.to_le_bytes();
+        length_dst[0] = self.address.len() as u8;
+        *address_dst = [0; MAX_CHAIN_ADDRESS_LENGTH];
+        address_dst[..self.address.len()].copy_from_slice(&self.address);
+    }
+}
+// Hidden registration intent, see `commitment_hash`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Commitment {
+    pub is_initialized: bool,
+    pub owner: Pubkey,
+    /// Slot the commitment was made in
+    pub slot: u64,
+}
+impl Sealed for Commitment {}
+impl IsInitialized for Commitment {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for Commitment {
+    const LEN: usize = COMMITMENT_DATA_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, COMMITMENT_DATA_SIZE];
+        let (is_initialized, owner, slot) = array_refs![src, 1, 32, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Commitment {
+            is_initialized,
+            owner: Pubkey::new_from_array(*owner),
+            slot: u64::from_le_bytes(*slot),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, COMMITMENT_DATA_SIZE];
+        let (is_initialized_dst, owner_dst, slot_dst) = mut_array_refs![dst, 1, 32, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        *owner_dst = self.owner.to_bytes();
+        *slot_dst = self.slot.to_le_bytes();
+    }
+}
+// Program wide settings, managed by admin
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Config {
+    pub is_initialized: bool,
+    pub admin: Pubkey,
+    /// Accounts the treasury is split between on withdraw
+    pub beneficiaries: [Beneficiary; MAX_BENEFICIARIES],
+    /// Registration fee in lamports, indexed by name length, see `fee_for`
+    pub fees: [u64; FEE_TIERS],
+    /// Registrations and updates are refused while set
+    pub paused: bool,
+    /// Proxy-pointer registry whose counter entry numbers registrations
+    pub registry: Pubkey,
+}
+impl ServiceConfig for Config {
+    type Error = NameServiceError;
+    fn admin(&self) -> &Pubkey {
+        &self.admin
+    }
+    fn beneficiaries(&self) -> &[Beneficiary; MAX_BENEFICIARIES] {
+        &self.beneficiaries
+    }
+    fn fees(&self) -> &[u64; FEE_TIERS] {
+        &self.fees
+    }
+    fn paused(&self) -> bool {
+        self.paused
+    }
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused
+    }
+}
+impl Sealed for Config {}
+impl IsInitialized for Config {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for Config {
+    const LEN: usize = CONFIG_DATA_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, CONFIG_DATA_SIZE];
+        let (is_initialized, admin, beneficiaries, fees, paused, registry) =
+            array_refs![src, 1, 32, BENEFICIARIES_SIZE, 8 * FEE_TIERS, 1, 32];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let paused = match paused {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Config {
+            is_initialized,
+            admin: Pubkey::new_from_array(*admin),
+            beneficiaries: unpack_beneficiaries(beneficiaries),
+            fees: unpack_fees(fees),
+            paused,
+            registry: Pubkey::new_from_array(*registry),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, CONFIG_DATA_SIZE];
+        let (is_initialized_dst, admin_dst, beneficiaries_dst, fees_dst, paused_dst, registry_dst) =
+            mut_array_refs![dst, 1, 32, BENEFICIARIES_SIZE, 8 * FEE_TIERS, 1, 32];
+        let &Config {
+            is_initialized,
+            ref admin,
+            ref beneficiaries,
+            fees,
+            paused,
+            ref registry,
+        } = self;
+        is_initialized_dst[0] = is_initialized as u8;
+        *admin_dst = admin.to_bytes();
+        pack_beneficiaries(beneficiaries, beneficiaries_dst);
+        pack_fees(&fees, fees_dst);
+        paused_dst[0] = paused as u8;
+        *registry_dst = registry.to_bytes();
     }
 }
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
@@ -167,6 +578,181 @@ impl Pack for Counter {
         *index_dst = index.to_le_bytes();
     }
 }
+/// Seed of the record holding `name`. Names that fit a seed are NUL padded,
+/// longer ones are hashed.
+pub fn name_seed(name: &[u8]) -> [u8; 32] {
+    if name.len() > PADDED_NAME_SIZE {
+        return hash(name).to_bytes();
+    }
+    let mut seed = [0u8; 32];
+    seed[..name.len()].copy_from_slice(name);
+    seed
+}
+/// Address of the record account holding `name`, which has to be normalized.
+/// Only one record can exist per normalized name, which is what makes names
+/// unique.
+pub fn find_name_record_address(name: &[u8], program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[NAME_SEED, &name_seed(name)], program_id)
+}
+/// Address of the record holding subdomain `name` under `parent` record
+pub fn find_subdomain_record_address(
+    parent: &Pubkey,
+    name: &[u8],
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[NAME_SEED, &parent.to_bytes(), &name_seed(name)],
+        program_id,
+    )
+}
+/// Address of the record for a full dotted name like "pay.nebula", derived
+/// label by label starting from the top level name
+pub fn find_record_address_for_name(name: &str, program_id: &Pubkey) -> (Pubkey, u8) {
+    let mut labels = name.rsplit('.');
+    let top_level = labels.next().unwrap_or_default();
+    let mut address = find_name_record_address(top_level.as_bytes(), program_id);
+    for label in labels {
+        address = find_subdomain_record_address(&address.0, label.as_bytes(), program_id);
+    }
+    address
+}
+/// Resolves a full dotted name to the address it points at by walking down
+/// from the top level name. `get_record` fetches a record account, every
+/// level has to be registered, not expired at unix timestamp `now` and
+/// linked to the level above it.
+pub fn resolve_name<F>(name: &str, now: i64, program_id: &Pubkey, get_record: F) -> Option<Pubkey>
+where
+    F: Fn(&Pubkey) -> Option<AccountRecord>,
+{
+    resolve_record(name, now, program_id, get_record).map(|(_, record)| record.account_address)
+}
+/// Resolves a full dotted name like `resolve_name` to its address on the chain with SLIP-44
+/// `coin_type`. Address records set by an earlier owner of the name are
+/// ignored. Solana names without their own `COIN_TYPE_SOL` record resolve to
+/// the address of the name record.
+pub fn resolve_chain_address<F, G>(
+    name: &str,
+    now: i64,
+    coin_type: u32,
+    program_id: &Pubkey,
+    get_record: F,
+    get_chain_address: G,
+) -> Option<Vec<u8>>
+where
+    F: Fn(&Pubkey) -> Option<AccountRecord>,
+    G: Fn(&Pubkey) -> Option<ChainAddressRecord>,
+{
+    let (record_address, record) = resolve_record(name, now, program_id, get_record)?;
+    let (address, _) = find_chain_address_record_address(&record_address, coin_type, program_id);
+    match get_chain_address(&address) {
+        Some(chain_address)
+            if chain_address.is_initialized
+                && chain_address.record == record_address
+                && chain_address.owner == record.owner =>
+        {
+            Some(chain_address.address)
+        }
+        _ if coin_type == COIN_TYPE_SOL => Some(record.account_address.to_bytes().to_vec()),
+        _ => None,
+    }
+}
+fn resolve_record<F>(
+    name: &str,
+    now: i64,
+    program_id: &Pubkey,
+    get_record: F,
+) -> Option<(Pubkey, AccountRecord)>
+where
+    F: Fn(&Pubkey) -> Option<AccountRecord>,
+{
+    let mut parent = Pubkey::default();
+    let mut resolved: Option<(Pubkey, AccountRecord)> = None;
+    for label in name.rsplit('.') {
+        let label = normalize_name(label.as_bytes()).ok()?;
+        let (address, _) = if parent == Pubkey::default() {
+            find_name_record_address(label.as_bytes(), program_id)
+        } else {
+            find_subdomain_record_address(&parent, label.as_bytes(), program_id)
+        };
+        let record = get_record(&address)?;
+        if !record.is_initialized || record.is_expired(now) || record.parent != parent {
+            return None;
+        }
+        // Subdomains left over from an earlier registration of the parent
+        if let Some((_, parent_record)) = &resolved {
+            if record.index != parent_record.index {
+                return None;
+            }
+        }
+        parent = address;
+        resolved = Some((address, record));
+    }
+    resolved
+}
+/// Address of the `key` text record of a name record
+pub fn find_text_record_address(
+    record: &Pubkey,
+    key: TextKey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TEXT_SEED, &record.to_bytes(), &[key as u8]], program_id)
+}
+/// Address of the `coin_type` address record of a name record
+pub fn find_chain_address_record_address(
+    record: &Pubkey,
+    coin_type: u32,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ADDRESS_SEED, &record.to_bytes(), &coin_type.to_le_bytes()],
+        program_id,
+    )
+}
+/// Checks `address` has a valid length and format for `coin_type`. Bitcoin
+/// style chains take the output script, like ENS does, other known chains
+/// their raw address bytes and unknown ones anything up to
+/// `MAX_CHAIN_ADDRESS_LENGTH` bytes.
+pub fn is_valid_chain_address(coin_type: u32, address: &[u8]) -> bool {
+    match coin_type {
+        COIN_TYPE_BTC | COIN_TYPE_LTC | COIN_TYPE_DOGE => match address {
+            // P2PKH
+            [0x76, 0xa9, 0x14, hash @ .., 0x88, 0xac] => hash.len() == 20,
+            // P2SH
+            [0xa9, 0x14, hash @ .., 0x87] => hash.len() == 20,
+            // Segwit, version 0 or 1 to 16 and a 2 to 40 byte program
+            [version, length, program @ ..] => {
+                (*version == 0 || (0x51..=0x60).contains(version))
+                    && (2..=40).contains(length)
+                    && program.len() == *length as usize
+            }
+            _ => false,
+        },
+        COIN_TYPE_ETH | COIN_TYPE_ETC => address.len() == 20,
+        COIN_TYPE_SOL => address.len() == 32,
+        _ => !address.is_empty() && address.len() <= MAX_CHAIN_ADDRESS_LENGTH,
+    }
+}
+/// Commitment to register normalized `name` for `owner`, `salt` keeps it
+/// from being guessed
+pub fn commitment_hash(name: &[u8], salt: &[u8; 32], owner: &Pubkey) -> [u8; 32] {
+    hashv(&[name, salt, &owner.to_bytes()]).to_bytes()
+}
+/// Address of the account holding `commitment` made by `owner`
+pub fn find_commitment_address(
+    owner: &Pubkey,
+    commitment: &[u8; 32],
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[COMMITMENT_SEED, &owner.to_bytes(), commitment],
+        program_id,
+    )
+}
+/// Address of the reverse record for `account_address`, which holds the
+/// name most recently registered for that address.
+pub fn find_reverse_record_address(account_address: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REVERSE_SEED, &account_address.to_bytes()], program_id)
+}
 entrypoint!(process_instruction);
 
 // Program entrypoint's implementation
@@ -176,121 +762,1170 @@ fn process_instruction(
     instruction_data: &[u8],
 ) -> ProgramResult {
     log::sol_log("Rust program entrypoint");
+    let instruction = NameInstruction::unpack(instruction_data)?;
+    if instruction.is_pausable() {
+        Config::check_not_paused(program_id, accounts)?;
+    }
+    let result = match instruction {
+        NameInstruction::Register { instruction, salt } => {
+            process_register(program_id, accounts, instruction, salt)
+        }
+        NameInstruction::Transfer { new_owner } => {
+            process_transfer(program_id, accounts, new_owner)
+        }
+        NameInstruction::UpdateAddress { new_address } => {
+            process_update_address(program_id, accounts, new_address)
+        }
+        NameInstruction::InitializeConfig {
+            beneficiaries,
+            fees,
+            registry,
+        } => process_initialize_config(program_id, accounts, beneficiaries, fees, registry),
+        NameInstruction::UpdateConfig {
+            admin,
+            beneficiaries,
+            fees,
+            registry,
+        } => process_update_config(program_id, accounts, admin, beneficiaries, fees, registry),
+        NameInstruction::CreateSubdomain(instruction) => {
+            process_create_subdomain(program_id, accounts, instruction)
+        }
+        NameInstruction::SetSubdomainFee { fee } => {
+            process_set_subdomain_fee(program_id, accounts, fee)
+        }
+        NameInstruction::SetText { key, value } => {
+            process_set_text(program_id, accounts, key, value)
+        }
+        NameInstruction::ClearText { key } => process_clear_text(program_id, accounts, key),
+        NameInstruction::SetChainAddress { coin_type, address } => {
+            process_set_chain_address(program_id, accounts, coin_type, address)
+        }
+        NameInstruction::ClearChainAddress { coin_type } => {
+            process_clear_chain_address(program_id, accounts, coin_type)
+        }
+        NameInstruction::Commit { commitment } => process_commit(program_id, accounts, commitment),
+        NameInstruction::RefundCommitment => process_refund_commitment(program_id, accounts),
+        NameInstruction::Renew => process_renew(program_id, accounts),
+        NameInstruction::Close => process_close(program_id, accounts),
+        NameInstruction::Withdraw => Config::process_withdraw(program_id, accounts),
+        NameInstruction::SetPaused { paused } => {
+            Config::process_set_paused(program_id, accounts, paused)
+        }
+        NameInstruction::ReserveName {
+            name_hash,
+            authority,
+        } => Config::process_reserve_name(program_id, accounts, name_hash, authority),
+        NameInstruction::ReleaseName { name_hash } => {
+            Config::process_release_name(program_id, accounts, name_hash)
+        }
+    };
+    if let Err(error) = &result {
+        error.print::<NameServiceError>();
+    }
+    result
+}
 
+fn process_register(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: Instruction,
+    salt: [u8; 32],
+) -> ProgramResult {
     let accounts_iter = &mut accounts.iter();
+    let config = Config::load(program_id, next_account_info(accounts_iter)?)?;
     let payment_account = next_account_info(accounts_iter)?;
 
     // Validate payment account
-    if payment_account.key.to_bytes()
-        != Pubkey::from_str(PAYMENT_ACCOUNT_ADDRESS)
-            .unwrap()
-            .to_bytes()
-    {
-        log::sol_log("Invalid payment_account");
-        return Err(ProgramError::InvalidAccountData);
-    }
-    let counter_pointer = next_account_info(accounts_iter)?;
-    if counter_pointer.key.to_bytes()
-        != Pubkey::from_str(COUNTER_POINTER_ADDRESS)
-            .unwrap()
-            .to_bytes()
-    {
-        log::sol_log("Invalid counter_pointer");
-        return Err(ProgramError::InvalidAccountData);
+    if *payment_account.key != find_treasury_address(program_id).0 {
+        return Err(NameServiceError::InvalidPaymentAccount.into());
+    }
+    let registry_account = next_account_info(accounts_iter)?;
+    if *registry_account.key != config.registry {
+        return Err(NameServiceError::InvalidRegistry.into());
+    }
+    if *registry_account.owner != Pubkey::from_str(PROXY_POINTER_PROGRAM_ID).unwrap() {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let registry_data = registry_account.try_borrow_data()?;
+    if registry_data.len() != REGISTRY_DATA_SIZE {
+        return Err(NameServiceError::RegistryNotInitialized.into());
     }
-    let counter_pointer_data = counter_pointer.try_borrow_data()?;
-    let counter_pointer_data = Pointer::unpack_from_slice(&counter_pointer_data)?;
-    if counter_pointer_data.is_initialized == false {
-        log::sol_log("counter pointer is not initialized");
-        return Err(ProgramError::InvalidAccountData);
+    let registry = Registry::unpack_from_slice(&registry_data)?;
+    if !registry.is_initialized {
+        return Err(NameServiceError::RegistryNotInitialized.into());
     }
     let counter = next_account_info(accounts_iter)?;
-    if counter.key.to_bytes() != counter_pointer_data.token_address.to_bytes() {
-        log::sol_log("Invalid counter address");
-        return Err(ProgramError::InvalidAccountData);
+    if *counter.key != registry.entries[REGISTRY_COUNTER] {
+        return Err(NameServiceError::InvalidCounterAccount.into());
     }
+    let name = normalize_name(&instruction_data.name).map_err(NameServiceError::from)?;
+    // Shorter names are more expensive, length is counted in characters
+    let fee = config.fee_for(name.chars().count());
+
+    // Record must live at the address derived from the name
+    let storage_account = next_account_info(accounts_iter)?;
+    let reverse_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    let commitment_account = next_account_info(accounts_iter)?;
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    let reserved_account = next_account_info(accounts_iter)?;
+    if !payer.is_signer {
+        log::sol_log("Transaction need to be signed by payer");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Config::check_reserved_name(
+        program_id,
+        name.as_bytes(),
+        reserved_account,
+        accounts_iter.next(),
+    )?;
+    // Name has to be revealed within the window of a commitment by the payer
+    let commitment = load_commitment(
+        program_id,
+        commitment_account,
+        payer,
+        &commitment_hash(name.as_bytes(), &salt, payer.key),
+    )?;
+    let age = clock.slot.saturating_sub(commitment.slot);
+    if age < MIN_COMMITMENT_AGE {
+        return Err(NameServiceError::CommitmentTooNew.into());
+    }
+    if age > MAX_COMMITMENT_AGE {
+        return Err(NameServiceError::CommitmentExpired.into());
+    }
+    // Payer owns the name, index is taken from the counter once it is free
+    let mut account_record = AccountRecord {
+        is_initialized: true,
+        account_address: instruction_data.account_address,
+        name: name.into_bytes(),
+        index: 0,
+        owner: *payer.key,
+        parent: Pubkey::default(),
+        subdomain_fee: 0,
+        expires_at: clock.unix_timestamp + REGISTRATION_PERIOD,
+    };
+    let (record_address, bump_seed) = find_name_record_address(&account_record.name, program_id);
+    if *storage_account.key != record_address {
+        return Err(NameServiceError::InvalidRecordAddress.into());
+    }
+    if storage_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            payer,
+            storage_account,
+            system_program,
+            rent.minimum_balance(account_record.packed_len()),
+            account_record.packed_len(),
+            &[NAME_SEED, &name_seed(&account_record.name), &[bump_seed]],
+        )?;
+    } else {
+        take_over_record(storage_account, &clock)?;
+    }
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    check_rent_exempt(storage_account, &rent)?;
+    let mut storage_data = storage_account.try_borrow_mut_data()?;
+    if storage_data.len().lt(&account_record.packed_len()) {
+        return Err(NameServiceError::AccountDataTooSmall.into());
+    }
+    charge_fee(payer, payment_account, system_program, fee)?;
     let mut counter_data = counter.try_borrow_mut_data()?;
     let counter_value_slice = array_ref![counter_data, 0, 8];
     let mut counter = Counter::unpack_from_slice(counter_value_slice)?;
     // Increment counter
-    counter.index = counter.index + 1;
+    counter.index += 1;
     log::sol_log(&counter.index.to_string());
     Counter::pack_into_slice(&counter, &mut counter_data);
     println!("Current counter:{:?}", counter.index);
-    // Parse instruction
-    let instruction_data = Instruction::unpack_from_slice(instruction_data)?;
+    account_record.index = counter.index;
 
-    let account_record = AccountRecord {
+    // Save data
+    AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+    // Latest registration signed by the address wins its reverse lookup
+    write_reverse_record(
+        program_id,
+        &account_record,
+        reverse_account,
+        payer,
+        system_program,
+        &rent,
+        accounts,
+    )?;
+    close_account(commitment_account, payer)
+}
+
+/// Points the reverse record of `account_record.account_address` at the
+/// record's name, creating the reverse account when it does not exist yet.
+/// Only the address itself can claim its reverse lookup, unless it signed
+/// as one of `accounts` the reverse record is left as it is
+fn write_reverse_record<'a>(
+    program_id: &Pubkey,
+    account_record: &AccountRecord,
+    reverse_account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    rent: &Rent,
+    accounts: &[AccountInfo<'a>],
+) -> ProgramResult {
+    let (reverse_address, reverse_bump_seed) =
+        find_reverse_record_address(&account_record.account_address, program_id);
+    if *reverse_account.key != reverse_address {
+        return Err(NameServiceError::InvalidReverseAddress.into());
+    }
+    if !accounts
+        .iter()
+        .any(|account| *account.key == account_record.account_address && account.is_signer)
+    {
+        return Ok(());
+    }
+    if reverse_account.data_is_empty() {
+        // Room for the longest name, an address can point at another name later
+        create_pda_account(
+            program_id,
+            payer,
+            reverse_account,
+            system_program,
+            rent.minimum_balance(REVERSE_DATA_SIZE + MAX_NAME_EXTENSION_LEN),
+            REVERSE_DATA_SIZE + MAX_NAME_EXTENSION_LEN,
+            &[
+                REVERSE_SEED,
+                &account_record.account_address.to_bytes(),
+                &[reverse_bump_seed],
+            ],
+        )?;
+    }
+    if reverse_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let reverse_record = ReverseRecord {
         is_initialized: true,
-        account_address: instruction_data.account_address,
-        name: instruction_data.name,
-        index: counter.index,
+        account_address: account_record.account_address,
+        name: account_record.name.clone(),
+        index: account_record.index,
     };
-    // Save new record
+    let mut reverse_data = reverse_account.try_borrow_mut_data()?;
+    if reverse_data.len().lt(&reverse_record.packed_len()) {
+        return Err(NameServiceError::AccountDataTooSmall.into());
+    }
+    ReverseRecord::pack_into_slice(&reverse_record, &mut reverse_data);
+    Ok(())
+}
+
+fn process_transfer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_owner: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
     let storage_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
     if storage_account.owner != program_id {
-        log::sol_log("storage_account must be owned by program");
-        return Err(ProgramError::InvalidAccountData);
+        return Err(NameServiceError::IncorrectAccountOwner.into());
     }
     let mut storage_data = storage_account.try_borrow_mut_data()?;
-    // I should add special field but is good enough for now
-    if storage_data[64] != 0 {
-        log::sol_log("Account data is not empty.");
-        return Err(ProgramError::InvalidAccountData);
+    let mut account_record = AccountRecord::unpack_from_slice(&storage_data)?;
+    check_record_owner(&account_record, owner)?;
+    account_record.owner = new_owner;
+    AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+    Ok(())
+}
+
+fn process_update_address(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_address: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let old_reverse_account = next_account_info(accounts_iter)?;
+    let new_reverse_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
     }
-    if storage_data.len().lt(&STORAGE_DATA_SIZE) {
-        log::sol_log("Account data field is insufficient");
-        return Err(ProgramError::InvalidAccountData);
+    let mut storage_data = storage_account.try_borrow_mut_data()?;
+    let mut account_record = AccountRecord::unpack_from_slice(&storage_data)?;
+    check_record_owner(&account_record, owner)?;
+    if !payer.is_signer {
+        log::sol_log("Transaction need to be signed by payer");
+        return Err(ProgramError::MissingRequiredSignature);
     }
 
-    // Transfer fee
-    let mut storage_account_balance = storage_account.try_borrow_mut_lamports()?;
-    if **storage_account_balance < REGISTRATION_FEE {
-        log::sol_log("InsufficientFunds in storage_account");
-        return Err(ProgramError::InsufficientFunds);
+    // Old address no longer resolves to this name
+    let (old_reverse_address, _) =
+        find_reverse_record_address(&account_record.account_address, program_id);
+    if *old_reverse_account.key != old_reverse_address {
+        return Err(NameServiceError::InvalidReverseAddress.into());
+    }
+    if old_reverse_account.owner == program_id {
+        let mut old_reverse_data = old_reverse_account.try_borrow_mut_data()?;
+        if old_reverse_data.len() >= REVERSE_DATA_SIZE {
+            let old_reverse_record = ReverseRecord::unpack_from_slice(&old_reverse_data)?;
+            if old_reverse_record.is_initialized && old_reverse_record.name == account_record.name {
+                ReverseRecord::pack_into_slice(&ReverseRecord::default(), &mut old_reverse_data);
+            }
+        }
     }
-    let mut payment_account_balance = payment_account.try_borrow_mut_lamports()?;
 
-    **storage_account_balance = storage_account_balance.wrapping_sub(REGISTRATION_FEE);
-    **payment_account_balance = payment_account_balance.wrapping_add(REGISTRATION_FEE);
-    // Save data
+    account_record.account_address = new_address;
     AccountRecord::pack_into_slice(&account_record, &mut storage_data);
-    Ok(())
+    write_reverse_record(
+        program_id,
+        &account_record,
+        new_reverse_account,
+        payer,
+        system_program,
+        &rent,
+        accounts,
+    )
 }
 
-// tests
-#[cfg(test)]
-mod test {
-    use std::str::FromStr;
-
-    use super::*;
-    use solana_sdk::clock::Epoch;
-    #[test]
-    fn test_flow() {
-        // mock program id
-
-        let program_id = Pubkey::default();
+fn process_create_subdomain(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: Instruction,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let parent_account = next_account_info(accounts_iter)?;
+    let parent_owner = next_account_info(accounts_iter)?;
+    let storage_account = next_account_info(accounts_iter)?;
+    let reverse_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    if parent_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let parent = AccountRecord::unpack_from_slice(&parent_account.try_borrow_data()?)?;
+    if !parent.is_initialized {
+        return Err(NameServiceError::NameNotRegistered.into());
+    }
+    if parent.is_expired(clock.unix_timestamp) {
+        return Err(NameServiceError::NameExpired.into());
+    }
+    if parent.owner != *parent_owner.key {
+        return Err(NameServiceError::NotNameOwner.into());
+    }
+    if !payer.is_signer {
+        log::sol_log("Transaction need to be signed by payer");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // Parent owner creates subdomains for free, others pay the parent's fee
+    let fee = if payer.key == parent_owner.key {
+        0
+    } else if parent.subdomain_fee == 0 {
+        return Err(NameServiceError::SubdomainsClosed.into());
+    } else {
+        parent.subdomain_fee
+    };
+    let label = normalize_name(&instruction_data.name).map_err(NameServiceError::from)?;
+    let name = [label.as_bytes(), b".", &parent.name].concat();
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(NameServiceError::InvalidName.into());
+    }
 
-        let mut empty_data = vec![0; 0];
-        let mut balance_payment_account = 0;
-        let mut zero_balance = 0;
-        let mut balance_minter_token = 0;
-        let mut balance_storage_account = REGISTRATION_FEE + 1;
-        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
-        let mut counter_pointer_data = vec![0; POINTER_DATA_SIZE];
+    // Subdomains carry the index and current term of the top level
+    // registration they belong to, so they go away with it
+    let account_record = AccountRecord {
+        is_initialized: true,
+        account_address: instruction_data.account_address,
+        name,
+        index: parent.index,
+        owner: *payer.key,
+        parent: *parent_account.key,
+        subdomain_fee: 0,
+        expires_at: parent.expires_at,
+    };
+    let (record_address, bump_seed) =
+        find_subdomain_record_address(parent_account.key, label.as_bytes(), program_id);
+    if *storage_account.key != record_address {
+        return Err(NameServiceError::InvalidRecordAddress.into());
+    }
+    if storage_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            payer,
+            storage_account,
+            system_program,
+            rent.minimum_balance(account_record.packed_len()),
+            account_record.packed_len(),
+            &[
+                NAME_SEED,
+                &parent_account.key.to_bytes(),
+                &name_seed(label.as_bytes()),
+                &[bump_seed],
+            ],
+        )?;
+    } else {
+        take_over_record(storage_account, &clock)?;
+    }
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    check_rent_exempt(storage_account, &rent)?;
+    let mut storage_data = storage_account.try_borrow_mut_data()?;
+    if storage_data.len().lt(&account_record.packed_len()) {
+        return Err(NameServiceError::AccountDataTooSmall.into());
+    }
 
-        let counter_address =
-            Pubkey::from_str("FJNj5YDJVT3pbtiZsMMeRPJumM35iF71rUzDQWC7CXqq").unwrap();
-        let pointer = Pointer {
-            is_initialized: true,
-            token_address: counter_address,
-        };
-        Pointer::pack_into_slice(&pointer, &mut counter_pointer_data);
-        let mut counter_initial_data = vec![0; 8];
+    // Fee goes to the parent owner
+    charge_fee(payer, parent_owner, system_program, fee)?;
+    AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+    write_reverse_record(
+        program_id,
+        &account_record,
+        reverse_account,
+        payer,
+        system_program,
+        &rent,
+        accounts,
+    )
+}
 
-        let owner = Pubkey::default();
-        let payment_account_key = Pubkey::from_str(PAYMENT_ACCOUNT_ADDRESS).unwrap();
-        let counter_account_key = Pubkey::from_str(COUNTER_POINTER_ADDRESS).unwrap();
+fn process_set_subdomain_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    fee: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let mut storage_data = storage_account.try_borrow_mut_data()?;
+    let mut account_record = AccountRecord::unpack_from_slice(&storage_data)?;
+    check_record_owner(&account_record, owner)?;
+    // Records from before subdomains have no room for the fee
+    if storage_data.len().lt(&account_record.packed_len()) {
+        return Err(NameServiceError::AccountDataTooSmall.into());
+    }
+    account_record.subdomain_fee = fee;
+    AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+    Ok(())
+}
+
+fn process_set_text(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    key: TextKey,
+    value: Vec<u8>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let text_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    if value.is_empty() || value.len() > MAX_TEXT_LENGTH || std::str::from_utf8(&value).is_err() {
+        return Err(NameServiceError::InvalidTextValue.into());
+    }
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let account_record = AccountRecord::unpack_from_slice(&storage_account.try_borrow_data()?)?;
+    check_record_owner(&account_record, owner)?;
+    let (text_address, bump_seed) = find_text_record_address(storage_account.key, key, program_id);
+    if *text_account.key != text_address {
+        return Err(NameServiceError::InvalidTextAddress.into());
+    }
+    if text_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            owner,
+            text_account,
+            system_program,
+            rent.minimum_balance(TEXT_DATA_SIZE),
+            TEXT_DATA_SIZE,
+            &[
+                TEXT_SEED,
+                &storage_account.key.to_bytes(),
+                &[key as u8],
+                &[bump_seed],
+            ],
+        )?;
+    }
+    write_text_record(
+        program_id,
+        text_account,
+        &TextRecord {
+            is_initialized: true,
+            record: *storage_account.key,
+            owner: account_record.owner,
+            key,
+            value,
+        },
+    )
+}
+
+fn process_clear_text(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    key: TextKey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let text_account = next_account_info(accounts_iter)?;
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let account_record = AccountRecord::unpack_from_slice(&storage_account.try_borrow_data()?)?;
+    check_record_owner(&account_record, owner)?;
+    let (text_address, _) = find_text_record_address(storage_account.key, key, program_id);
+    if *text_account.key != text_address {
+        return Err(NameServiceError::InvalidTextAddress.into());
+    }
+    write_text_record(
+        program_id,
+        text_account,
+        &TextRecord {
+            is_initialized: false,
+            record: *storage_account.key,
+            owner: account_record.owner,
+            key,
+            value: Vec::new(),
+        },
+    )
+}
+
+fn write_text_record(
+    program_id: &Pubkey,
+    text_account: &AccountInfo,
+    text_record: &TextRecord,
+) -> ProgramResult {
+    if text_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let mut text_data = text_account.try_borrow_mut_data()?;
+    if text_data.len().lt(&TEXT_DATA_SIZE) {
+        return Err(NameServiceError::AccountDataTooSmall.into());
+    }
+    TextRecord::pack_into_slice(text_record, &mut text_data);
+    Ok(())
+}
+
+fn process_set_chain_address(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    coin_type: u32,
+    address: Vec<u8>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let address_account = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    if !is_valid_chain_address(coin_type, &address) {
+        return Err(NameServiceError::InvalidChainAddress.into());
+    }
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let account_record = AccountRecord::unpack_from_slice(&storage_account.try_borrow_data()?)?;
+    check_record_owner(&account_record, owner)?;
+    let (record_address, bump_seed) =
+        find_chain_address_record_address(storage_account.key, coin_type, program_id);
+    if *address_account.key != record_address {
+        return Err(NameServiceError::InvalidChainAddressRecord.into());
+    }
+    if address_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            owner,
+            address_account,
+            system_program,
+            rent.minimum_balance(CHAIN_ADDRESS_DATA_SIZE),
+            CHAIN_ADDRESS_DATA_SIZE,
+            &[
+                ADDRESS_SEED,
+                &storage_account.key.to_bytes(),
+                &coin_type.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+    }
+    write_chain_address_record(
+        program_id,
+        address_account,
+        &ChainAddressRecord {
+            is_initialized: true,
+            record: *storage_account.key,
+            owner: account_record.owner,
+            coin_type,
+            address,
+        },
+    )
+}
+
+fn process_clear_chain_address(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    coin_type: u32,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let address_account = next_account_info(accounts_iter)?;
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let account_record = AccountRecord::unpack_from_slice(&storage_account.try_borrow_data()?)?;
+    check_record_owner(&account_record, owner)?;
+    let (record_address, _) =
+        find_chain_address_record_address(storage_account.key, coin_type, program_id);
+    if *address_account.key != record_address {
+        return Err(NameServiceError::InvalidChainAddressRecord.into());
+    }
+    write_chain_address_record(
+        program_id,
+        address_account,
+        &ChainAddressRecord {
+            is_initialized: false,
+            record: *storage_account.key,
+            owner: account_record.owner,
+            coin_type,
+            address: Vec::new(),
+        },
+    )
+}
+
+fn write_chain_address_record(
+    program_id: &Pubkey,
+    address_account: &AccountInfo,
+    chain_address: &ChainAddressRecord,
+) -> ProgramResult {
+    if address_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let mut address_data = address_account.try_borrow_mut_data()?;
+    if address_data.len().lt(&CHAIN_ADDRESS_DATA_SIZE) {
+        return Err(NameServiceError::AccountDataTooSmall.into());
+    }
+    ChainAddressRecord::pack_into_slice(chain_address, &mut address_data);
+    Ok(())
+}
+
+fn process_commit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    commitment: [u8; 32],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let commitment_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    if !owner.is_signer {
+        log::sol_log("Transaction need to be signed by owner");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let (commitment_address, bump_seed) =
+        find_commitment_address(owner.key, &commitment, program_id);
+    if *commitment_account.key != commitment_address {
+        return Err(NameServiceError::InvalidCommitmentAddress.into());
+    }
+    if commitment_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            owner,
+            commitment_account,
+            system_program,
+            rent.minimum_balance(COMMITMENT_DATA_SIZE),
+            COMMITMENT_DATA_SIZE,
+            &[
+                COMMITMENT_SEED,
+                &owner.key.to_bytes(),
+                &commitment,
+                &[bump_seed],
+            ],
+        )?;
+    }
+    if commitment_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let mut commitment_data = commitment_account.try_borrow_mut_data()?;
+    if Commitment::unpack_from_slice(&commitment_data)?.is_initialized {
+        return Err(NameServiceError::CommitmentAlreadyExists.into());
+    }
+    let commitment = Commitment {
+        is_initialized: true,
+        owner: *owner.key,
+        slot: clock.slot,
+    };
+    Commitment::pack_into_slice(&commitment, &mut commitment_data);
+    Ok(())
+}
+
+fn process_refund_commitment(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let commitment_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    if !owner.is_signer {
+        log::sol_log("Transaction need to be signed by owner");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if commitment_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let commitment = unpack_commitment(commitment_account)?;
+    if !commitment.is_initialized || commitment.owner != *owner.key {
+        return Err(NameServiceError::CommitmentNotFound.into());
+    }
+    if clock.slot.saturating_sub(commitment.slot) <= MAX_COMMITMENT_AGE {
+        return Err(NameServiceError::CommitmentNotExpired.into());
+    }
+    close_account(commitment_account, owner)
+}
+
+/// Loads the commitment `owner` made to `commitment`
+fn load_commitment(
+    program_id: &Pubkey,
+    commitment_account: &AccountInfo,
+    owner: &AccountInfo,
+    commitment: &[u8; 32],
+) -> Result<Commitment, ProgramError> {
+    let (commitment_address, _) = find_commitment_address(owner.key, commitment, program_id);
+    if *commitment_account.key != commitment_address {
+        return Err(NameServiceError::InvalidCommitmentAddress.into());
+    }
+    if commitment_account.owner != program_id {
+        return Err(NameServiceError::CommitmentNotFound.into());
+    }
+    let commitment = unpack_commitment(commitment_account)?;
+    if !commitment.is_initialized || commitment.owner != *owner.key {
+        return Err(NameServiceError::CommitmentNotFound.into());
+    }
+    Ok(commitment)
+}
+
+/// Reads a commitment, other accounts of the program have different sizes
+fn unpack_commitment(commitment_account: &AccountInfo) -> Result<Commitment, ProgramError> {
+    let commitment_data = commitment_account.try_borrow_data()?;
+    if commitment_data.len() != COMMITMENT_DATA_SIZE {
+        return Err(NameServiceError::CommitmentNotFound.into());
+    }
+    Commitment::unpack_from_slice(&commitment_data)
+}
+
+/// Zeroes the data of a program account and moves all its lamports to
+/// `recipient`, the runtime removes it once the transaction is done
+fn close_account(account: &AccountInfo, recipient: &AccountInfo) -> ProgramResult {
+    let mut account_balance = account.try_borrow_mut_lamports()?;
+    let mut recipient_balance = recipient.try_borrow_mut_lamports()?;
+    **recipient_balance = recipient_balance
+        .checked_add(**account_balance)
+        .ok_or(NameServiceError::Overflow)?;
+    **account_balance = 0;
+    for byte in account.try_borrow_mut_data()?.iter_mut() {
+        *byte = 0;
+    }
+    Ok(())
+}
+
+fn process_renew(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config = Config::load(program_id, next_account_info(accounts_iter)?)?;
+    let payment_account = next_account_info(accounts_iter)?;
+    let storage_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    if *payment_account.key != find_treasury_address(program_id).0 {
+        return Err(NameServiceError::InvalidPaymentAccount.into());
+    }
+    if !payer.is_signer {
+        log::sol_log("Transaction need to be signed by payer");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let mut account_record = AccountRecord::unpack_from_slice(&storage_account.try_borrow_data()?)?;
+    if !account_record.is_initialized {
+        return Err(NameServiceError::NameNotRegistered.into());
+    }
+    if account_record.expires_at == 0 {
+        return Err(NameServiceError::NameDoesNotExpire.into());
+    }
+    // Past the grace period the name has to be registered again
+    if account_record.is_available(clock.unix_timestamp) {
+        return Err(NameServiceError::NameExpired.into());
+    }
+    if account_record.parent == Pubkey::default() {
+        // Anyone can pay for another term, counted from the current expiry,
+        // but once expired only the owner can keep the name
+        if account_record.is_expired(clock.unix_timestamp) && *payer.key != account_record.owner {
+            return Err(NameServiceError::NotNameOwner.into());
+        }
+        let name = from_utf8(&account_record.name).map_err(|_| NameServiceError::InvalidName)?;
+        let fee = config.fee_for(name.chars().count());
+        charge_fee(payer, payment_account, system_program, fee)?;
+        account_record.expires_at += REGISTRATION_PERIOD;
+    } else {
+        // Subdomains catch up with the current term of their parent for free
+        let parent_account = next_account_info(accounts_iter)?;
+        if *parent_account.key != account_record.parent {
+            return Err(NameServiceError::InvalidRecordAddress.into());
+        }
+        if parent_account.owner != program_id {
+            return Err(NameServiceError::IncorrectAccountOwner.into());
+        }
+        let parent = AccountRecord::unpack_from_slice(&parent_account.try_borrow_data()?)?;
+        if !parent.is_initialized
+            || parent.is_expired(clock.unix_timestamp)
+            || parent.index != account_record.index
+        {
+            return Err(NameServiceError::NameExpired.into());
+        }
+        account_record.expires_at = parent.expires_at;
+    }
+    let mut storage_data = storage_account.try_borrow_mut_data()?;
+    if storage_data.len().lt(&account_record.packed_len()) {
+        return Err(NameServiceError::AccountDataTooSmall.into());
+    }
+    AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+    Ok(())
+}
+
+/// Checks an existing record account can be registered again, it must be
+/// empty or hold a name whose grace period is over
+fn take_over_record(storage_account: &AccountInfo, clock: &Clock) -> ProgramResult {
+    let previous = AccountRecord::unpack_from_slice(&storage_account.try_borrow_data()?)?;
+    if !previous.is_available(clock.unix_timestamp) {
+        return Err(NameServiceError::NameAlreadyRegistered.into());
+    }
+    Ok(())
+}
+
+/// Fails unless `account` holds enough lamports to be rent exempt
+fn check_rent_exempt(account: &AccountInfo, rent: &Rent) -> ProgramResult {
+    if !rent.is_exempt(account.lamports(), account.data_len()) {
+        return Err(NameServiceError::NotRentExempt.into());
+    }
+    Ok(())
+}
+
+/// Moves `fee` from signing `payer` to `to`, fails early when the payer can
+/// not cover it
+fn charge_fee<'a>(
+    payer: &AccountInfo<'a>,
+    to: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    fee: u64,
+) -> ProgramResult {
+    if fee == 0 {
+        return Ok(());
+    }
+    if payer.lamports() < fee {
+        return Err(NameServiceError::InsufficientFunds.into());
+    }
+    transfer_lamports(payer, to, system_program, fee)
+}
+
+/// Moves `lamports` from signing `payer` to `to` through the system program
+fn transfer_lamports<'a>(
+    payer: &AccountInfo<'a>,
+    to: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    lamports: u64,
+) -> ProgramResult {
+    invoke(
+        &system_instruction::transfer(payer.key, to.key, lamports),
+        &[payer.clone(), to.clone(), system_program.clone()],
+    )
+}
+
+fn process_close(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+    let reverse_account = next_account_info(accounts_iter)?;
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let account_record = AccountRecord::unpack_from_slice(&storage_account.try_borrow_data()?)?;
+    check_record_owner(&account_record, owner)?;
+
+    // Remaining accounts are text and address records of the name, all are
+    // checked before anything is closed
+    let attached_accounts: Vec<&AccountInfo> = accounts_iter.collect();
+    for attached in &attached_accounts {
+        if attached.owner != program_id {
+            return Err(NameServiceError::IncorrectAccountOwner.into());
+        }
+        let attached_data = attached.try_borrow_data()?;
+        let record = match attached_data.len() {
+            TEXT_DATA_SIZE => TextRecord::unpack_from_slice(&attached_data)?.record,
+            CHAIN_ADDRESS_DATA_SIZE => {
+                ChainAddressRecord::unpack_from_slice(&attached_data)?.record
+            }
+            _ => return Err(NameServiceError::InvalidNameAccount.into()),
+        };
+        if record != *storage_account.key {
+            return Err(NameServiceError::InvalidNameAccount.into());
+        }
+    }
+    // Release the reverse lookup unless the address was registered again
+    let (reverse_address, _) =
+        find_reverse_record_address(&account_record.account_address, program_id);
+    if *reverse_account.key != reverse_address {
+        return Err(NameServiceError::InvalidReverseAddress.into());
+    }
+    if reverse_account.owner == program_id {
+        let reverse_record = ReverseRecord::unpack_from_slice(&reverse_account.try_borrow_data()?)?;
+        if reverse_record.is_initialized && reverse_record.name == account_record.name {
+            close_account(reverse_account, destination)?;
+        }
+    }
+    for attached in attached_accounts {
+        close_account(attached, destination)?;
+    }
+    close_account(storage_account, destination)
+}
+
+fn process_initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    beneficiaries: [Beneficiary; MAX_BENEFICIARIES],
+    fees: [u64; FEE_TIERS],
+    registry: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    let treasury = next_account_info(accounts_iter)?;
+    if !admin.is_signer {
+        log::sol_log("Transaction need to be signed by admin");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *admin.key != Pubkey::from_str(DEPLOYER_ADDRESS).unwrap() {
+        return Err(NameServiceError::NotDeployer.into());
+    }
+    Config::check_beneficiaries(&beneficiaries)?;
+    let (config_address, bump_seed) = find_config_address(program_id);
+    if *config_account.key != config_address {
+        return Err(NameServiceError::InvalidConfigAddress.into());
+    }
+    let (treasury_address, treasury_bump_seed) = find_treasury_address(program_id);
+    if *treasury.key != treasury_address {
+        return Err(NameServiceError::InvalidPaymentAccount.into());
+    }
+    // Treasury holds no data, the program owns it to pay out withdrawals
+    if treasury.owner != program_id {
+        create_pda_account(
+            program_id,
+            admin,
+            treasury,
+            system_program,
+            rent.minimum_balance(0),
+            0,
+            &[TREASURY_SEED, &[treasury_bump_seed]],
+        )?;
+    }
+    if config_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            admin,
+            config_account,
+            system_program,
+            rent.minimum_balance(CONFIG_DATA_SIZE),
+            CONFIG_DATA_SIZE,
+            &[CONFIG_SEED, &[bump_seed]],
+        )?;
+    }
+    if config_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let mut config_data = config_account.try_borrow_mut_data()?;
+    if config_data.len().lt(&CONFIG_DATA_SIZE) {
+        return Err(NameServiceError::AccountDataTooSmall.into());
+    }
+    if Config::unpack_from_slice(&config_data)?.is_initialized {
+        return Err(NameServiceError::ConfigAlreadyInitialized.into());
+    }
+    let config = Config {
+        is_initialized: true,
+        admin: *admin.key,
+        beneficiaries,
+        fees,
+        paused: false,
+        registry,
+    };
+    Config::pack_into_slice(&config, &mut config_data);
+    Ok(())
+}
+
+fn process_update_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_admin: Pubkey,
+    beneficiaries: [Beneficiary; MAX_BENEFICIARIES],
+    fees: [u64; FEE_TIERS],
+    registry: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let mut config = Config::load(program_id, config_account)?;
+    config.check_admin(admin)?;
+    Config::check_beneficiaries(&beneficiaries)?;
+    config.admin = new_admin;
+    config.beneficiaries = beneficiaries;
+    config.fees = fees;
+    config.registry = registry;
+    Config::pack_into_slice(&config, &mut config_account.try_borrow_mut_data()?);
+    Ok(())
+}
+
+/// Fails unless `owner` is the signing owner of an initialized record
+fn check_record_owner(account_record: &AccountRecord, owner: &AccountInfo) -> ProgramResult {
+    if !account_record.is_initialized {
+        return Err(NameServiceError::NameNotRegistered.into());
+    }
+    if account_record.owner != *owner.key {
+        return Err(NameServiceError::NotNameOwner.into());
+    }
+    if !owner.is_signer {
+        log::sol_log("Transaction need to be signed by owner");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+// tests
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+    use name_service_common::{
+        find_reserved_name_address, reserved_name_hash, ReservedName, RESERVED_DATA_SIZE,
+    };
+    use solana_sdk::{clock::Epoch, system_program, sysvar};
+
+    const REGISTRATION_FEE: u64 = 1_000_000_000;
+
+    /// Initialized config account for instructions that check the pause
+    /// switch, leaked to outlive the test
+    fn config_with_pause<'a>(program_id: &Pubkey, paused: bool) -> AccountInfo<'a> {
+        let config = Config {
+            is_initialized: true,
+            admin: Pubkey::new_unique(),
+            paused,
+            ..Default::default()
+        };
+        let mut config_data = vec![0; CONFIG_DATA_SIZE];
+        Config::pack_into_slice(&config, &mut config_data);
+        AccountInfo::new(
+            Box::leak(Box::new(find_config_address(program_id).0)), // account pubkey
+            false,                                                  // is_signer
+            false,                                                  // is_writable
+            Box::leak(Box::new(0)),                                 // balance in lamports
+            Box::leak(config_data.into_boxed_slice()),              // storage
+            Box::leak(Box::new(*program_id)),                       // owner pubkey
+            false,                                                  // is_executable
+            Epoch::default(),                                       // rent_epoch
+        )
+    }
+
+    /// Signature of a registered address, needed to write its reverse record
+    fn address_signer(address: &Pubkey) -> AccountInfo<'_> {
+        AccountInfo::new(
+            address,                                  // account pubkey
+            true,                                     // is_signer
+            false,                                    // is_writable
+            Box::leak(Box::new(0)),                   // balance in lamports
+            Box::leak(Vec::new().into_boxed_slice()), // storage
+            Box::leak(Box::new(Pubkey::default())),   // owner pubkey
+            false,                                    // is_executable
+            Epoch::default(),                         // rent_epoch
+        )
+    }
+
+    #[test]
+    fn test_flow() {
+        // mock program id
+
+        let program_id = Pubkey::default();
+
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut empty_data3 = vec![0; 0];
+        let mut balance_payment_account = 0;
+        let mut balance_payer = REGISTRATION_FEE;
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut zero_balance3 = 0;
+        let mut rent_data = vec![0; Rent::size_of()];
+        let mut balance_minter_token = 0;
+        let mut balance_storage_account = REGISTRATION_FEE + 1;
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        let mut balance_reverse_account = 0;
+        let mut reverse_data = vec![0; REVERSE_DATA_SIZE];
+        let mut registry_data = vec![0; REGISTRY_DATA_SIZE];
+        let mut zero_balance4 = 0;
+        let mut config_data = vec![0; CONFIG_DATA_SIZE];
+        let mut balance_commitment_account = 0;
+        let mut commitment_data = vec![0; COMMITMENT_DATA_SIZE];
+        let mut balance_commitment_account2 = 0;
+        let mut commitment_data2 = vec![0; COMMITMENT_DATA_SIZE];
+        let mut zero_balance5 = 0;
+        let mut clock_data = vec![0; Clock::size_of()];
+        let mut zero_balance6 = 0;
+        let mut reserved_data = vec![0; RESERVED_DATA_SIZE];
+        let mut zero_balance7 = 0;
+        let mut empty_data4 = vec![0; 0];
+        let mut zero_balance8 = 0;
+        let mut empty_data5 = vec![0; 0];
+
+        let (payment_account_key, _) = find_treasury_address(&program_id);
+        let registry_address = Pubkey::new_unique();
+        let config = Config {
+            is_initialized: true,
+            admin: Pubkey::new_unique(),
+            fees: [10, 8, 6, 4, REGISTRATION_FEE],
+            registry: registry_address,
+            ..Default::default()
+        };
+        Config::pack_into_slice(&config, &mut config_data);
+        let counter_address =
+            Pubkey::from_str("FJNj5YDJVT3pbtiZsMMeRPJumM35iF71rUzDQWC7CXqq").unwrap();
+        let mut registry = Registry {
+            is_initialized: true,
+            authority: Pubkey::new_unique(),
+            ..Registry::default()
+        };
+        registry.entries[REGISTRY_COUNTER] = counter_address;
+        Registry::pack_into_slice(&registry, &mut registry_data);
+        let mut counter_initial_data = vec![0; 8];
+
+        let owner = Pubkey::default();
+        let proxy_pointer_program_id = Pubkey::from_str(PROXY_POINTER_PROGRAM_ID).unwrap();
+        let (config_address, _) = find_config_address(&program_id);
+        let config_account = AccountInfo::new(
+            &config_address,    // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance4, // balance in lamports
+            &mut config_data,   // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
         let payment_account = AccountInfo::new(
             &payment_account_key,         // account pubkey
             false,                        // is_signer
@@ -301,13 +1936,13 @@ mod test {
             false,                        // is_executable
             Epoch::default(),             // rent_epoch
         );
-        let counter_pointer = AccountInfo::new(
-            &counter_account_key,      // account pubkey
+        let registry_account = AccountInfo::new(
+            &registry_address,         // account pubkey
             false,                     // is_signer
-            true,                      // is_writable
+            false,                     // is_writable
             &mut zero_balance,         // balance in lamports
-            &mut counter_pointer_data, // storage
-            &owner,                    // owner pubkey
+            &mut registry_data,        // storage
+            &proxy_pointer_program_id, // owner pubkey
             false,                     // is_executable
             Epoch::default(),          // rent_epoch
         );
@@ -321,9 +1956,10 @@ mod test {
             false,                     // is_executable
             Epoch::default(),          // rent_epoch
         );
-        let random_address = Pubkey::default();
+        let name = *b"name that we want to regsiter 12";
+        let (record_address, _) = find_name_record_address(&name, &program_id);
         let storage_account = AccountInfo::new(
-            &random_address,              // account pubkey
+            &record_address,              // account pubkey
             false,                        // is_signer
             true,                         // is_writable
             &mut balance_storage_account, // balance in lamports
@@ -332,27 +1968,2098 @@ mod test {
             false,                        // is_executable
             Epoch::default(),             // rent_epoch
         );
-        let accounts = vec![payment_account, counter_pointer, counter, storage_account];
+        let account_address =
+            Pubkey::from_str("8hVSuapWRrZXGR4MEdwCfzAi7d7hSgVwTRy6jv5kokCY").unwrap();
+        let (reverse_address, _) = find_reverse_record_address(&account_address, &program_id);
+        let reverse_account = AccountInfo::new(
+            &reverse_address,             // account pubkey
+            false,                        // is_signer
+            true,                         // is_writable
+            &mut balance_reverse_account, // balance in lamports
+            &mut reverse_data,            // storage
+            &program_id,                  // owner pubkey
+            false,                        // is_executable
+            Epoch::default(),             // rent_epoch
+        );
+        let payer_address = Pubkey::new_unique();
+        let payer = AccountInfo::new(
+            &payer_address,     // account pubkey
+            true,               // is_signer
+            true,               // is_writable
+            &mut balance_payer, // balance in lamports
+            &mut empty_data2,   // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut empty_data3,   // storage
+            &owner,             // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut rent_data,     // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let salt = [7; 32];
+        let commitment = commitment_hash(&name, &salt, &payer_address);
+        let (commitment_address, _) =
+            find_commitment_address(&payer_address, &commitment, &program_id);
+        let commitment_account = AccountInfo::new(
+            &commitment_address,             // account pubkey
+            false,                           // is_signer
+            true,                            // is_writable
+            &mut balance_commitment_account, // balance in lamports
+            &mut commitment_data,            // storage
+            &program_id,                     // owner pubkey
+            false,                           // is_executable
+            Epoch::default(),                // rent_epoch
+        );
+        let clock_id = sysvar::clock::id();
+        let mut clock_account = AccountInfo::new(
+            &clock_id,          // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance5, // balance in lamports
+            &mut clock_data,    // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let authority_address = Pubkey::new_unique();
+        let name_hash = reserved_name_hash(&name);
+        let reserved_name = ReservedName {
+            is_initialized: true,
+            name_hash,
+            authority: authority_address,
+        };
+        ReservedName::pack_into_slice(&reserved_name, &mut reserved_data);
+        let (reserved_address, _) = find_reserved_name_address(&name_hash, &program_id);
+        let reserved_account = AccountInfo::new(
+            &reserved_address,  // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance6, // balance in lamports
+            &mut reserved_data, // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let authority = AccountInfo::new(
+            &authority_address, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance7, // balance in lamports
+            &mut empty_data4,   // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let set_slot = |clock_account: &mut AccountInfo, slot| {
+            let clock = Clock {
+                slot,
+                ..Clock::default()
+            };
+            clock.to_account_info(clock_account).unwrap();
+        };
+        set_slot(&mut clock_account, 10);
+        let commit_accounts = vec![
+            commitment_account.clone(),
+            payer.clone(),
+            system_program.clone(),
+            rent_account.clone(),
+            clock_account.clone(),
+            config_account.clone(),
+        ];
+        let commit_data = NameInstruction::Commit { commitment }.pack();
+        process_instruction(&program_id, &commit_accounts, &commit_data).unwrap();
+        assert_eq!(
+            process_instruction(&program_id, &commit_accounts, &commit_data),
+            Err(NameServiceError::CommitmentAlreadyExists.into())
+        );
+        **commitment_account.lamports.borrow_mut() = 5;
+        let mut accounts = vec![
+            config_account,
+            payment_account,
+            registry_account,
+            counter,
+            storage_account,
+            reverse_account,
+            payer,
+            system_program,
+            rent_account,
+            commitment_account,
+            clock_account,
+            reserved_account,
+        ];
         let instruction = Instruction {
-            account_address: Pubkey::from_str("8hVSuapWRrZXGR4MEdwCfzAi7d7hSgVwTRy6jv5kokCY")
-                .unwrap(),
-            name: *b"name that we want to regsiter 12",
+            account_address,
+            name: name.to_vec(),
         };
-        let mut instruction_data = vec![0; INSTRUCTION_DATA_SIZE];
-        instruction.pack_into_slice(&mut instruction_data);
+        let instruction_data = NameInstruction::Register {
+            instruction: instruction.clone(),
+            salt,
+        }
+        .pack();
+        // Registry has to be the one in the config and belong to proxy-pointer
+        let mut wrong_accounts = accounts.clone();
+        wrong_accounts[2].key = &counter_address;
+        assert_eq!(
+            process_instruction(&program_id, &wrong_accounts, &instruction_data),
+            Err(NameServiceError::InvalidRegistry.into())
+        );
+        wrong_accounts[2] = accounts[2].clone();
+        wrong_accounts[2].owner = &program_id;
+        assert_eq!(
+            process_instruction(&program_id, &wrong_accounts, &instruction_data),
+            Err(NameServiceError::IncorrectAccountOwner.into())
+        );
+        // Counter has to be the one the registry resolves
+        Registry::pack_into_slice(
+            &Registry {
+                is_initialized: true,
+                ..Registry::default()
+            },
+            &mut accounts[2].data.borrow_mut(),
+        );
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InvalidCounterAccount.into())
+        );
+        Registry::pack_into_slice(&registry, &mut accounts[2].data.borrow_mut());
+        // Reserved name can only be claimed with its authority signature
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NameReserved.into())
+        );
+        accounts.push(authority);
+        accounts.push(address_signer(&account_address));
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        accounts[12].is_signer = true;
+        // Reveal has to wait for a later slot
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::CommitmentTooNew.into())
+        );
+        set_slot(&mut accounts[10], 10 + MIN_COMMITMENT_AGE);
+        // Salt has to match the commitment
+        let wrong_salt = NameInstruction::Register {
+            instruction: instruction.clone(),
+            salt: [8; 32],
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &wrong_salt),
+            Err(NameServiceError::InvalidCommitmentAddress.into())
+        );
+        // Payer has to cover the fee of the name length tier
+        let fee = config.fee_for(name.len());
+        **accounts[6].lamports.borrow_mut() = fee - 1;
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InsufficientFunds.into())
+        );
+        **accounts[6].lamports.borrow_mut() = fee;
         process_instruction(&program_id, &accounts, &instruction_data).unwrap();
-        // Check if fund are moved
-        let payment_target = accounts[0].lamports.borrow();
-        assert_eq!(**payment_target, REGISTRATION_FEE);
+        // System program transfers are not run in tests, so the payer keeps
+        // the fee and the record keeps its balance
+        assert_eq!(**accounts[4].lamports.borrow(), REGISTRATION_FEE + 1);
+        // Commitment is closed and its rent returned
+        assert_eq!(**accounts[9].lamports.borrow(), 0);
+        assert_eq!(**accounts[6].lamports.borrow(), REGISTRATION_FEE + 5);
+        assert!(
+            !Commitment::unpack_from_slice(&accounts[9].data.borrow())
+                .unwrap()
+                .is_initialized
+        );
 
         // Check if data stored is correct
-        let data_stored = accounts[3].data.borrow();
+        let data_stored = accounts[4].data.borrow();
         let registered_data = AccountRecord::unpack_from_slice(&data_stored).unwrap();
         assert_eq!(registered_data.name, instruction.name);
         assert_eq!(registered_data.account_address, instruction.account_address);
+        assert_eq!(registered_data.owner, payer_address);
+        assert_eq!(registered_data.expires_at, REGISTRATION_PERIOD);
         // Check if counter incremented
-        let data_stored_counter = accounts[2].data.borrow();
+        let data_stored_counter = accounts[3].data.borrow();
         let counter_data = Counter::unpack_from_slice(&data_stored_counter).unwrap();
         assert_eq!(counter_data.index, 1);
+        // Check if address resolves back to the name
+        let reverse_stored = accounts[5].data.borrow();
+        let reverse_data = ReverseRecord::unpack_from_slice(&reverse_stored).unwrap();
+        assert!(reverse_data.is_initialized);
+        assert_eq!(reverse_data.account_address, account_address);
+        assert_eq!(reverse_data.name, instruction.name);
+        assert_eq!(reverse_data.index, 1);
+        drop(reverse_stored);
+        drop(data_stored_counter);
+        drop(data_stored);
+
+        // Same name with different casing maps to the same record
+        process_instruction(&program_id, &commit_accounts, &commit_data).unwrap();
+        let duplicate = Instruction {
+            account_address,
+            name: b"NAME THAT WE WANT TO REGSITER 12".to_vec(),
+        };
+        let instruction_data = NameInstruction::Register {
+            instruction: duplicate,
+            salt,
+        }
+        .pack();
+        set_slot(&mut accounts[10], 11 + MAX_COMMITMENT_AGE + 1);
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::CommitmentExpired.into())
+        );
+        set_slot(&mut accounts[10], 11 + MIN_COMMITMENT_AGE);
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NameAlreadyRegistered.into())
+        );
+        // Anyone can take the name once its grace period is over
+        let clock = Clock {
+            slot: 11 + MIN_COMMITMENT_AGE,
+            unix_timestamp: REGISTRATION_PERIOD + GRACE_PERIOD + 1,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut accounts[10]).unwrap();
+        // Without the signature of the address its reverse record is kept
+        accounts.pop();
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let registered_data = AccountRecord::unpack_from_slice(&accounts[4].data.borrow()).unwrap();
+        assert_eq!(registered_data.index, 2);
+        let reverse_data = ReverseRecord::unpack_from_slice(&accounts[5].data.borrow()).unwrap();
+        assert_eq!(reverse_data.index, 1);
+        assert_eq!(
+            registered_data.expires_at,
+            2 * REGISTRATION_PERIOD + GRACE_PERIOD + 1
+        );
+        // Record for another name can not be stored in this account
+        let other_name = b"some other name\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+        let other_commitment = commitment_hash(b"some other name", &salt, &payer_address);
+        let (other_commitment_address, _) =
+            find_commitment_address(&payer_address, &other_commitment, &program_id);
+        accounts[9] = AccountInfo::new(
+            &other_commitment_address,        // account pubkey
+            false,                            // is_signer
+            true,                             // is_writable
+            &mut balance_commitment_account2, // balance in lamports
+            &mut commitment_data2,            // storage
+            &program_id,                      // owner pubkey
+            false,                            // is_executable
+            Epoch::default(),                 // rent_epoch
+        );
+        set_slot(&mut accounts[10], 11);
+        let commit_accounts = vec![
+            accounts[9].clone(),
+            accounts[6].clone(),
+            accounts[7].clone(),
+            accounts[8].clone(),
+            accounts[10].clone(),
+            accounts[0].clone(),
+        ];
+        let commit_data = NameInstruction::Commit {
+            commitment: other_commitment,
+        }
+        .pack();
+        process_instruction(&program_id, &commit_accounts, &commit_data).unwrap();
+        set_slot(&mut accounts[10], 11 + MIN_COMMITMENT_AGE);
+        let other = Instruction {
+            account_address: Pubkey::new_unique(),
+            name: other_name.to_vec(),
+        };
+        // Name was never reserved, its reserved name account does not exist
+        let (other_reserved_address, _) =
+            find_reserved_name_address(&reserved_name_hash(b"some other name"), &program_id);
+        accounts[11] = AccountInfo::new(
+            &other_reserved_address, // account pubkey
+            false,                   // is_signer
+            false,                   // is_writable
+            &mut zero_balance8,      // balance in lamports
+            &mut empty_data5,        // storage
+            &owner,                  // owner pubkey
+            false,                   // is_executable
+            Epoch::default(),        // rent_epoch
+        );
+        let instruction_data = NameInstruction::Register {
+            instruction: other,
+            salt,
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InvalidRecordAddress.into())
+        );
+    }
+    #[test]
+    fn test_refund_commitment() {
+        let program_id = Pubkey::default();
+        let owner = Pubkey::default();
+        let mut balance_commitment_account = 5;
+        let mut commitment_data = vec![0; COMMITMENT_DATA_SIZE];
+        let mut balance_owner = 0;
+        let mut empty_data = vec![0; 0];
+        let mut zero_balance = 0;
+        let mut clock_data = vec![0; Clock::size_of()];
+
+        let owner_address = Pubkey::new_unique();
+        let commitment = Commitment {
+            is_initialized: true,
+            owner: owner_address,
+            slot: 10,
+        };
+        Commitment::pack_into_slice(&commitment, &mut commitment_data);
+        let (commitment_address, _) =
+            find_commitment_address(&owner_address, &[1; 32], &program_id);
+        let commitment_account = AccountInfo::new(
+            &commitment_address,             // account pubkey
+            false,                           // is_signer
+            true,                            // is_writable
+            &mut balance_commitment_account, // balance in lamports
+            &mut commitment_data,            // storage
+            &program_id,                     // owner pubkey
+            false,                           // is_executable
+            Epoch::default(),                // rent_epoch
+        );
+        let owner_account = AccountInfo::new(
+            &owner_address,     // account pubkey
+            true,               // is_signer
+            true,               // is_writable
+            &mut balance_owner, // balance in lamports
+            &mut empty_data,    // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let clock_id = sysvar::clock::id();
+        let mut clock_account = AccountInfo::new(
+            &clock_id,         // account pubkey
+            false,             // is_signer
+            false,             // is_writable
+            &mut zero_balance, // balance in lamports
+            &mut clock_data,   // storage
+            &owner,            // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let clock = Clock {
+            slot: 10 + MAX_COMMITMENT_AGE,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut clock_account).unwrap();
+        let mut accounts = vec![commitment_account, owner_account, clock_account];
+        let instruction_data = NameInstruction::RefundCommitment.pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::CommitmentNotExpired.into())
+        );
+        let clock = Clock {
+            slot: 11 + MAX_COMMITMENT_AGE,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut accounts[2]).unwrap();
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        assert_eq!(**accounts[0].lamports.borrow(), 0);
+        assert_eq!(**accounts[1].lamports.borrow(), 5);
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::CommitmentNotFound.into())
+        );
+    }
+    #[test]
+    fn test_transfer() {
+        let program_id = Pubkey::default();
+
+        let mut empty_data = vec![0; 0];
+        let mut zero_balance = 0;
+        let mut balance_storage_account = 0;
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+
+        let owner_address = Pubkey::new_unique();
+        let new_owner_address = Pubkey::new_unique();
+        let account_record = AccountRecord {
+            account_address: Pubkey::new_unique(),
+            name: b"name that we want to transfer 12".to_vec(),
+            is_initialized: true,
+            index: 1,
+            owner: owner_address,
+            ..Default::default()
+        };
+        AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+
+        let record_address = Pubkey::new_unique();
+        let storage_account = AccountInfo::new(
+            &record_address,              // account pubkey
+            false,                        // is_signer
+            true,                         // is_writable
+            &mut balance_storage_account, // balance in lamports
+            &mut storage_data,            // storage
+            &program_id,                  // owner pubkey
+            false,                        // is_executable
+            Epoch::default(),             // rent_epoch
+        );
+        let owner = AccountInfo::new(
+            &owner_address,    // account pubkey
+            true,              // is_signer
+            false,             // is_writable
+            &mut zero_balance, // balance in lamports
+            &mut empty_data,   // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let mut accounts = vec![storage_account, owner, config_with_pause(&program_id, true)];
+        let instruction_data = NameInstruction::Transfer {
+            new_owner: new_owner_address,
+        }
+        .pack();
+        // Nothing changes hands while the program is paused
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::ProgramPaused.into())
+        );
+        assert_eq!(
+            process_instruction(&program_id, &accounts[..2], &instruction_data),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        accounts[2] = config_with_pause(&program_id, false);
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        // Check if owner changed and rest of record is untouched
+        let data_stored = accounts[0].data.borrow();
+        let transferred = AccountRecord::unpack_from_slice(&data_stored).unwrap();
+        assert_eq!(
+            transferred,
+            AccountRecord {
+                owner: new_owner_address,
+                ..account_record
+            }
+        );
+        drop(data_stored);
+
+        // Previous owner can not transfer it anymore
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NotNameOwner.into())
+        );
+        // New owner has to sign
+        accounts[1].key = &new_owner_address;
+        accounts[1].is_signer = false;
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+    #[test]
+    fn test_update_address() {
+        let program_id = Pubkey::default();
+
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut zero_balance3 = 0;
+        let mut zero_balance4 = 0;
+        let mut zero_balance5 = 0;
+        let mut zero_balance6 = 0;
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        let mut old_reverse_data = vec![0; REVERSE_DATA_SIZE];
+        let mut new_reverse_data = vec![0; REVERSE_DATA_SIZE];
+        let mut rent_data = vec![0; Rent::size_of()];
+
+        let owner_address = Pubkey::new_unique();
+        let old_address = Pubkey::new_unique();
+        let new_address = Pubkey::new_unique();
+        let account_record = AccountRecord {
+            account_address: old_address,
+            name: b"name that we want to update 1234".to_vec(),
+            is_initialized: true,
+            index: 7,
+            owner: owner_address,
+            ..Default::default()
+        };
+        AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+        let old_reverse_record = ReverseRecord {
+            account_address: old_address,
+            name: account_record.name.clone(),
+            is_initialized: true,
+            index: account_record.index,
+        };
+        ReverseRecord::pack_into_slice(&old_reverse_record, &mut old_reverse_data);
+
+        let (record_address, _) = find_name_record_address(&account_record.name, &program_id);
+        let (old_reverse_address, _) = find_reverse_record_address(&old_address, &program_id);
+        let (new_reverse_address, _) = find_reverse_record_address(&new_address, &program_id);
+        let storage_account = AccountInfo::new(
+            &record_address,   // account pubkey
+            false,             // is_signer
+            true,              // is_writable
+            &mut zero_balance, // balance in lamports
+            &mut storage_data, // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let owner = AccountInfo::new(
+            &owner_address,     // account pubkey
+            true,               // is_signer
+            true,               // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut empty_data,    // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let old_reverse_account = AccountInfo::new(
+            &old_reverse_address,  // account pubkey
+            false,                 // is_signer
+            true,                  // is_writable
+            &mut zero_balance3,    // balance in lamports
+            &mut old_reverse_data, // storage
+            &program_id,           // owner pubkey
+            false,                 // is_executable
+            Epoch::default(),      // rent_epoch
+        );
+        let new_reverse_account = AccountInfo::new(
+            &new_reverse_address,  // account pubkey
+            false,                 // is_signer
+            true,                  // is_writable
+            &mut zero_balance4,    // balance in lamports
+            &mut new_reverse_data, // storage
+            &program_id,           // owner pubkey
+            false,                 // is_executable
+            Epoch::default(),      // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance5, // balance in lamports
+            &mut empty_data2,   // storage
+            &program_id,        // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance6, // balance in lamports
+            &mut rent_data,     // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let accounts = vec![
+            storage_account,
+            owner.clone(),
+            old_reverse_account,
+            new_reverse_account,
+            owner,
+            system_program,
+            rent_account,
+            config_with_pause(&program_id, false),
+            address_signer(&new_address),
+        ];
+        let instruction_data = NameInstruction::UpdateAddress { new_address }.pack();
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+
+        // Name points at the new address and keeps its index
+        let data_stored = accounts[0].data.borrow();
+        let updated = AccountRecord::unpack_from_slice(&data_stored).unwrap();
+        assert_eq!(
+            updated,
+            AccountRecord {
+                account_address: new_address,
+                ..account_record
+            }
+        );
+        // Old address no longer resolves, new one does
+        let old_reverse_stored = accounts[2].data.borrow();
+        let old_reverse = ReverseRecord::unpack_from_slice(&old_reverse_stored).unwrap();
+        assert!(!old_reverse.is_initialized);
+        let new_reverse_stored = accounts[3].data.borrow();
+        let new_reverse = ReverseRecord::unpack_from_slice(&new_reverse_stored).unwrap();
+        assert_eq!(
+            new_reverse,
+            ReverseRecord {
+                account_address: new_address,
+                ..old_reverse_record
+            }
+        );
+    }
+    #[test]
+    fn test_config() {
+        let program_id = Pubkey::default();
+
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut zero_balance3 = 0;
+        let mut zero_balance4 = 0;
+        let mut config_data = vec![0; CONFIG_DATA_SIZE];
+        let mut rent_data = vec![0; Rent::size_of()];
+        let mut empty_data3 = vec![0; 0];
+        let mut empty_data4 = vec![0; 0];
+        let mut empty_data5 = vec![0; 0];
+        let mut balance_treasury = Rent::default().minimum_balance(0) + 1_001;
+        let mut zero_balance5 = 0;
+        let mut zero_balance6 = 0;
+        // Prefunded, account creation is not run in tests
+        let mut balance_reserved = Rent::default().minimum_balance(RESERVED_DATA_SIZE);
+        let mut reserved_data = vec![0; RESERVED_DATA_SIZE];
+
+        let admin_address = Pubkey::from_str(DEPLOYER_ADDRESS).unwrap();
+        let intruder_address = Pubkey::new_unique();
+        let partner_address = Pubkey::new_unique();
+        let beneficiaries = [
+            Beneficiary {
+                address: admin_address,
+                share: 7_000,
+            },
+            Beneficiary {
+                address: partner_address,
+                share: 3_000,
+            },
+            Beneficiary::default(),
+            Beneficiary::default(),
+        ];
+        let (config_address, _) = find_config_address(&program_id);
+        let config_account = AccountInfo::new(
+            &config_address,   // account pubkey
+            false,             // is_signer
+            true,              // is_writable
+            &mut zero_balance, // balance in lamports
+            &mut config_data,  // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let admin = AccountInfo::new(
+            &admin_address,     // account pubkey
+            true,               // is_signer
+            true,               // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut empty_data,    // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut empty_data2,   // storage
+            &program_id,        // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance4, // balance in lamports
+            &mut rent_data,     // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let (treasury_address, _) = find_treasury_address(&program_id);
+        let treasury = AccountInfo::new(
+            &treasury_address,     // account pubkey
+            false,                 // is_signer
+            true,                  // is_writable
+            &mut balance_treasury, // balance in lamports
+            &mut empty_data3,      // storage
+            &program_id,           // owner pubkey
+            false,                 // is_executable
+            Epoch::default(),      // rent_epoch
+        );
+        let partner = AccountInfo::new(
+            &partner_address,   // account pubkey
+            false,              // is_signer
+            true,               // is_writable
+            &mut zero_balance5, // balance in lamports
+            &mut empty_data4,   // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let mut accounts = vec![
+            config_account,
+            admin,
+            system_program,
+            rent_account,
+            treasury,
+        ];
+        // Shares have to add up to the whole
+        let mut uneven = beneficiaries;
+        uneven[1].share = 2_000;
+        let registry_address = Pubkey::new_unique();
+        let instruction_data = NameInstruction::InitializeConfig {
+            beneficiaries: uneven,
+            fees: [10, 8, 6, 4, REGISTRATION_FEE],
+            registry: registry_address,
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InvalidBeneficiaries.into())
+        );
+        let instruction_data = NameInstruction::InitializeConfig {
+            beneficiaries,
+            fees: [10, 8, 6, 4, REGISTRATION_FEE],
+            registry: registry_address,
+        }
+        .pack();
+        // Only the deployer can claim the config
+        accounts[1].key = &intruder_address;
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NotDeployer.into())
+        );
+        accounts[1].key = &admin_address;
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let config = Config::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                is_initialized: true,
+                admin: admin_address,
+                beneficiaries,
+                fees: [10, 8, 6, 4, REGISTRATION_FEE],
+                paused: false,
+                registry: registry_address,
+            }
+        );
+        // Config can be initialized only once
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::ConfigAlreadyInitialized.into())
+        );
+
+        // Withdraw splits the treasury above rent by shares
+        let withdraw_data = NameInstruction::Withdraw.pack();
+        let withdraw_accounts = vec![
+            accounts[0].clone(),
+            accounts[4].clone(),
+            accounts[3].clone(),
+            partner.clone(),
+            accounts[1].clone(),
+        ];
+        assert_eq!(
+            process_instruction(&program_id, &withdraw_accounts, &withdraw_data),
+            Err(NameServiceError::InvalidBeneficiaryAccount.into())
+        );
+        let withdraw_accounts = vec![
+            accounts[0].clone(),
+            accounts[4].clone(),
+            accounts[3].clone(),
+            accounts[1].clone(),
+            partner,
+        ];
+        process_instruction(&program_id, &withdraw_accounts, &withdraw_data).unwrap();
+        assert_eq!(**withdraw_accounts[3].lamports.borrow(), 700);
+        assert_eq!(**withdraw_accounts[4].lamports.borrow(), 300);
+        // Rounding leftovers stay for the next withdraw
+        assert_eq!(
+            **withdraw_accounts[1].lamports.borrow(),
+            Rent::default().minimum_balance(0) + 1
+        );
+        let mut stranger = withdraw_accounts.clone();
+        let stranger_address = Pubkey::new_unique();
+        stranger[4] = AccountInfo::new(
+            &stranger_address,  // account pubkey
+            false,              // is_signer
+            true,               // is_writable
+            &mut zero_balance6, // balance in lamports
+            &mut empty_data5,   // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        assert_eq!(
+            process_instruction(&program_id, &stranger, &withdraw_data),
+            Err(NameServiceError::InvalidBeneficiaryAccount.into())
+        );
+        // Only the treasury derived from the program pays out
+        stranger[1] = stranger[4].clone();
+        assert_eq!(
+            process_instruction(&program_id, &stranger, &withdraw_data),
+            Err(NameServiceError::InvalidPaymentAccount.into())
+        );
+
+        // Admin reserves a name for an authority and releases it later
+        let name_hash = reserved_name_hash(b"nebula");
+        let (reserved_address, _) = find_reserved_name_address(&name_hash, &program_id);
+        let reserved_account = AccountInfo::new(
+            &reserved_address,     // account pubkey
+            false,                 // is_signer
+            true,                  // is_writable
+            &mut balance_reserved, // balance in lamports
+            &mut reserved_data,    // storage
+            &program_id,           // owner pubkey
+            false,                 // is_executable
+            Epoch::default(),      // rent_epoch
+        );
+        let authority_address = Pubkey::new_unique();
+        let reserve_data = NameInstruction::ReserveName {
+            name_hash,
+            authority: authority_address,
+        }
+        .pack();
+        let reserve_accounts = vec![
+            accounts[0].clone(),
+            accounts[1].clone(),
+            reserved_account.clone(),
+            accounts[2].clone(),
+            accounts[3].clone(),
+        ];
+        let other_name_data = NameInstruction::ReserveName {
+            name_hash: reserved_name_hash(b"other"),
+            authority: authority_address,
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &reserve_accounts, &other_name_data),
+            Err(NameServiceError::InvalidReservedAddress.into())
+        );
+        process_instruction(&program_id, &reserve_accounts, &reserve_data).unwrap();
+        assert_eq!(
+            ReservedName::unpack_from_slice(&reserved_account.data.borrow()).unwrap(),
+            ReservedName {
+                is_initialized: true,
+                name_hash,
+                authority: authority_address,
+            }
+        );
+        let release_data = NameInstruction::ReleaseName { name_hash }.pack();
+        let release_accounts = vec![
+            accounts[0].clone(),
+            accounts[1].clone(),
+            reserved_account.clone(),
+            accounts[4].clone(),
+        ];
+        process_instruction(&program_id, &release_accounts, &release_data).unwrap();
+        assert_eq!(**reserved_account.lamports.borrow(), 0);
+        assert_eq!(
+            process_instruction(&program_id, &release_accounts, &release_data),
+            Err(NameServiceError::NameNotReserved.into())
+        );
+
+        // Admin pauses and resumes the program
+        let pause_data = NameInstruction::SetPaused { paused: true }.pack();
+        process_instruction(&program_id, &accounts[..2], &pause_data).unwrap();
+        assert!(
+            Config::unpack_from_slice(&accounts[0].data.borrow())
+                .unwrap()
+                .paused
+        );
+        let resume_data = NameInstruction::SetPaused { paused: false }.pack();
+        process_instruction(&program_id, &accounts[..2], &resume_data).unwrap();
+        assert!(
+            !Config::unpack_from_slice(&accounts[0].data.borrow())
+                .unwrap()
+                .paused
+        );
+
+        // Admin changes the fees and hands config over
+        let new_admin_address = Pubkey::new_unique();
+        let new_registry_address = Pubkey::new_unique();
+        let instruction_data = NameInstruction::UpdateConfig {
+            admin: new_admin_address,
+            beneficiaries,
+            fees: [42; FEE_TIERS],
+            registry: new_registry_address,
+        }
+        .pack();
+        process_instruction(&program_id, &accounts[..2], &instruction_data).unwrap();
+        let config = Config::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(config.admin, new_admin_address);
+        assert_eq!(config.fees, [42; FEE_TIERS]);
+        assert_eq!(config.registry, new_registry_address);
+        // Previous admin lost access
+        assert_eq!(
+            process_instruction(&program_id, &accounts[..2], &instruction_data),
+            Err(NameServiceError::NotAdmin.into())
+        );
+        assert_eq!(
+            process_instruction(&program_id, &accounts[..2], &pause_data),
+            Err(NameServiceError::NotAdmin.into())
+        );
+        accounts[1].key = &new_admin_address;
+        accounts[1].is_signer = false;
+        assert_eq!(
+            process_instruction(&program_id, &accounts[..2], &instruction_data),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+    #[test]
+    fn test_fee_for() {
+        let config = Config {
+            is_initialized: true,
+            admin: Pubkey::new_unique(),
+            beneficiaries: [Beneficiary {
+                address: Pubkey::new_unique(),
+                share: 2_500,
+            }; MAX_BENEFICIARIES],
+            fees: [10, 8, 6, 4, REGISTRATION_FEE],
+            paused: true,
+            registry: Pubkey::new_unique(),
+        };
+        assert_eq!(config.fee_for(1), 10);
+        assert_eq!(config.fee_for(3), 6);
+        assert_eq!(config.fee_for(4), 4);
+        assert_eq!(config.fee_for(6), REGISTRATION_FEE);
+        // Packed schedule keeps tier order
+        let mut config_data = vec![0; CONFIG_DATA_SIZE];
+        Config::pack_into_slice(&config, &mut config_data);
+        assert_eq!(Config::unpack_from_slice(&config_data).unwrap(), config);
+    }
+    #[test]
+    fn test_record_layout() {
+        // Records written before long names decode unchanged
+        let account_address = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut legacy_name = [0u8; PADDED_NAME_SIZE];
+        legacy_name[..4].copy_from_slice(b"bruh");
+        let legacy_data = [
+            &account_address.to_bytes()[..],
+            &legacy_name,
+            &[1],
+            &7u64.to_le_bytes(),
+            &owner.to_bytes(),
+        ]
+        .concat();
+        let account_record = AccountRecord {
+            account_address,
+            name: b"bruh".to_vec(),
+            is_initialized: true,
+            index: 7,
+            owner,
+            ..Default::default()
+        };
+        assert_eq!(
+            AccountRecord::unpack_from_slice(&legacy_data).unwrap(),
+            account_record
+        );
+        let mut data = vec![0; LEGACY_STORAGE_DATA_SIZE];
+        account_record.pack_into_slice(&mut data);
+        assert_eq!(data, legacy_data);
+        // Records from before owners still decode, without an owner
+        let baseline_data = &legacy_data[..BASELINE_STORAGE_DATA_SIZE];
+        let baseline_record = AccountRecord {
+            owner: Pubkey::default(),
+            ..account_record.clone()
+        };
+        assert_eq!(
+            AccountRecord::unpack_from_slice(baseline_data).unwrap(),
+            baseline_record
+        );
+        let mut data = vec![0; BASELINE_STORAGE_DATA_SIZE];
+        baseline_record.pack_into_slice(&mut data);
+        assert_eq!(data, baseline_data);
+        // Sizes records never had are rejected
+        for len in &[0, BASELINE_STORAGE_DATA_SIZE - 1, 90, STORAGE_DATA_SIZE - 1] {
+            assert_eq!(
+                AccountRecord::unpack_from_slice(&legacy_data.repeat(2)[..*len]),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+        // New records carry the subdomain fields
+        let subdomain_record = AccountRecord {
+            parent: Pubkey::new_unique(),
+            subdomain_fee: 5,
+            ..account_record.clone()
+        };
+        assert_eq!(subdomain_record.packed_len(), STORAGE_DATA_SIZE);
+        let mut data = vec![0; subdomain_record.packed_len()];
+        subdomain_record.pack_into_slice(&mut data);
+        assert_eq!(
+            AccountRecord::unpack_from_slice(&data).unwrap(),
+            subdomain_record
+        );
+
+        // Long names grow the account
+        let long_record = AccountRecord {
+            name: "\u{1f980} a name that does not fit thirty two bytes"
+                .as_bytes()
+                .to_vec(),
+            ..account_record
+        };
+        let mut data = vec![0; long_record.packed_len()];
+        long_record.pack_into_slice(&mut data);
+        assert_eq!(
+            AccountRecord::unpack_from_slice(&data).unwrap(),
+            long_record
+        );
+        let reverse_record = ReverseRecord {
+            account_address,
+            name: long_record.name.clone(),
+            is_initialized: true,
+            index: 7,
+        };
+        let mut data = vec![0; REVERSE_DATA_SIZE + MAX_NAME_EXTENSION_LEN];
+        reverse_record.pack_into_slice(&mut data);
+        assert_eq!(
+            ReverseRecord::unpack_from_slice(&data).unwrap(),
+            reverse_record
+        );
+        assert_eq!(
+            ReverseRecord::unpack_from_slice(&data[..REVERSE_DATA_SIZE - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        // Shorter name written over it drops the old extension
+        ReverseRecord::default().pack_into_slice(&mut data);
+        assert_eq!(
+            ReverseRecord::unpack_from_slice(&data).unwrap(),
+            ReverseRecord::default()
+        );
+    }
+    #[test]
+    fn test_subdomain() {
+        let program_id = Pubkey::default();
+        let owner = Pubkey::default();
+        let mut parent_data = vec![0; STORAGE_DATA_SIZE];
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        let mut storage_data2 = vec![0; STORAGE_DATA_SIZE];
+        let mut reverse_data = vec![0; REVERSE_DATA_SIZE];
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut empty_data3 = vec![0; 0];
+        let mut rent_data = vec![0; Rent::size_of()];
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut zero_balance3 = 0;
+        let mut balance_parent_owner = 0;
+        let mut balance_payer = 0;
+        // Account creation does not run outside the runtime, records come
+        // funded for rent
+        let mut balance_storage_account = REGISTRATION_FEE;
+        let mut balance_storage_account2 = REGISTRATION_FEE;
+
+        let (parent_address, _) = find_name_record_address(b"nebula", &program_id);
+        let parent_owner_address = Pubkey::new_unique();
+        let parent = AccountRecord {
+            account_address: Pubkey::new_unique(),
+            name: b"nebula".to_vec(),
+            is_initialized: true,
+            index: 1,
+            owner: parent_owner_address,
+            expires_at: 1000,
+            ..Default::default()
+        };
+        AccountRecord::pack_into_slice(&parent, &mut parent_data);
+        let parent_account = AccountInfo::new(
+            &parent_address,   // account pubkey
+            false,             // is_signer
+            true,              // is_writable
+            &mut zero_balance, // balance in lamports
+            &mut parent_data,  // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let parent_owner = AccountInfo::new(
+            &parent_owner_address,     // account pubkey
+            true,                      // is_signer
+            true,                      // is_writable
+            &mut balance_parent_owner, // balance in lamports
+            &mut empty_data,           // storage
+            &owner,                    // owner pubkey
+            false,                     // is_executable
+            Epoch::default(),          // rent_epoch
+        );
+        let (record_address, _) =
+            find_subdomain_record_address(&parent_address, b"pay", &program_id);
+        let storage_account = AccountInfo::new(
+            &record_address,              // account pubkey
+            false,                        // is_signer
+            true,                         // is_writable
+            &mut balance_storage_account, // balance in lamports
+            &mut storage_data,            // storage
+            &program_id,                  // owner pubkey
+            false,                        // is_executable
+            Epoch::default(),             // rent_epoch
+        );
+        let (record_address2, _) =
+            find_subdomain_record_address(&parent_address, b"shop", &program_id);
+        let storage_account2 = AccountInfo::new(
+            &record_address2,              // account pubkey
+            false,                         // is_signer
+            true,                          // is_writable
+            &mut balance_storage_account2, // balance in lamports
+            &mut storage_data2,            // storage
+            &program_id,                   // owner pubkey
+            false,                         // is_executable
+            Epoch::default(),              // rent_epoch
+        );
+        let account_address = Pubkey::new_unique();
+        let (reverse_address, _) = find_reverse_record_address(&account_address, &program_id);
+        let reverse_account = AccountInfo::new(
+            &reverse_address,   // account pubkey
+            false,              // is_signer
+            true,               // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut reverse_data,  // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let payer_address = Pubkey::new_unique();
+        let payer = AccountInfo::new(
+            &payer_address,     // account pubkey
+            true,               // is_signer
+            true,               // is_writable
+            &mut balance_payer, // balance in lamports
+            &mut empty_data2,   // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut empty_data3,   // storage
+            &owner,             // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut zero_balance4 = 0;
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance4, // balance in lamports
+            &mut rent_data,     // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let clock_id = sysvar::clock::id();
+        let mut zero_balance5 = 0;
+        let mut clock_data = vec![0; Clock::size_of()];
+        let mut clock_account = AccountInfo::new(
+            &clock_id,          // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance5, // balance in lamports
+            &mut clock_data,    // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let clock = Clock {
+            unix_timestamp: 100,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut clock_account).unwrap();
+        let accounts = vec![
+            parent_account.clone(),
+            parent_owner.clone(),
+            storage_account,
+            reverse_account.clone(),
+            payer,
+            system_program.clone(),
+            rent_account.clone(),
+            clock_account.clone(),
+            config_with_pause(&program_id, false),
+            address_signer(&account_address),
+        ];
+        let instruction_data = NameInstruction::CreateSubdomain(Instruction {
+            account_address,
+            name: b"Pay".to_vec(),
+        })
+        .pack();
+
+        // Others can not create subdomains until the owner sets a fee
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::SubdomainsClosed.into())
+        );
+        let fee_accounts = vec![
+            parent_account.clone(),
+            parent_owner.clone(),
+            accounts[8].clone(),
+        ];
+        let fee_data = NameInstruction::SetSubdomainFee { fee: 5 }.pack();
+        process_instruction(&program_id, &fee_accounts, &fee_data).unwrap();
+        assert_eq!(
+            process_instruction(&program_id, &fee_accounts[..1], &fee_data),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // Payer has to cover the fee
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InsufficientFunds.into())
+        );
+        **accounts[4].lamports.borrow_mut() = 5;
+
+        // Fee goes to the parent owner, record links to the parent
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        assert_eq!(**accounts[2].lamports.borrow(), REGISTRATION_FEE);
+        let record = AccountRecord::unpack_from_slice(&accounts[2].data.borrow()).unwrap();
+        assert_eq!(record.name, b"pay.nebula");
+        assert_eq!(record.parent, parent_address);
+        assert_eq!(record.owner, payer_address);
+        assert_eq!(record.account_address, account_address);
+        // Subdomains expire with their parent
+        assert_eq!(record.expires_at, 1000);
+        let reverse = ReverseRecord::unpack_from_slice(&accounts[3].data.borrow()).unwrap();
+        assert_eq!(reverse.name, b"pay.nebula");
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NameAlreadyRegistered.into())
+        );
+
+        // Parent owner creates them for free
+        let accounts2 = vec![
+            parent_account,
+            parent_owner.clone(),
+            storage_account2,
+            reverse_account,
+            parent_owner,
+            system_program,
+            rent_account,
+            clock_account,
+            accounts[8].clone(),
+        ];
+        let instruction_data = NameInstruction::CreateSubdomain(Instruction {
+            account_address,
+            name: b"shop".to_vec(),
+        })
+        .pack();
+        process_instruction(&program_id, &accounts2, &instruction_data).unwrap();
+        let shop = AccountRecord::unpack_from_slice(&accounts2[2].data.borrow()).unwrap();
+        assert_eq!(shop.owner, parent_owner_address);
+        // Address did not sign, so it keeps resolving back to its own claim
+        let reverse = ReverseRecord::unpack_from_slice(&accounts2[3].data.borrow()).unwrap();
+        assert_eq!(reverse.name, b"pay.nebula");
+
+        // Resolution walks down from the top level name
+        let records: Vec<(Pubkey, AccountRecord)> = vec![
+            (parent_address, parent.clone()),
+            (record_address, record),
+            (record_address2, shop),
+        ];
+        let get_record = |address: &Pubkey| {
+            records
+                .iter()
+                .find(|(key, _)| key == address)
+                .map(|(_, record)| record.clone())
+        };
+        assert_eq!(
+            find_record_address_for_name("pay.nebula", &program_id).0,
+            record_address
+        );
+        assert_eq!(
+            resolve_name("PAY.nebula", 100, &program_id, get_record),
+            Some(account_address)
+        );
+        assert_eq!(
+            resolve_name("nebula", 100, &program_id, get_record),
+            Some(parent.account_address)
+        );
+        assert_eq!(
+            resolve_name("www.nebula", 100, &program_id, get_record),
+            None
+        );
+        assert_eq!(resolve_name("pay.", 100, &program_id, get_record), None);
+        assert_eq!(
+            resolve_name("pay.nebula", 1001, &program_id, get_record),
+            None
+        );
+        // Subdomains of an earlier registration of the parent do not resolve
+        let get_stale_record = |address: &Pubkey| {
+            get_record(address).map(|record| AccountRecord {
+                index: if record.parent == Pubkey::default() {
+                    2
+                } else {
+                    1
+                },
+                ..record
+            })
+        };
+        assert_eq!(
+            resolve_name("pay.nebula", 100, &program_id, get_stale_record),
+            None
+        );
+    }
+    #[test]
+    fn test_text() {
+        let program_id = Pubkey::default();
+        let owner = Pubkey::default();
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        let mut text_data = vec![0; TEXT_DATA_SIZE];
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut rent_data = vec![0; Rent::size_of()];
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut zero_balance3 = 0;
+        let mut zero_balance4 = 0;
+        let mut balance_owner = 0;
+
+        let (record_address, _) = find_name_record_address(b"bruh", &program_id);
+        let owner_address = Pubkey::new_unique();
+        let account_record = AccountRecord {
+            account_address: Pubkey::new_unique(),
+            name: b"bruh".to_vec(),
+            is_initialized: true,
+            index: 1,
+            owner: owner_address,
+            ..Default::default()
+        };
+        AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+        let storage_account = AccountInfo::new(
+            &record_address,   // account pubkey
+            false,             // is_signer
+            false,             // is_writable
+            &mut zero_balance, // balance in lamports
+            &mut storage_data, // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let owner_account = AccountInfo::new(
+            &owner_address,     // account pubkey
+            true,               // is_signer
+            true,               // is_writable
+            &mut balance_owner, // balance in lamports
+            &mut empty_data,    // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let (text_address, _) =
+            find_text_record_address(&record_address, TextKey::Github, &program_id);
+        let text_account = AccountInfo::new(
+            &text_address,      // account pubkey
+            false,              // is_signer
+            true,               // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut text_data,     // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut empty_data2,   // storage
+            &owner,             // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance4, // balance in lamports
+            &mut rent_data,     // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let accounts = vec![
+            storage_account,
+            owner_account,
+            text_account,
+            system_program,
+            rent_account,
+            config_with_pause(&program_id, false),
+        ];
+
+        let instruction_data = NameInstruction::SetText {
+            key: TextKey::Github,
+            value: b"Nebula-Wallet".to_vec(),
+        }
+        .pack();
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let text_record = TextRecord::unpack_from_slice(&accounts[2].data.borrow()).unwrap();
+        assert!(text_record.is_initialized);
+        assert_eq!(text_record.record, record_address);
+        assert_eq!(text_record.owner, owner_address);
+        assert_eq!(text_record.key, TextKey::Github);
+        assert_eq!(text_record.value, b"Nebula-Wallet");
+
+        // Key has to match the record account
+        let instruction_data = NameInstruction::SetText {
+            key: TextKey::Url,
+            value: b"https://nebula.example".to_vec(),
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InvalidTextAddress.into())
+        );
+        for value in [Vec::new(), b"\xff".to_vec()] {
+            let instruction_data = NameInstruction::SetText {
+                key: TextKey::Github,
+                value,
+            }
+            .pack();
+            assert_eq!(
+                process_instruction(&program_id, &accounts, &instruction_data),
+                Err(NameServiceError::InvalidTextValue.into())
+            );
+        }
+
+        let instruction_data = NameInstruction::ClearText {
+            key: TextKey::Github,
+        }
+        .pack();
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let text_record = TextRecord::unpack_from_slice(&accounts[2].data.borrow()).unwrap();
+        assert!(!text_record.is_initialized);
+        assert!(text_record.value.is_empty());
+
+        // Only the owner can change text records
+        let other = Pubkey::new_unique();
+        let mut account_record = account_record;
+        account_record.owner = other;
+        account_record.pack_into_slice(&mut accounts[0].data.borrow_mut());
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NotNameOwner.into())
+        );
+    }
+    #[test]
+    fn test_chain_address() {
+        let program_id = Pubkey::default();
+        let owner = Pubkey::default();
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        let mut address_data = vec![0; CHAIN_ADDRESS_DATA_SIZE];
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut rent_data = vec![0; Rent::size_of()];
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut zero_balance3 = 0;
+        let mut zero_balance4 = 0;
+        let mut balance_owner = 0;
+
+        let (record_address, _) = find_name_record_address(b"bruh", &program_id);
+        let owner_address = Pubkey::new_unique();
+        let account_record = AccountRecord {
+            account_address: Pubkey::new_unique(),
+            name: b"bruh".to_vec(),
+            is_initialized: true,
+            index: 1,
+            owner: owner_address,
+            ..Default::default()
+        };
+        AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+        let storage_account = AccountInfo::new(
+            &record_address,   // account pubkey
+            false,             // is_signer
+            false,             // is_writable
+            &mut zero_balance, // balance in lamports
+            &mut storage_data, // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let owner_account = AccountInfo::new(
+            &owner_address,     // account pubkey
+            true,               // is_signer
+            true,               // is_writable
+            &mut balance_owner, // balance in lamports
+            &mut empty_data,    // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let (address_record_address, _) =
+            find_chain_address_record_address(&record_address, COIN_TYPE_ETH, &program_id);
+        let address_account = AccountInfo::new(
+            &address_record_address, // account pubkey
+            false,                   // is_signer
+            true,                    // is_writable
+            &mut zero_balance2,      // balance in lamports
+            &mut address_data,       // storage
+            &program_id,             // owner pubkey
+            false,                   // is_executable
+            Epoch::default(),        // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut empty_data2,   // storage
+            &owner,             // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance4, // balance in lamports
+            &mut rent_data,     // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let accounts = vec![
+            storage_account,
+            owner_account,
+            address_account,
+            system_program,
+            rent_account,
+            config_with_pause(&program_id, false),
+        ];
+
+        let eth_address = vec![0xab; 20];
+        let instruction_data = NameInstruction::SetChainAddress {
+            coin_type: COIN_TYPE_ETH,
+            address: eth_address.clone(),
+        }
+        .pack();
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let chain_address =
+            ChainAddressRecord::unpack_from_slice(&accounts[2].data.borrow()).unwrap();
+        assert!(chain_address.is_initialized);
+        assert_eq!(chain_address.record, record_address);
+        assert_eq!(chain_address.owner, owner_address);
+        assert_eq!(chain_address.coin_type, COIN_TYPE_ETH);
+        assert_eq!(chain_address.address, eth_address);
+        let instruction_data = NameInstruction::SetChainAddress {
+            coin_type: COIN_TYPE_ETH,
+            address: vec![0xab; 32],
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InvalidChainAddress.into())
+        );
+        let instruction_data = NameInstruction::SetChainAddress {
+            coin_type: COIN_TYPE_SOL,
+            address: vec![0xab; 32],
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InvalidChainAddressRecord.into())
+        );
+
+        // Resolver falls back to the name record only for Solana
+        let address_account_data =
+            ChainAddressRecord::unpack_from_slice(&accounts[2].data.borrow()).unwrap();
+        let get_record = |address: &Pubkey| {
+            if *address == record_address {
+                Some(account_record.clone())
+            } else {
+                None
+            }
+        };
+        let get_chain_address = |address: &Pubkey| {
+            if *address == address_record_address {
+                Some(address_account_data.clone())
+            } else {
+                None
+            }
+        };
+        assert_eq!(
+            resolve_chain_address(
+                "Bruh",
+                0,
+                COIN_TYPE_ETH,
+                &program_id,
+                get_record,
+                get_chain_address
+            ),
+            Some(eth_address)
+        );
+        assert_eq!(
+            resolve_chain_address(
+                "bruh",
+                0,
+                COIN_TYPE_SOL,
+                &program_id,
+                get_record,
+                get_chain_address
+            ),
+            Some(account_record.account_address.to_bytes().to_vec())
+        );
+        assert_eq!(
+            resolve_chain_address(
+                "bruh",
+                0,
+                COIN_TYPE_BTC,
+                &program_id,
+                get_record,
+                get_chain_address
+            ),
+            None
+        );
+        // Addresses set before the name changed hands are stale
+        let taken_over = AccountRecord {
+            owner: Pubkey::new_unique(),
+            ..account_record.clone()
+        };
+        let get_taken_over = |address: &Pubkey| {
+            if *address == record_address {
+                Some(taken_over.clone())
+            } else {
+                None
+            }
+        };
+        assert_eq!(
+            resolve_chain_address(
+                "bruh",
+                0,
+                COIN_TYPE_ETH,
+                &program_id,
+                get_taken_over,
+                get_chain_address
+            ),
+            None
+        );
+
+        let instruction_data = NameInstruction::ClearChainAddress {
+            coin_type: COIN_TYPE_ETH,
+        }
+        .pack();
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let chain_address =
+            ChainAddressRecord::unpack_from_slice(&accounts[2].data.borrow()).unwrap();
+        assert!(!chain_address.is_initialized);
+        assert!(chain_address.address.is_empty());
+    }
+    #[test]
+    fn test_is_valid_chain_address() {
+        let p2pkh = [&[0x76, 0xa9, 0x14][..], &[1; 20], &[0x88, 0xac]].concat();
+        let p2sh = [&[0xa9, 0x14][..], &[1; 20], &[0x87]].concat();
+        let p2wpkh = [&[0x00, 0x14][..], &[1; 20]].concat();
+        let p2tr = [&[0x51, 0x20][..], &[1; 32]].concat();
+        for script in &[&p2pkh, &p2sh, &p2wpkh, &p2tr] {
+            assert!(is_valid_chain_address(COIN_TYPE_BTC, script));
+            assert!(is_valid_chain_address(COIN_TYPE_LTC, script));
+        }
+        assert!(!is_valid_chain_address(COIN_TYPE_BTC, &p2pkh[..24]));
+        assert!(!is_valid_chain_address(COIN_TYPE_BTC, &[0x01, 0x14]));
+        assert!(!is_valid_chain_address(COIN_TYPE_BTC, &[1; 20]));
+        assert!(is_valid_chain_address(COIN_TYPE_ETC, &[1; 20]));
+        assert!(!is_valid_chain_address(COIN_TYPE_SOL, &[1; 20]));
+        // Unknown chains only have their length bounded
+        assert!(is_valid_chain_address(118, &[1; 45]));
+        assert!(!is_valid_chain_address(118, &[]));
+        assert!(!is_valid_chain_address(
+            118,
+            &[1; MAX_CHAIN_ADDRESS_LENGTH + 1]
+        ));
+    }
+    #[test]
+    fn test_renew() {
+        let program_id = Pubkey::default();
+        let owner = Pubkey::default();
+        let mut config_data = vec![0; CONFIG_DATA_SIZE];
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        let mut parent_data = vec![0; STORAGE_DATA_SIZE];
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut empty_data3 = vec![0; 0];
+        let mut clock_data = vec![0; Clock::size_of()];
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut zero_balance3 = 0;
+        let mut zero_balance4 = 0;
+        let mut zero_balance5 = 0;
+        let mut zero_balance6 = 0;
+        let mut balance_payer = REGISTRATION_FEE;
+
+        let (treasury, _) = find_treasury_address(&program_id);
+        let config = Config {
+            is_initialized: true,
+            admin: Pubkey::new_unique(),
+            fees: [10, 8, 6, 4, REGISTRATION_FEE],
+            ..Default::default()
+        };
+        Config::pack_into_slice(&config, &mut config_data);
+        let (config_address, _) = find_config_address(&program_id);
+        let config_account = AccountInfo::new(
+            &config_address,   // account pubkey
+            false,             // is_signer
+            false,             // is_writable
+            &mut zero_balance, // balance in lamports
+            &mut config_data,  // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let payment_account = AccountInfo::new(
+            &treasury,          // account pubkey
+            false,              // is_signer
+            true,               // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut empty_data,    // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let (record_address, _) = find_name_record_address(b"bruh", &program_id);
+        let account_record = AccountRecord {
+            account_address: Pubkey::new_unique(),
+            name: b"bruh".to_vec(),
+            is_initialized: true,
+            index: 1,
+            owner: Pubkey::new_unique(),
+            expires_at: 1000,
+            ..Default::default()
+        };
+        AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+        let storage_account = AccountInfo::new(
+            &record_address,    // account pubkey
+            false,              // is_signer
+            true,               // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut storage_data,  // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let payer_address = Pubkey::new_unique();
+        let payer = AccountInfo::new(
+            &payer_address,     // account pubkey
+            true,               // is_signer
+            true,               // is_writable
+            &mut balance_payer, // balance in lamports
+            &mut empty_data2,   // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance4, // balance in lamports
+            &mut empty_data3,   // storage
+            &owner,             // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let clock_id = sysvar::clock::id();
+        let mut clock_account = AccountInfo::new(
+            &clock_id,          // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance5, // balance in lamports
+            &mut clock_data,    // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let set_time = |clock_account: &mut AccountInfo, unix_timestamp| {
+            let clock = Clock {
+                unix_timestamp,
+                ..Clock::default()
+            };
+            clock.to_account_info(clock_account).unwrap();
+        };
+        set_time(&mut clock_account, 1000 + GRACE_PERIOD);
+        let (parent_address, _) = find_name_record_address(b"nebula", &program_id);
+        let parent = AccountRecord {
+            name: b"nebula".to_vec(),
+            is_initialized: true,
+            index: 1,
+            expires_at: 5000 + GRACE_PERIOD,
+            ..Default::default()
+        };
+        AccountRecord::pack_into_slice(&parent, &mut parent_data);
+        let parent_account = AccountInfo::new(
+            &parent_address,    // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance6, // balance in lamports
+            &mut parent_data,   // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let mut accounts = vec![
+            config_account,
+            payment_account,
+            storage_account,
+            payer,
+            system_program,
+            clock_account,
+            parent_account,
+        ];
+        let instruction_data = NameInstruction::Renew.pack();
+
+        // Renewal in the grace period is left to the owner and extends the
+        // old expiry
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NotNameOwner.into())
+        );
+        let mut owned_record = account_record.clone();
+        owned_record.owner = payer_address;
+        owned_record.pack_into_slice(&mut accounts[2].data.borrow_mut());
+        // Names keep expiring while the program is paused, so owners can
+        // still renew
+        let paused_config = Config {
+            paused: true,
+            ..config
+        };
+        Config::pack_into_slice(&paused_config, &mut accounts[0].data.borrow_mut());
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let renewed = AccountRecord::unpack_from_slice(&accounts[2].data.borrow()).unwrap();
+        assert_eq!(renewed.expires_at, 1000 + REGISTRATION_PERIOD);
+        Config::pack_into_slice(&config, &mut accounts[0].data.borrow_mut());
+
+        // Before expiry anyone can pay
+        let unexpired = AccountRecord {
+            expires_at: renewed.expires_at,
+            ..account_record.clone()
+        };
+        unexpired.pack_into_slice(&mut accounts[2].data.borrow_mut());
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let renewed = AccountRecord::unpack_from_slice(&accounts[2].data.borrow()).unwrap();
+        assert_eq!(renewed.expires_at, 1000 + 2 * REGISTRATION_PERIOD);
+        set_time(
+            &mut accounts[5],
+            1000 + 2 * REGISTRATION_PERIOD + GRACE_PERIOD + 1,
+        );
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NameExpired.into())
+        );
+
+        // Subdomains follow their parent
+        let subdomain = AccountRecord {
+            parent: parent_address,
+            ..account_record.clone()
+        };
+        subdomain.pack_into_slice(&mut accounts[2].data.borrow_mut());
+        set_time(&mut accounts[5], 1000);
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let renewed = AccountRecord::unpack_from_slice(&accounts[2].data.borrow()).unwrap();
+        assert_eq!(renewed.expires_at, 5000 + GRACE_PERIOD);
+        assert_eq!(
+            process_instruction(&program_id, &accounts[..6], &instruction_data),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // Names from before expiry stay registered for good
+        let legacy = AccountRecord {
+            expires_at: 0,
+            ..account_record
+        };
+        legacy.pack_into_slice(&mut accounts[2].data.borrow_mut());
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NameDoesNotExpire.into())
+        );
+        assert!(!legacy.is_expired(i64::MAX));
+        assert!(!legacy.is_available(i64::MAX));
+    }
+    #[test]
+    fn test_close() {
+        let program_id = Pubkey::default();
+        let owner = Pubkey::default();
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        let mut reverse_data = vec![0; REVERSE_DATA_SIZE];
+        let mut text_data = vec![0; TEXT_DATA_SIZE];
+        let mut other_text_data = vec![0; TEXT_DATA_SIZE];
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut balance_storage_account = 10;
+        let mut balance_reverse_account = 20;
+        let mut balance_text_account = 30;
+        let mut balance_other_text_account = 40;
+        let mut balance_owner = 0;
+        let mut balance_destination = 0;
+
+        let (record_address, _) = find_name_record_address(b"bruh", &program_id);
+        let owner_address = Pubkey::new_unique();
+        let account_record = AccountRecord {
+            account_address: Pubkey::new_unique(),
+            name: b"bruh".to_vec(),
+            is_initialized: true,
+            index: 1,
+            owner: owner_address,
+            ..Default::default()
+        };
+        AccountRecord::pack_into_slice(&account_record, &mut storage_data);
+        let reverse_record = ReverseRecord {
+            account_address: account_record.account_address,
+            name: account_record.name.clone(),
+            is_initialized: true,
+            index: 1,
+        };
+        ReverseRecord::pack_into_slice(&reverse_record, &mut reverse_data);
+        let text_record = TextRecord {
+            is_initialized: true,
+            record: record_address,
+            owner: owner_address,
+            key: TextKey::Url,
+            value: b"https://nebula.example".to_vec(),
+        };
+        TextRecord::pack_into_slice(&text_record, &mut text_data);
+        let other_text_record = TextRecord {
+            record: Pubkey::new_unique(),
+            ..text_record.clone()
+        };
+        TextRecord::pack_into_slice(&other_text_record, &mut other_text_data);
+        let storage_account = AccountInfo::new(
+            &record_address,              // account pubkey
+            false,                        // is_signer
+            true,                         // is_writable
+            &mut balance_storage_account, // balance in lamports
+            &mut storage_data,            // storage
+            &program_id,                  // owner pubkey
+            false,                        // is_executable
+            Epoch::default(),             // rent_epoch
+        );
+        let owner_account = AccountInfo::new(
+            &owner_address,     // account pubkey
+            true,               // is_signer
+            false,              // is_writable
+            &mut balance_owner, // balance in lamports
+            &mut empty_data,    // storage
+            &owner,             // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let destination_address = Pubkey::new_unique();
+        let destination = AccountInfo::new(
+            &destination_address,     // account pubkey
+            false,                    // is_signer
+            true,                     // is_writable
+            &mut balance_destination, // balance in lamports
+            &mut empty_data2,         // storage
+            &owner,                   // owner pubkey
+            false,                    // is_executable
+            Epoch::default(),         // rent_epoch
+        );
+        let (reverse_address, _) =
+            find_reverse_record_address(&account_record.account_address, &program_id);
+        let reverse_account = AccountInfo::new(
+            &reverse_address,             // account pubkey
+            false,                        // is_signer
+            true,                         // is_writable
+            &mut balance_reverse_account, // balance in lamports
+            &mut reverse_data,            // storage
+            &program_id,                  // owner pubkey
+            false,                        // is_executable
+            Epoch::default(),             // rent_epoch
+        );
+        let (text_address, _) =
+            find_text_record_address(&record_address, TextKey::Url, &program_id);
+        let text_account = AccountInfo::new(
+            &text_address,             // account pubkey
+            false,                     // is_signer
+            true,                      // is_writable
+            &mut balance_text_account, // balance in lamports
+            &mut text_data,            // storage
+            &program_id,               // owner pubkey
+            false,                     // is_executable
+            Epoch::default(),          // rent_epoch
+        );
+        let other_text_address = Pubkey::new_unique();
+        let other_text_account = AccountInfo::new(
+            &other_text_address,             // account pubkey
+            false,                           // is_signer
+            true,                            // is_writable
+            &mut balance_other_text_account, // balance in lamports
+            &mut other_text_data,            // storage
+            &program_id,                     // owner pubkey
+            false,                           // is_executable
+            Epoch::default(),                // rent_epoch
+        );
+        let mut accounts = vec![
+            storage_account,
+            owner_account,
+            destination,
+            reverse_account,
+            text_account,
+            other_text_account,
+        ];
+        let instruction_data = NameInstruction::Close.pack();
+
+        // Records of other names can not be closed with it
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InvalidNameAccount.into())
+        );
+        accounts.pop();
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        assert_eq!(**accounts[2].lamports.borrow(), 60);
+        for closed in &[&accounts[0], &accounts[3], &accounts[4]] {
+            assert_eq!(**closed.lamports.borrow(), 0);
+            assert!(closed.data.borrow().iter().all(|byte| *byte == 0));
+        }
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NameNotRegistered.into())
+        );
+    }
+    #[test]
+    fn test_normalize_name() {
+        let mut expected = [0u8; 32];
+        expected[..4].copy_from_slice(b"bruh");
+        let name = normalize_name(b"Bruh\0\0").unwrap();
+        assert_eq!(name_seed(name.as_bytes()), expected);
+        assert_eq!(
+            find_name_record_address(name.as_bytes(), &Pubkey::default()),
+            find_name_record_address(b"bruh", &Pubkey::default())
+        );
+        // Names that do not fit a seed are hashed
+        let long_name = "a".repeat(PADDED_NAME_SIZE + 1);
+        assert_eq!(
+            name_seed(long_name.as_bytes()),
+            hash(long_name.as_bytes()).to_bytes()
+        );
+        assert_eq!(
+            normalize_name(b"").map_err(NameServiceError::from),
+            Err(NameServiceError::EmptyName)
+        );
+        assert_eq!(
+            normalize_name(b"bruh\0junk").map_err(NameServiceError::from),
+            Err(NameServiceError::InvalidName)
+        );
     }
 }
@@ -4,21 +4,25 @@ import {
   TransactionInstruction,
   PublicKey,
   encodeData,
-  Account
+  Account,
+  SYSVAR_CLOCK_PUBKEY,
+  SYSVAR_RENT_PUBKEY
 } from '@solana/web3.js'
+import { createHash, randomBytes } from 'crypto'
 import { getOurAccount } from './ourAccount'
 import { getNodeConnection } from './nodeConnection'
-import { makeAccount } from './deploy'
 import { getStore } from './storeConfig'
 import { sendAndConfirmTransaction } from './util/send-and-confirm-transaction'
 import { airDrop } from './util/air-drop'
+import { encodeName, nameSeed, normalizeName, reservedNameHash } from './util/normalize-name'
+import { sleep } from './util/sleep'
 
 const main = async () => {
   const ourAccount = await getOurAccount()
 
   const connection = await getNodeConnection()
   const store = await getStore(connection, 'account-name-service.json')
-  const storeProxy = await getStore(connection, 'proxy-pointer.json')
+  const registryStore = await getStore(connection, 'registry.json')
   const counterStore = await getStore(connection, 'counter.json')
 
   console.log(counterStore.accountId.toString())
@@ -50,37 +54,91 @@ const main = async () => {
       process.exit()
     }
   })
-  const storageAccount = await makeAccount(connection, newAccount, 73, store.programId)
+  // Program stores names normalized, record address is derived from that form
+  const name = normalizeName('Bruh')
+  const [storageAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('name'), nameSeed(name)],
+    store.programId
+  )
+  const [configAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('config')],
+    store.programId
+  )
+  const [treasuryAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('treasury')],
+    store.programId
+  )
+  // Registration checks the name against the reserved names
+  const [reservedAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('reserved'), reservedNameHash(name)],
+    store.programId
+  )
+  const [reverseAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('reverse'), newAccount.publicKey.toBuffer()],
+    store.programId
+  )
+  // Commit to the name first so it can not be front-run when revealed
+  const salt = randomBytes(32)
+  const commitment = createHash('sha256')
+    .update(Buffer.from(name, 'utf8'))
+    .update(salt)
+    .update(newAccount.publicKey.toBuffer())
+    .digest()
+  const [commitmentAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('commit'), newAccount.publicKey.toBuffer(), commitment],
+    store.programId
+  )
+  // Commit instruction: tag 11, commitment
+  const commitInstruction = new TransactionInstruction({
+    keys: [
+      { pubkey: commitmentAccount, isSigner: false, isWritable: true },
+      { pubkey: newAccount.publicKey, isSigner: true, isWritable: true },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: false },
+      // Config is read to refuse commits while the program is paused
+      { pubkey: configAccount, isSigner: false, isWritable: false }
+    ],
+    programId: store.programId,
+    data: Buffer.concat([Buffer.from([11]), commitment])
+  })
   await sendAndConfirmTransaction(
-    'transfer 1 SOL',
+    'commit name',
     connection,
-    new Transaction().add(
-      SystemProgram.transfer({
-        fromPubkey: newAccount.publicKey,
-        toPubkey: storageAccount,
-        lamports: 1 * 1e9
-      })
-    ),
+    new Transaction().add(commitInstruction),
     newAccount
   )
+  // Reveal has to land in a later slot than the commitment
+  await sleep(1000)
 
-  const name = Buffer.alloc(32)
-  name.write('Bruh')
-  const instruction_data = Buffer.concat([newAccount.publicKey.toBuffer(), name]) // 64 bytes
+  // Register instruction: tag 0, salt, address, name length, name
+  const instruction_data = Buffer.concat([
+    Buffer.from([0]),
+    salt,
+    newAccount.publicKey.toBuffer(),
+    encodeName(name)
+  ])
   console.log(instruction_data.length)
   const instruction = new TransactionInstruction({
     keys: [
-      // This account must match one in smartcontract
-      { pubkey: ourAccount.publicKey, isSigner: false, isWritable: true },
-      // This account must match one in smartcontract
+      { pubkey: configAccount, isSigner: false, isWritable: false },
+      { pubkey: treasuryAccount, isSigner: false, isWritable: true },
+      // Has to be the registry in the program config
       {
-        pubkey: storeProxy.accountId,
+        pubkey: registryStore.accountId,
         isSigner: false,
-        isWritable: true
+        isWritable: false
       },
       // This account must match one in smartcontract
       { pubkey: counterAccount, isSigner: false, isWritable: true },
-      { pubkey: storageAccount, isSigner: false, isWritable: true }
+      { pubkey: storageAccount, isSigner: false, isWritable: true },
+      { pubkey: reverseAccount, isSigner: false, isWritable: true },
+      { pubkey: newAccount.publicKey, isSigner: true, isWritable: true },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: commitmentAccount, isSigner: false, isWritable: true },
+      { pubkey: SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: reservedAccount, isSigner: false, isWritable: false }
     ],
     programId: store.programId,
     data: instruction_data
@@ -4,15 +4,16 @@ import {
   TransactionInstruction,
   PublicKey,
   encodeData,
-  Account
+  Account,
+  SYSVAR_RENT_PUBKEY
 } from '@solana/web3.js'
 import { getOurAccount } from './ourAccount'
 import { getNodeConnection } from './nodeConnection'
-import { makeAccount } from './deploy'
 import { getStore } from './storeConfig'
 import { sendAndConfirmTransaction } from './util/send-and-confirm-transaction'
 import { createToken } from './createToken'
 import { airDrop } from './util/air-drop'
+import { encodeName, normalizeName, reservedNameHash } from './util/normalize-name'
 
 const main = async () => {
   const ourAccount = await getOurAccount()
@@ -41,19 +42,7 @@ const main = async () => {
       process.exit()
     }
   })
-  const storageAccount = await makeAccount(connection, newAccount, 64, store.programId)
-  await sendAndConfirmTransaction(
-    'transfer 1 SOL',
-    connection,
-    new Transaction().add(
-      SystemProgram.transfer({
-        fromPubkey: newAccount.publicKey,
-        toPubkey: storageAccount,
-        lamports: 1 * 1e9
-      })
-    ),
-    newAccount
-  )
+  const name = normalizeName('test name')
   const tokenId = await createToken(
     connection,
     newAccount,
@@ -61,19 +50,42 @@ const main = async () => {
     undefined,
     newAccount.publicKey.toString()
   )
-  const instruction_data = Buffer.alloc(32)
-  instruction_data.write('test name')
+  // Program creates the record at the address derived from the token
+  const [storageAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('token'), new PublicKey(tokenId).toBuffer()],
+    store.programId
+  )
+  const [configAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('config')],
+    store.programId
+  )
+  const [treasuryAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('treasury')],
+    store.programId
+  )
+  // Registration checks the name against the reserved names
+  const [reservedAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('reserved'), reservedNameHash(name)],
+    store.programId
+  )
+  // Register instruction: tag 0, name length, name
+  const instruction_data = Buffer.concat([Buffer.from([0]), encodeName(name)])
   const instruction = new TransactionInstruction({
     keys: [
-      // This account must match one in smartcontract
-      { pubkey: ourAccount.publicKey, isSigner: false, isWritable: true },
+      { pubkey: configAccount, isSigner: false, isWritable: false },
+      { pubkey: treasuryAccount, isSigner: false, isWritable: true },
       {
         pubkey: new PublicKey(tokenId),
         isSigner: false,
         isWritable: true
       },
       { pubkey: newAccount.publicKey, isSigner: true, isWritable: true },
-      { pubkey: storageAccount, isSigner: false, isWritable: true }
+      { pubkey: storageAccount, isSigner: false, isWritable: true },
+      // Payer
+      { pubkey: newAccount.publicKey, isSigner: true, isWritable: true },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: reservedAccount, isSigner: false, isWritable: false }
     ],
     programId: store.programId,
     data: instruction_data
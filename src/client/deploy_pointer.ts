@@ -12,7 +12,8 @@ import { getOurAccount } from './ourAccount'
 import { getNodeConnection } from './nodeConnection'
 import { getStore, setStore } from './storeConfig'
 
-import { estCostLoadProgram, loadProgram, estCostMakeAccount, makeAccount } from './deploy'
+import { estCostLoadProgram, loadProgram } from './deploy'
+import { findPointerAddress, findRegistryAddress, pointerSeed } from './setPointer'
 
 import * as fs from 'fs'
 
@@ -78,9 +79,22 @@ async function main() {
     ') Sol'
   )
 
-  const proxyAccount = await makeAccount(connection, ourAccount, 33, programId)
-  console.log(`Created pointer: ${proxyAccount.toString()}`)
+  // Program creates the pointer on initialize, only its address is known here
+  const proxyAccount = await findPointerAddress(
+    programId,
+    ourAccount.publicKey,
+    pointerSeed('counter')
+  )
+  console.log(`Pointer address: ${proxyAccount.toString()}`)
   await setStore('proxy-pointer.json', programId, proxyAccount)
+  // Name services resolve the counter and their other accounts through it
+  const registryAccount = await findRegistryAddress(
+    programId,
+    ourAccount.publicKey,
+    pointerSeed('registry')
+  )
+  console.log(`Registry address: ${registryAccount.toString()}`)
+  await setStore('registry.json', programId, registryAccount)
   console.log('-----')
 }
 
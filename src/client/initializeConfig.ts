@@ -0,0 +1,104 @@
+import {
+  Transaction,
+  SystemProgram,
+  TransactionInstruction,
+  PublicKey,
+  SYSVAR_RENT_PUBKEY
+} from '@solana/web3.js'
+import { getDeployerAccount, getOurAccount } from './ourAccount'
+import { getNodeConnection } from './nodeConnection'
+import { getStore } from './storeConfig'
+import { sendAndConfirmTransaction } from './util/send-and-confirm-transaction'
+
+// InitializeConfig tag differs between programs
+const INITIALIZE_CONFIG_TAG: { [program: string]: number } = {
+  'account-name-service': 3,
+  'token-name-service': 1
+}
+const UPDATE_CONFIG_TAG: { [program: string]: number } = {
+  'account-name-service': 4,
+  'token-name-service': 2
+}
+
+const main = async () => {
+  const program = process.argv[2] || 'account-name-service'
+  const ourAccount = await getOurAccount()
+  const deployer = await getDeployerAccount()
+  const connection = await getNodeConnection()
+  const store = await getStore(connection, `${program}.json`)
+
+  const [configAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('config')],
+    store.programId
+  )
+  // Fees collect in the program treasury
+  const [treasuryAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('treasury')],
+    store.programId
+  )
+  // Withdrawals go to our account, or are split with a partner given as
+  // `<partner pubkey> <partner share in basis points>`
+  const partnerShare = process.argv[3] ? Number(process.argv[4]) : 0
+  const shares: [PublicKey, number][] = [[ourAccount.publicKey, 10000 - partnerShare]]
+  if (process.argv[3]) {
+    shares.push([new PublicKey(process.argv[3]), partnerShare])
+  }
+  // 4 slots of address and u16 share, unused slots stay zero
+  const beneficiaries = Buffer.alloc(34 * 4)
+  shares.forEach(([address, share], slot) => {
+    address.toBuffer().copy(beneficiaries, slot * 34)
+    beneficiaries.writeUInt16LE(share, slot * 34 + 32)
+  })
+  // Priced by name length: 1, 2, 3, 4 and 5+ characters
+  const feesInSol = [100, 50, 20, 5, 1]
+  const fees = Buffer.alloc(8 * feesInSol.length)
+  feesInSol.forEach((sol, tier) => {
+    const lamports = sol * 1e9
+    fees.writeUInt32LE(lamports % 2 ** 32, tier * 8)
+    fees.writeUInt32LE(Math.floor(lamports / 2 ** 32), tier * 8 + 4)
+  })
+  // Account names are numbered through the counter of our registry
+  const registry =
+    program === 'account-name-service'
+      ? (await getStore(connection, 'registry.json')).accountId.toBuffer()
+      : Buffer.alloc(0)
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: configAccount, isSigner: false, isWritable: true },
+      { pubkey: deployer.publicKey, isSigner: true, isWritable: true },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: treasuryAccount, isSigner: false, isWritable: true }
+    ],
+    programId: store.programId,
+    data: Buffer.concat([
+      Buffer.from([INITIALIZE_CONFIG_TAG[program]]),
+      beneficiaries,
+      fees,
+      registry
+    ])
+  })
+  // The deployer only initializes, our account takes over as admin
+  const handOver = new TransactionInstruction({
+    keys: [
+      { pubkey: configAccount, isSigner: false, isWritable: true },
+      { pubkey: deployer.publicKey, isSigner: true, isWritable: false }
+    ],
+    programId: store.programId,
+    data: Buffer.concat([
+      Buffer.from([UPDATE_CONFIG_TAG[program]]),
+      ourAccount.publicKey.toBuffer(),
+      beneficiaries,
+      fees,
+      registry
+    ])
+  })
+  await sendAndConfirmTransaction(
+    'Initialize config',
+    connection,
+    new Transaction().add(instruction, handOver),
+    deployer
+  )
+  console.log(`Initialized config of ${program} on address ${configAccount.toString()}`)
+}
+main()
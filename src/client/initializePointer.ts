@@ -10,6 +10,15 @@ import { getOurAccount } from './ourAccount'
 import { getNodeConnection } from './nodeConnection'
 import { makeAccount } from './deploy'
 import { getStore, setStore } from './storeConfig'
+import { Store } from './util/store'
+import {
+  findRegistryAddress,
+  initializeRegistry,
+  pointerSeed,
+  REGISTRY_DATA_SIZE,
+  RegistryKey,
+  setPointer
+} from './setPointer'
 import { sendAndConfirmTransaction } from './util/send-and-confirm-transaction'
 import { createToken } from './createToken'
 import { airDrop } from './util/air-drop'
@@ -19,34 +28,55 @@ const main = async () => {
   console.log('####')
   const connection = await getNodeConnection()
   const store = await getStore(connection, 'account-name-service.json')
-  const storeProxy = await getStore(connection, 'proxy-pointer.json')
+  // Pointer account does not exist until it is initialized below
+  const storeProxy: any = await new Store().load('proxy-pointer.json')
+  const pointerProgramId = new PublicKey(storeProxy.programId)
 
   // Listen for new registration
   const nullAccount = '11111111111111111111111111111111'
-  connection.onProgramAccountChange(storeProxy.programId, (accountInfo, { slot }) => {
+  connection.onProgramAccountChange(pointerProgramId, (accountInfo, { slot }) => {
     const data = accountInfo.accountInfo.data
+    // Registry accounts of the same program are set up below too
+    if (data.length === REGISTRY_DATA_SIZE) {
+      return
+    }
     const key = new PublicKey(data.slice(0, 32))
     if (key.toString() !== nullAccount) {
       // trim empty
       // console.log(msg.replace(/\0/g, ''))
       console.log(`Created Pointer to -> ${key}`)
-      
-      process.exit()
     }
   })
   const counterAccount = await makeAccount(connection, ourAccount, 8, store.programId)
   await setStore('counter.json', counterAccount, counterAccount)
   console.log(`Created counter on address ${counterAccount.toString()}`)
-  const instruction = new TransactionInstruction({
-    keys: [{ pubkey: storeProxy.accountId, isSigner: false, isWritable: true }],
-    programId: storeProxy.programId,
-    data: counterAccount.toBuffer()
-  })
-  await sendAndConfirmTransaction(
-    'Create pointer',
+  await setPointer(connection, ourAccount, pointerProgramId, pointerSeed('counter'), counterAccount)
+
+  const registryAccount = await findRegistryAddress(
+    pointerProgramId,
+    ourAccount.publicKey,
+    pointerSeed('registry')
+  )
+  const [configAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('config')],
+    store.programId
+  )
+  const [treasuryAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('treasury')],
+    store.programId
+  )
+  // Entries set now take effect at once, later changes wait for UPDATE_DELAY
+  const entries: PublicKey[] = []
+  entries[RegistryKey.Counter] = counterAccount
+  entries[RegistryKey.Treasury] = treasuryAccount
+  entries[RegistryKey.Config] = configAccount
+  await initializeRegistry(
     connection,
-    new Transaction().add(instruction),
-    ourAccount
+    ourAccount,
+    pointerProgramId,
+    pointerSeed('registry'),
+    entries
   )
+  console.log(`Created registry on address ${registryAccount.toString()}`)
 }
-main()
+main().then(() => process.exit())
@@ -2,7 +2,8 @@ import { Account } from '@solana/web3.js'
 import * as fs from 'fs'
 
 async function main() {
-  const keypairFile = './keypair.json'
+  // `npm run keypair:deployer` makes the deployer key instead
+  const keypairFile = process.argv[2] || './keypair.json'
 
   if (fs.existsSync(keypairFile)) {
     console.log('The file', keypairFile, 'already exists.')
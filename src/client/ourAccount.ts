@@ -2,8 +2,20 @@ import { Account } from '@solana/web3.js'
 import * as fs from 'fs'
 
 export async function getOurAccount() {
-  const keypairFile = './keypair.json'
+  const account = loadAccount('./keypair.json')
+  console.log('Our account:', account.publicKey.toBase58())
+  return account
+}
+
+// Key the programs are deployed with, the DEPLOYER_ADDRESS of
+// name-service-common. Create it with `npm run keypair:deployer`
+export async function getDeployerAccount() {
+  const account = loadAccount('./deployer.json')
+  console.log('Deployer account:', account.publicKey.toBase58())
+  return account
+}
 
+function loadAccount(keypairFile: string) {
   if (!fs.existsSync(keypairFile)) {
     console.log('The expected keypair file', keypairFile, 'was not found')
     process.exit(1)
@@ -13,7 +25,5 @@ export async function getOurAccount() {
     .split(',')
     .map(x => parseInt(x))
 
-  const account = new Account(checkSecret)
-  console.log('Our account:', account.publicKey.toBase58())
-  return account
+  return new Account(checkSecret)
 }
@@ -0,0 +1,31 @@
+import { PublicKey } from '@solana/web3.js'
+import { getNodeConnection } from './nodeConnection'
+import { Store } from './util/store'
+import { decodePointer } from './setPointer'
+
+// Usage: pointerHistory.ts [pointer address], defaults to the stored pointer
+const main = async () => {
+  const connection = await getNodeConnection()
+  const storeProxy: any = await new Store().load('proxy-pointer.json')
+  const pointerAddress = new PublicKey(process.argv[2] || storeProxy.accountId)
+  const accountInfo = await connection.getAccountInfo(pointerAddress)
+  if (!accountInfo) {
+    console.log(`No pointer at ${pointerAddress.toString()}`)
+    return
+  }
+  const pointer = decodePointer(accountInfo.data)
+  console.log(`Pointer ${pointerAddress.toString()} version ${pointer.version}`)
+  console.log(`  points to ${pointer.tokenAddress.toString()}`)
+  console.log(`  authority ${pointer.authority.toString()}`)
+  if (pointer.pendingUpdate) {
+    console.log(
+      `  pending update to ${pointer.pendingUpdate.tokenAddress.toString()} executable at slot ${pointer.pendingUpdate.executableSlot}`
+    )
+  }
+  for (const entry of pointer.history) {
+    console.log(
+      `  v${entry.version} at slot ${entry.slot} replaced ${entry.tokenAddress.toString()}`
+    )
+  }
+}
+main()
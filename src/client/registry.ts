@@ -0,0 +1,61 @@
+import { PublicKey } from '@solana/web3.js'
+import { getOurAccount } from './ourAccount'
+import { getNodeConnection } from './nodeConnection'
+import { Store } from './util/store'
+import {
+  cancelRegistryEntry,
+  decodeRegistry,
+  executeRegistryEntry,
+  proposeRegistryEntry,
+  RegistryKey
+} from './setPointer'
+
+// Usage: registry.ts | registry.ts set <Counter|Treasury|Config|FeeOracle> <address>
+//   | registry.ts execute | registry.ts cancel
+const main = async () => {
+  const connection = await getNodeConnection()
+  const storeRegistry: any = await new Store().load('registry.json')
+  const pointerProgramId = new PublicKey(storeRegistry.programId)
+  const registryAddress = new PublicKey(storeRegistry.accountId)
+
+  if (process.argv[2] === 'set') {
+    const key = RegistryKey[process.argv[3] as keyof typeof RegistryKey]
+    if (key === undefined) {
+      console.log(`Unknown registry key ${process.argv[3]}`)
+      return
+    }
+    const ourAccount = await getOurAccount()
+    const value = new PublicKey(process.argv[4])
+    await proposeRegistryEntry(
+      connection,
+      ourAccount,
+      pointerProgramId,
+      registryAddress,
+      key,
+      value
+    )
+  } else if (process.argv[2] === 'execute') {
+    await executeRegistryEntry(connection, await getOurAccount(), pointerProgramId, registryAddress)
+  } else if (process.argv[2] === 'cancel') {
+    await cancelRegistryEntry(connection, await getOurAccount(), pointerProgramId, registryAddress)
+  }
+  const accountInfo = await connection.getAccountInfo(registryAddress)
+  if (!accountInfo) {
+    console.log(`No registry at ${registryAddress.toString()}`)
+    return
+  }
+  const registry = decodeRegistry(accountInfo.data)
+  console.log(`Registry ${registryAddress.toString()}`)
+  console.log(`  authority ${registry.authority.toString()}`)
+  registry.entries.forEach((entry, key) => {
+    console.log(`  ${RegistryKey[key]} ${entry.toString()}`)
+  })
+  if (registry.pendingUpdate) {
+    const { key, address, executableSlot } = registry.pendingUpdate
+    console.log(`  pending ${RegistryKey[key]} ${address.toString()} from slot ${executableSlot}`)
+  }
+  registry.history.forEach(({ version, key, address, slot }) => {
+    console.log(`  v${version} ${RegistryKey[key]} was ${address.toString()} until slot ${slot}`)
+  })
+}
+main()
@@ -0,0 +1,75 @@
+import {
+  Transaction,
+  TransactionInstruction,
+  PublicKey,
+  SystemProgram,
+  SYSVAR_RENT_PUBKEY
+} from '@solana/web3.js'
+import { getOurAccount } from './ourAccount'
+import { getNodeConnection } from './nodeConnection'
+import { getStore } from './storeConfig'
+import { sendAndConfirmTransaction } from './util/send-and-confirm-transaction'
+import { normalizeName, reservedNameHash } from './util/normalize-name'
+
+// ReserveName and ReleaseName tags differ between programs
+const RESERVE_TAG: { [program: string]: number } = {
+  'account-name-service': 17,
+  'token-name-service': 6
+}
+const RELEASE_TAG: { [program: string]: number } = {
+  'account-name-service': 18,
+  'token-name-service': 7
+}
+
+// Usage: reserveName.ts <program> <name> [authority | release]
+const main = async () => {
+  const program = process.argv[2] || 'account-name-service'
+  const name = normalizeName(process.argv[3])
+  const ourAccount = await getOurAccount()
+  const connection = await getNodeConnection()
+  const store = await getStore(connection, `${program}.json`)
+
+  const nameHash = reservedNameHash(name)
+  const [configAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('config')],
+    store.programId
+  )
+  const [reservedAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('reserved'), nameHash],
+    store.programId
+  )
+  let instruction
+  if (process.argv[4] === 'release') {
+    instruction = new TransactionInstruction({
+      keys: [
+        { pubkey: configAccount, isSigner: false, isWritable: false },
+        { pubkey: ourAccount.publicKey, isSigner: true, isWritable: false },
+        { pubkey: reservedAccount, isSigner: false, isWritable: true },
+        { pubkey: ourAccount.publicKey, isSigner: false, isWritable: true }
+      ],
+      programId: store.programId,
+      data: Buffer.concat([Buffer.from([RELEASE_TAG[program]]), nameHash])
+    })
+  } else {
+    // Admin claims the name itself unless another authority is given
+    const authority = process.argv[4] ? new PublicKey(process.argv[4]) : ourAccount.publicKey
+    instruction = new TransactionInstruction({
+      keys: [
+        { pubkey: configAccount, isSigner: false, isWritable: false },
+        { pubkey: ourAccount.publicKey, isSigner: true, isWritable: true },
+        { pubkey: reservedAccount, isSigner: false, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false }
+      ],
+      programId: store.programId,
+      data: Buffer.concat([Buffer.from([RESERVE_TAG[program]]), nameHash, authority.toBuffer()])
+    })
+  }
+  await sendAndConfirmTransaction(
+    `Update reserved name ${name}`,
+    connection,
+    new Transaction().add(instruction),
+    ourAccount
+  )
+}
+main()
@@ -0,0 +1,39 @@
+import { Transaction, TransactionInstruction, PublicKey } from '@solana/web3.js'
+import { getOurAccount } from './ourAccount'
+import { getNodeConnection } from './nodeConnection'
+import { getStore } from './storeConfig'
+import { sendAndConfirmTransaction } from './util/send-and-confirm-transaction'
+
+// SetPaused tag differs between programs
+const SET_PAUSED_TAG: { [program: string]: number } = {
+  'account-name-service': 16,
+  'token-name-service': 5
+}
+
+const main = async () => {
+  const program = process.argv[2] || 'account-name-service'
+  const paused = process.argv[3] !== 'false'
+  const ourAccount = await getOurAccount()
+  const connection = await getNodeConnection()
+  const store = await getStore(connection, `${program}.json`)
+
+  const [configAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('config')],
+    store.programId
+  )
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: configAccount, isSigner: false, isWritable: true },
+      { pubkey: ourAccount.publicKey, isSigner: true, isWritable: false }
+    ],
+    programId: store.programId,
+    data: Buffer.from([SET_PAUSED_TAG[program], paused ? 1 : 0])
+  })
+  await sendAndConfirmTransaction(
+    paused ? 'Pause program' : 'Resume program',
+    connection,
+    new Transaction().add(instruction),
+    ourAccount
+  )
+}
+main()
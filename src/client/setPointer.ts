@@ -2,22 +2,110 @@ import {
   Connection,
   PublicKey,
   Account,
+  SystemProgram,
   Transaction,
-  TransactionInstruction
+  TransactionInstruction,
+  SYSVAR_CLOCK_PUBKEY,
+  SYSVAR_RENT_PUBKEY
 } from '@solana/web3.js'
 import { sendAndConfirmTransaction } from './util/send-and-confirm-transaction'
 
+// Previous addresses kept by a pointer, matches HISTORY_LENGTH of the program
+export const HISTORY_LENGTH = 8
+const HISTORY_ENTRY_SIZE = 48
+const PENDING_UPDATE_OFFSET = 73 + HISTORY_ENTRY_SIZE * HISTORY_LENGTH
+
+export interface HistoryEntry {
+  version: number
+  tokenAddress: PublicKey
+  slot: number
+}
+
+export interface Pointer {
+  tokenAddress: PublicKey
+  isInitialized: boolean
+  authority: PublicKey
+  version: number
+  // Newest entry first
+  history: HistoryEntry[]
+  // Proposed update waiting for its delay, null if none
+  pendingUpdate: { tokenAddress: PublicKey; executableSlot: number } | null
+}
+
+function readU64(data: Buffer, offset: number): number {
+  return data.readUInt32LE(offset) + data.readUInt32LE(offset + 4) * 2 ** 32
+}
+
+// Decodes pointer account data, history is ordered like Pointer::history
+export function decodePointer(data: Buffer): Pointer {
+  const version = readU64(data, 65)
+  const history = []
+  for (let age = 0; age < Math.min(version, HISTORY_LENGTH); age++) {
+    const offset = 73 + ((version - 1 - age) % HISTORY_LENGTH) * HISTORY_ENTRY_SIZE
+    history.push({
+      version: readU64(data, offset),
+      tokenAddress: new PublicKey(data.slice(offset + 8, offset + 40)),
+      slot: readU64(data, offset + 40)
+    })
+  }
+  return {
+    tokenAddress: new PublicKey(data.slice(0, 32)),
+    isInitialized: data[32] === 1,
+    authority: new PublicKey(data.slice(33, 65)),
+    version,
+    history,
+    pendingUpdate:
+      data[PENDING_UPDATE_OFFSET] === 1
+        ? {
+            tokenAddress: new PublicKey(
+              data.slice(PENDING_UPDATE_OFFSET + 1, PENDING_UPDATE_OFFSET + 33)
+            ),
+            executableSlot: readU64(data, PENDING_UPDATE_OFFSET + 33)
+          }
+        : null
+  }
+}
+
+// Pointer seeds are 32 bytes, shorter labels are padded with zeros
+export function pointerSeed(label: string): Buffer {
+  const seed = Buffer.alloc(32)
+  seed.write(label, 'utf8')
+  return seed
+}
+
+export async function findPointerAddress(
+  pointerProgramAddress: PublicKey,
+  creator: PublicKey,
+  seed: Buffer
+): Promise<PublicKey> {
+  const [pointerAddress] = await PublicKey.findProgramAddress(
+    [Buffer.from('pointer'), creator.toBuffer(), seed],
+    pointerProgramAddress
+  )
+  return pointerAddress
+}
+
 export async function setPointer(
   connection: Connection,
   ourAccount: Account,
   pointerProgramAddress: PublicKey,
-  pointerAddress: PublicKey,
+  seed: Buffer,
   value: PublicKey
 ) {
+  const pointerAddress = await findPointerAddress(
+    pointerProgramAddress,
+    ourAccount.publicKey,
+    seed
+  )
   const instruction = new TransactionInstruction({
-    keys: [{ pubkey: pointerAddress, isSigner: false, isWritable: true }],
+    keys: [
+      { pubkey: pointerAddress, isSigner: false, isWritable: true },
+      { pubkey: ourAccount.publicKey, isSigner: true, isWritable: true },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false }
+    ],
     programId: pointerProgramAddress,
-    data: value.toBuffer()
+    data: Buffer.concat([Buffer.from([0]), value.toBuffer(), seed]) // Initialize
   })
   await sendAndConfirmTransaction(
     'Create pointer',
@@ -26,3 +114,318 @@ export async function setPointer(
     ourAccount
   )
 }
+
+// Proposes pointing an existing pointer at another address, signed by its
+// authority. It can be executed once UPDATE_DELAY slots passed
+export async function proposePointerUpdate(
+  connection: Connection,
+  authority: Account,
+  pointerProgramAddress: PublicKey,
+  pointerAddress: PublicKey,
+  value: PublicKey
+) {
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: pointerAddress, isSigner: false, isWritable: true },
+      { pubkey: authority.publicKey, isSigner: true, isWritable: false },
+      { pubkey: SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: false }
+    ],
+    programId: pointerProgramAddress,
+    data: Buffer.concat([Buffer.from([2]), value.toBuffer()]) // ProposeUpdate
+  })
+  await sendAndConfirmTransaction(
+    'Propose pointer update',
+    connection,
+    new Transaction().add(instruction),
+    authority
+  )
+}
+
+// Applies a pending update whose delay passed, any account can pay for it
+export async function executePointerUpdate(
+  connection: Connection,
+  payer: Account,
+  pointerProgramAddress: PublicKey,
+  pointerAddress: PublicKey
+) {
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: pointerAddress, isSigner: false, isWritable: true },
+      { pubkey: SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: false }
+    ],
+    programId: pointerProgramAddress,
+    data: Buffer.from([4]) // ExecuteUpdate
+  })
+  await sendAndConfirmTransaction(
+    'Execute pointer update',
+    connection,
+    new Transaction().add(instruction),
+    payer
+  )
+}
+
+// Drops a pending update, signed by the pointer authority
+export async function cancelPointerUpdate(
+  connection: Connection,
+  authority: Account,
+  pointerProgramAddress: PublicKey,
+  pointerAddress: PublicKey
+) {
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: pointerAddress, isSigner: false, isWritable: true },
+      { pubkey: authority.publicKey, isSigner: true, isWritable: false }
+    ],
+    programId: pointerProgramAddress,
+    data: Buffer.from([5]) // CancelUpdate
+  })
+  await sendAndConfirmTransaction(
+    'Cancel pointer update',
+    connection,
+    new Transaction().add(instruction),
+    authority
+  )
+}
+
+// Hands a pointer over to a new authority
+export async function setPointerAuthority(
+  connection: Connection,
+  authority: Account,
+  pointerProgramAddress: PublicKey,
+  pointerAddress: PublicKey,
+  newAuthority: PublicKey
+) {
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: pointerAddress, isSigner: false, isWritable: true },
+      { pubkey: authority.publicKey, isSigner: true, isWritable: false }
+    ],
+    programId: pointerProgramAddress,
+    data: Buffer.concat([Buffer.from([3]), newAuthority.toBuffer()]) // SetAuthority
+  })
+  await sendAndConfirmTransaction(
+    'Set pointer authority',
+    connection,
+    new Transaction().add(instruction),
+    authority
+  )
+}
+
+// Moves a legacy 33 byte pointer to a new pointer derived from the deployer
+// and seed and closes it, the deployer becomes the authority
+export async function migratePointer(
+  connection: Connection,
+  deployer: Account,
+  pointerProgramAddress: PublicKey,
+  legacyPointerAddress: PublicKey,
+  seed: Buffer
+): Promise<PublicKey> {
+  const pointerAddress = await findPointerAddress(pointerProgramAddress, deployer.publicKey, seed)
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: legacyPointerAddress, isSigner: false, isWritable: true },
+      { pubkey: pointerAddress, isSigner: false, isWritable: true },
+      { pubkey: deployer.publicKey, isSigner: true, isWritable: true },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false }
+    ],
+    programId: pointerProgramAddress,
+    data: Buffer.concat([Buffer.from([8]), seed]) // Migrate
+  })
+  await sendAndConfirmTransaction(
+    'Migrate pointer',
+    connection,
+    new Transaction().add(instruction),
+    deployer
+  )
+  return pointerAddress
+}
+
+// Entries of a registry, matches RegistryKey of the program
+export enum RegistryKey {
+  Counter,
+  Treasury,
+  Config,
+  FeeOracle
+}
+const REGISTRY_KEYS = 4
+const REGISTRY_HISTORY_ENTRY_SIZE = 49
+const REGISTRY_HISTORY_OFFSET = 41 + 32 * REGISTRY_KEYS
+const REGISTRY_PENDING_OFFSET =
+  REGISTRY_HISTORY_OFFSET + REGISTRY_HISTORY_ENTRY_SIZE * HISTORY_LENGTH
+export const REGISTRY_DATA_SIZE = REGISTRY_PENDING_OFFSET + 42
+
+export interface RegistryHistoryEntry {
+  version: number
+  key: RegistryKey
+  address: PublicKey
+  slot: number
+}
+
+export interface Registry {
+  isInitialized: boolean
+  authority: PublicKey
+  // Indexed by RegistryKey, unset entries are the default public key
+  entries: PublicKey[]
+  version: number
+  // Newest entry first
+  history: RegistryHistoryEntry[]
+  // Proposed entry waiting for its delay, null if none
+  pendingUpdate: { key: RegistryKey; address: PublicKey; executableSlot: number } | null
+}
+
+// Decodes registry account data, history is ordered like Registry::history
+export function decodeRegistry(data: Buffer): Registry {
+  const entries = []
+  for (let key = 0; key < REGISTRY_KEYS; key++) {
+    entries.push(new PublicKey(data.slice(33 + key * 32, 65 + key * 32)))
+  }
+  const version = readU64(data, REGISTRY_HISTORY_OFFSET - 8)
+  const history = []
+  for (let age = 0; age < Math.min(version, HISTORY_LENGTH); age++) {
+    const offset =
+      REGISTRY_HISTORY_OFFSET + ((version - 1 - age) % HISTORY_LENGTH) * REGISTRY_HISTORY_ENTRY_SIZE
+    history.push({
+      version: readU64(data, offset),
+      key: data[offset + 8],
+      address: new PublicKey(data.slice(offset + 9, offset + 41)),
+      slot: readU64(data, offset + 41)
+    })
+  }
+  return {
+    isInitialized: data[0] === 1,
+    authority: new PublicKey(data.slice(1, 33)),
+    entries,
+    version,
+    history,
+    pendingUpdate:
+      data[REGISTRY_PENDING_OFFSET] === 1
+        ? {
+            key: data[REGISTRY_PENDING_OFFSET + 1],
+            address: new PublicKey(
+              data.slice(REGISTRY_PENDING_OFFSET + 2, REGISTRY_PENDING_OFFSET + 34)
+            ),
+            executableSlot: readU64(data, REGISTRY_PENDING_OFFSET + 34)
+          }
+        : null
+  }
+}
+
+export async function findRegistryAddress(
+  pointerProgramAddress: PublicKey,
+  creator: PublicKey,
+  seed: Buffer
+): Promise<PublicKey> {
+  const [registryAddress] = await PublicKey.findProgramAddress(
+    [Buffer.from('registry'), creator.toBuffer(), seed],
+    pointerProgramAddress
+  )
+  return registryAddress
+}
+
+// Creates a registry owned by ourAccount, entries are indexed by RegistryKey
+// and missing ones stay unset
+export async function initializeRegistry(
+  connection: Connection,
+  ourAccount: Account,
+  pointerProgramAddress: PublicKey,
+  seed: Buffer,
+  entries: PublicKey[]
+) {
+  const registryAddress = await findRegistryAddress(
+    pointerProgramAddress,
+    ourAccount.publicKey,
+    seed
+  )
+  const entriesData = Buffer.alloc(32 * REGISTRY_KEYS)
+  entries.forEach((entry, key) => entry.toBuffer().copy(entriesData, key * 32))
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: registryAddress, isSigner: false, isWritable: true },
+      { pubkey: ourAccount.publicKey, isSigner: true, isWritable: true },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false }
+    ],
+    programId: pointerProgramAddress,
+    data: Buffer.concat([Buffer.from([6]), seed, entriesData]) // InitializeRegistry
+  })
+  await sendAndConfirmTransaction(
+    'Create registry',
+    connection,
+    new Transaction().add(instruction),
+    ourAccount
+  )
+}
+
+// Proposes pointing one registry entry at an address, signed by the registry
+// authority. It can be executed once UPDATE_DELAY slots passed
+export async function proposeRegistryEntry(
+  connection: Connection,
+  authority: Account,
+  pointerProgramAddress: PublicKey,
+  registryAddress: PublicKey,
+  key: RegistryKey,
+  value: PublicKey
+) {
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: registryAddress, isSigner: false, isWritable: true },
+      { pubkey: authority.publicKey, isSigner: true, isWritable: false },
+      { pubkey: SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: false }
+    ],
+    programId: pointerProgramAddress,
+    data: Buffer.concat([Buffer.from([7, key]), value.toBuffer()]) // ProposeRegistryEntry
+  })
+  await sendAndConfirmTransaction(
+    `Propose registry ${RegistryKey[key]}`,
+    connection,
+    new Transaction().add(instruction),
+    authority
+  )
+}
+
+// Applies a pending registry entry whose delay passed, any account can pay for it
+export async function executeRegistryEntry(
+  connection: Connection,
+  payer: Account,
+  pointerProgramAddress: PublicKey,
+  registryAddress: PublicKey
+) {
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: registryAddress, isSigner: false, isWritable: true },
+      { pubkey: SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: false }
+    ],
+    programId: pointerProgramAddress,
+    data: Buffer.from([9]) // ExecuteRegistryEntry
+  })
+  await sendAndConfirmTransaction(
+    'Execute registry entry',
+    connection,
+    new Transaction().add(instruction),
+    payer
+  )
+}
+
+// Drops a pending registry entry, signed by the registry authority
+export async function cancelRegistryEntry(
+  connection: Connection,
+  authority: Account,
+  pointerProgramAddress: PublicKey,
+  registryAddress: PublicKey
+) {
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: registryAddress, isSigner: false, isWritable: true },
+      { pubkey: authority.publicKey, isSigner: true, isWritable: false }
+    ],
+    programId: pointerProgramAddress,
+    data: Buffer.from([10]) // CancelRegistryEntry
+  })
+  await sendAndConfirmTransaction(
+    'Cancel registry entry',
+    connection,
+    new Transaction().add(instruction),
+    authority
+  )
+}
@@ -0,0 +1,63 @@
+import { PublicKey } from '@solana/web3.js'
+import { getDeployerAccount, getOurAccount } from './ourAccount'
+import { getNodeConnection } from './nodeConnection'
+import { Store } from './util/store'
+import {
+  cancelPointerUpdate,
+  executePointerUpdate,
+  migratePointer,
+  pointerSeed,
+  proposePointerUpdate,
+  setPointerAuthority
+} from './setPointer'
+
+// Usage: updatePointer.ts <new address> | updatePointer.ts execute | updatePointer.ts cancel
+//        | updatePointer.ts authority <new authority>
+//        | updatePointer.ts migrate <seed label>
+const main = async () => {
+  const ourAccount = await getOurAccount()
+  const connection = await getNodeConnection()
+  const storeProxy: any = await new Store().load('proxy-pointer.json')
+  const pointerProgramId = new PublicKey(storeProxy.programId)
+  const pointerAddress = new PublicKey(storeProxy.accountId)
+
+  if (process.argv[2] === 'migrate') {
+    // Legacy pointers are replaced by one derived from the deployer key, which
+    // hands the authority over to our account right after
+    const deployer = await getDeployerAccount()
+    const migrated = await migratePointer(
+      connection,
+      deployer,
+      pointerProgramId,
+      pointerAddress,
+      pointerSeed(process.argv[3])
+    )
+    await setPointerAuthority(
+      connection,
+      deployer,
+      pointerProgramId,
+      migrated,
+      ourAccount.publicKey
+    )
+    await new Store().save('proxy-pointer.json', {
+      ...storeProxy,
+      accountId: migrated.toString()
+    })
+    console.log(`Pointer ${pointerAddress.toString()} migrated to ${migrated.toString()}`)
+  } else if (process.argv[2] === 'authority') {
+    const newAuthority = new PublicKey(process.argv[3])
+    await setPointerAuthority(connection, ourAccount, pointerProgramId, pointerAddress, newAuthority)
+    console.log(`Pointer ${pointerAddress.toString()} handed to ${newAuthority.toString()}`)
+  } else if (process.argv[2] === 'execute') {
+    await executePointerUpdate(connection, ourAccount, pointerProgramId, pointerAddress)
+    console.log(`Pointer ${pointerAddress.toString()} pending update executed`)
+  } else if (process.argv[2] === 'cancel') {
+    await cancelPointerUpdate(connection, ourAccount, pointerProgramId, pointerAddress)
+    console.log(`Pointer ${pointerAddress.toString()} pending update cancelled`)
+  } else {
+    const value = new PublicKey(process.argv[2])
+    await proposePointerUpdate(connection, ourAccount, pointerProgramId, pointerAddress, value)
+    console.log(`Pointer ${pointerAddress.toString()} update to ${value.toString()} proposed`)
+  }
+}
+main()
@@ -0,0 +1,36 @@
+// Run with `npm run test:client`, cases mirror the tests of name-normalization
+import { strict as assert } from 'assert'
+import { normalizeName } from './normalize-name'
+
+assert.equal(normalizeName('Bruh'), 'bruh')
+assert.equal(normalizeName('some name_1-2'), 'some name_1-2')
+assert.equal(normalizeName('Z\u00f3\u0142w'), normalizeName('zo\u0301\u0142W'))
+assert.throws(() => normalizeName('bruh.sol'))
+assert.throws(() => normalizeName('br  uh'))
+
+// Heart with VS16, ZWJ family, skin tone, keycap, flag and tag sequences
+for (const name of [
+  '\u2764\ufe0f',
+  '\u{1f468}\u200d\u{1f469}\u200d\u{1f467}',
+  '\u{1f44d}\u{1f3fd}',
+  '1\ufe0f\u20e3',
+  '\u{1f1f5}\u{1f1f1}',
+  '\u{1f3f4}\u{e0067}\u{e0062}\u{e0073}\u{e0063}\u{e0074}\u{e007f}'
+]) {
+  assert.equal(normalizeName(name), name)
+}
+// Joiners, VS16 and tags outside emoji sequences look like nothing
+for (const name of [
+  'nebula\u200d',
+  '\u200dnebula',
+  '\u{1f525}\u200d',
+  'neb\u200dula',
+  'nebula\ufe0f',
+  'nebula\u{e0061}\u{e007f}',
+  '\u{1f3f4}\u{e0067}',
+  'nebula\u{e007f}',
+  'br\u200buh'
+]) {
+  assert.throws(() => normalizeName(name))
+}
+console.log('normalize-name ok')
@@ -0,0 +1,92 @@
+// Copy of src/name-normalization, keep both in sync so clients derive the
+// same record addresses as the programs
+import { createHash } from 'crypto'
+
+export const PADDED_NAME_SIZE = 32
+export const MAX_NAME_LENGTH = 128
+
+// Emoji blocks, same as is_emoji_base
+const EMOJI_BASE =
+  '\\u00a9\\u00ae\\u203c\\u2049\\u2122\\u2139\\u2194-\\u21ff\\u2300-\\u23ff' +
+  '\\u24c2\\u25a0-\\u25ff\\u2600-\\u27bf\\u2934\\u2935\\u2b00-\\u2bff\\u3030' +
+  '\\u303d\\u3297\\u3299\\u{1f000}-\\u{1faff}'
+// Plus ZWJ, VS16, the keycap mark and the flag tags, same as is_emoji_char
+const EMOJI = EMOJI_BASE + '\\u200d\\ufe0f\\u20e3\\u{e0020}-\\u{e007f}'
+const WORD = `[\\p{Alphabetic}\\p{N}_${EMOJI}-]+`
+const ALLOWED_NAME = new RegExp(`^${WORD}( ${WORD})*$`, 'u')
+
+export function normalizeName(raw: string): string {
+  const name = raw.replace(/\0+$/, '')
+  if (name.length === 0) {
+    throw new Error('Name is empty')
+  }
+  if (name.includes('\0')) {
+    throw new Error('Name contains NUL before its end')
+  }
+  const normalized = name.normalize('NFC').toLowerCase().normalize('NFC')
+  if (!ALLOWED_NAME.test(normalized) || !joinsEmojiOnly(normalized)) {
+    throw new Error('Name contains a character that is not allowed')
+  }
+  if (Buffer.byteLength(normalized, 'utf8') > MAX_NAME_LENGTH) {
+    throw new Error('Name is too long')
+  }
+  return normalized
+}
+
+const EMOJI_BASE_CHAR = new RegExp(`^[${EMOJI_BASE}]$`, 'u')
+const isEmojiBase = (c: string) => EMOJI_BASE_CHAR.test(c)
+const isDigit = (c: string) => c >= '0' && c <= '9'
+const isTag = (c: string) => c.codePointAt(0)! >= 0xe0020 && c.codePointAt(0)! <= 0xe007e
+
+// Invisible characters only inside emoji sequences, same as joins_emoji_only
+function joinsEmojiOnly(name: string): boolean {
+  const chars = Array.from(name)
+  return chars.every((c, i) => {
+    const prev = chars[i - 1] || '\0'
+    const next = chars[i + 1] || '\0'
+    switch (c) {
+      case '\u200d':
+        return (isEmojiBase(prev) || prev === '\ufe0f' || prev === '\u{e007f}') && isEmojiBase(next)
+      case '\ufe0f':
+        return isEmojiBase(prev) || (isDigit(prev) && next === '\u20e3')
+      case '\u20e3':
+        return isDigit(prev) || prev === '\ufe0f'
+      case '\u{e007f}':
+        return isTag(prev)
+      default:
+        if (isTag(c)) {
+          return (prev === '\u{1f3f4}' || isTag(prev)) && (isTag(next) || next === '\u{e007f}')
+        }
+        return true
+    }
+  })
+}
+
+// Name as sent in instruction data, one byte of length followed by the name
+export function encodeName(name: string): Buffer {
+  const bytes = Buffer.from(name, 'utf8')
+  return Buffer.concat([Buffer.from([bytes.length]), bytes])
+}
+
+// Bytes a stored name takes after its padded head, zero for short names
+export function nameExtensionLen(name: string): number {
+  const length = Buffer.byteLength(name, 'utf8')
+  return length > PADDED_NAME_SIZE ? 1 + length - PADDED_NAME_SIZE : 0
+}
+
+// Seed of the name record, long names are hashed
+export function nameSeed(name: string): Buffer {
+  const bytes = Buffer.from(name, 'utf8')
+  if (bytes.length > PADDED_NAME_SIZE) {
+    return createHash('sha256').update(bytes).digest()
+  }
+  const seed = Buffer.alloc(PADDED_NAME_SIZE)
+  bytes.copy(seed)
+  return seed
+}
+
+// Hash a normalized name is reserved under, the reserved name account is
+// derived from it
+export function reservedNameHash(name: string): Buffer {
+  return createHash('sha256').update(Buffer.from(name, 'utf8')).digest()
+}
@@ -0,0 +1,56 @@
+import { Transaction, TransactionInstruction, PublicKey, SYSVAR_RENT_PUBKEY } from '@solana/web3.js'
+import { getOurAccount } from './ourAccount'
+import { getNodeConnection } from './nodeConnection'
+import { getStore } from './storeConfig'
+import { sendAndConfirmTransaction } from './util/send-and-confirm-transaction'
+
+// Withdraw tag differs between programs
+const WITHDRAW_TAG: { [program: string]: number } = {
+  'account-name-service': 15,
+  'token-name-service': 4
+}
+
+const main = async () => {
+  const program = process.argv[2] || 'account-name-service'
+  const ourAccount = await getOurAccount()
+  const connection = await getNodeConnection()
+  const store = await getStore(connection, `${program}.json`)
+
+  const [configAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('config')],
+    store.programId
+  )
+  const [treasuryAccount] = await PublicKey.findProgramAddress(
+    [Buffer.from('treasury')],
+    store.programId
+  )
+  // Beneficiaries follow is_initialized and admin, address and u16 share each
+  const config = await connection.getAccountInfo(configAccount)
+  const beneficiaries = []
+  for (let slot = 0; slot < 4; slot++) {
+    const offset = 33 + slot * 34
+    if (config.data.readUInt16LE(offset + 32) !== 0) {
+      const address = new PublicKey(config.data.slice(offset, offset + 32))
+      beneficiaries.push({ pubkey: address, isSigner: false, isWritable: true })
+    }
+  }
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: configAccount, isSigner: false, isWritable: false },
+      { pubkey: treasuryAccount, isSigner: false, isWritable: true },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      ...beneficiaries
+    ],
+    programId: store.programId,
+    data: Buffer.from([WITHDRAW_TAG[program]])
+  })
+  console.log(`Treasury balance: ${await connection.getBalance(treasuryAccount)}`)
+  await sendAndConfirmTransaction(
+    'Withdraw treasury',
+    connection,
+    new Transaction().add(instruction),
+    ourAccount
+  )
+  console.log(`Treasury balance: ${await connection.getBalance(treasuryAccount)}`)
+}
+main()
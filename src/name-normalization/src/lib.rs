@@ -0,0 +1,262 @@
+use std::str::from_utf8;
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+
+/// Size of the NUL padded head every stored name starts with
+pub const PADDED_NAME_SIZE: usize = 32;
+/// Longest name in bytes after normalization, stored length takes one byte
+pub const MAX_NAME_LENGTH: usize = 128;
+/// Extension space needed by the longest name, see `name_extension_len`
+pub const MAX_NAME_EXTENSION_LEN: usize = 1 + MAX_NAME_LENGTH - PADDED_NAME_SIZE;
+
+/// Reasons a submitted name is rejected
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+pub enum NameError {
+    #[error("Name is empty")]
+    Empty,
+    #[error("Name is not valid utf8")]
+    InvalidUtf8,
+    #[error("Name contains NUL before its end")]
+    EmbeddedNul,
+    #[error("Name contains a character that is not allowed")]
+    InvalidCharacter,
+    #[error("Name is too long")]
+    TooLong,
+}
+
+/// Canonical form of a submitted name, shared by the programs and clients.
+///
+/// Trailing NUL padding is dropped, any other NUL is rejected. The rest has
+/// to be utf8 and is NFC normalized, lowercased and normalized again, since
+/// lowercasing can produce sequences that are not in NFC. The result may
+/// only contain letters, digits, emoji, `-`, `_` and single spaces between
+/// words. The invisible characters emoji sequences are built with are only
+/// allowed inside those sequences.
+pub fn normalize_name(raw: &[u8]) -> Result<String, NameError> {
+    let end = raw
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |last| last + 1);
+    let raw = &raw[..end];
+    if raw.is_empty() {
+        return Err(NameError::Empty);
+    }
+    if raw.contains(&0) {
+        return Err(NameError::EmbeddedNul);
+    }
+    let name = from_utf8(raw).map_err(|_| NameError::InvalidUtf8)?;
+    let name: String = name
+        .nfc()
+        .collect::<String>()
+        .to_lowercase()
+        .nfc()
+        .collect();
+    if !name.chars().all(is_allowed_char)
+        || !joins_emoji_only(&name)
+        || name.starts_with(' ')
+        || name.ends_with(' ')
+        || name.contains("  ")
+    {
+        return Err(NameError::InvalidCharacter);
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(NameError::TooLong);
+    }
+    Ok(name)
+}
+
+/// Space a stored name takes after its `PADDED_NAME_SIZE` byte head: the
+/// full length followed by the remaining bytes. Names that fit the head
+/// need none, so their records keep the layout from before long names.
+pub fn name_extension_len(name: &[u8]) -> usize {
+    if name.len() > PADDED_NAME_SIZE {
+        1 + name.len() - PADDED_NAME_SIZE
+    } else {
+        0
+    }
+}
+
+/// Writes `name` into a NUL padded `head` and `extension`, which has to hold
+/// at least `name_extension_len(name)` bytes
+pub fn pack_name(name: &[u8], head: &mut [u8; PADDED_NAME_SIZE], extension: &mut [u8]) {
+    let head_len = name.len().min(PADDED_NAME_SIZE);
+    *head = [0; PADDED_NAME_SIZE];
+    head[..head_len].copy_from_slice(&name[..head_len]);
+    if let Some((length, tail)) = extension.split_first_mut() {
+        if name.len() > PADDED_NAME_SIZE {
+            *length = name.len() as u8;
+            tail[..name.len() - PADDED_NAME_SIZE].copy_from_slice(&name[PADDED_NAME_SIZE..]);
+        } else {
+            *length = 0;
+        }
+    }
+}
+
+/// Reads a name written by `pack_name`, `None` when the extension is cut short
+pub fn unpack_name(head: &[u8; PADDED_NAME_SIZE], extension: &[u8]) -> Option<Vec<u8>> {
+    match extension.split_first() {
+        Some((&length, tail)) if length as usize > PADDED_NAME_SIZE => {
+            let tail = tail.get(..length as usize - PADDED_NAME_SIZE)?;
+            Some([&head[..], tail].concat())
+        }
+        _ => {
+            let end = head
+                .iter()
+                .rposition(|byte| *byte != 0)
+                .map_or(0, |last| last + 1);
+            Some(head[..end].to_vec())
+        }
+    }
+}
+
+fn is_allowed_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' || is_emoji_char(c)
+}
+
+/// Extended_Pictographic blocks plus the characters emoji sequences are
+/// joined with: ZWJ, VS16, the keycap mark and the flag tags
+fn is_emoji_char(c: char) -> bool {
+    is_emoji_base(c) || matches!(c as u32, 0x200d | 0xfe0f | 0x20e3 | 0xe0020..=0xe007f)
+}
+
+fn is_emoji_base(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x00a9
+            | 0x00ae
+            | 0x203c
+            | 0x2049
+            | 0x2122
+            | 0x2139
+            | 0x2194..=0x21ff
+            | 0x2300..=0x23ff
+            | 0x24c2
+            | 0x25a0..=0x25ff
+            | 0x2600..=0x27bf
+            | 0x2934..=0x2935
+            | 0x2b00..=0x2bff
+            | 0x3030
+            | 0x303d
+            | 0x3297
+            | 0x3299
+            | 0x1f000..=0x1faff
+    )
+}
+
+/// Whether the invisible characters of `name` all sit inside an emoji
+/// sequence, elsewhere they would make a look-alike of another name: ZWJ
+/// between two emoji, VS16 after an emoji or a keycap digit, the keycap
+/// mark after a digit and tags after a black flag up to the cancel tag
+fn joins_emoji_only(name: &str) -> bool {
+    let chars: Vec<char> = name.chars().collect();
+    // NUL stands for the missing neighbours, names can not contain it
+    (0..chars.len()).all(|i| {
+        let prev = i.checked_sub(1).map_or('\0', |prev| chars[prev]);
+        let next = chars.get(i + 1).copied().unwrap_or('\0');
+        match chars[i] {
+            '\u{200d}' => {
+                (is_emoji_base(prev) || prev == '\u{fe0f}' || prev == '\u{e007f}')
+                    && is_emoji_base(next)
+            }
+            '\u{fe0f}' => is_emoji_base(prev) || (prev.is_ascii_digit() && next == '\u{20e3}'),
+            '\u{20e3}' => prev.is_ascii_digit() || prev == '\u{fe0f}',
+            '\u{e0020}'..='\u{e007e}' => {
+                matches!(prev, '\u{1f3f4}' | '\u{e0020}'..='\u{e007e}')
+                    && matches!(next, '\u{e0020}'..='\u{e007f}')
+            }
+            '\u{e007f}' => matches!(prev, '\u{e0020}'..='\u{e007e}'),
+            _ => true,
+        }
+    })
+}
+
+// tests
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name(b"Bruh").unwrap(), "bruh");
+        assert_eq!(normalize_name(b"bruh\0\0\0").unwrap(), "bruh");
+        assert_eq!(normalize_name(b"some name_1-2").unwrap(), "some name_1-2");
+        // Composed and decomposed forms end up the same
+        assert_eq!(
+            normalize_name("Z\u{00f3}\u{0142}w".as_bytes()).unwrap(),
+            normalize_name("zo\u{0301}\u{0142}W".as_bytes()).unwrap()
+        );
+        assert_eq!(normalize_name(b"\0\0"), Err(NameError::Empty));
+        assert_eq!(normalize_name(b"bruh\0junk"), Err(NameError::EmbeddedNul));
+        assert_eq!(normalize_name(b"bruh\xff"), Err(NameError::InvalidUtf8));
+        assert_eq!(normalize_name(b"bruh\n"), Err(NameError::InvalidCharacter));
+        assert_eq!(
+            normalize_name(b"bruh.sol"),
+            Err(NameError::InvalidCharacter)
+        );
+        assert_eq!(normalize_name(b" bruh"), Err(NameError::InvalidCharacter));
+        assert_eq!(normalize_name(b"br  uh"), Err(NameError::InvalidCharacter));
+    }
+    #[test]
+    fn test_normalize_emoji_name() {
+        assert_eq!(normalize_name("\u{1f525}".as_bytes()).unwrap(), "\u{1f525}");
+        assert_eq!(
+            normalize_name("Bruh\u{1f680}".as_bytes()).unwrap(),
+            "bruh\u{1f680}"
+        );
+        // Heart with VS16, ZWJ family, skin tone, keycap and flag sequences
+        for name in &[
+            "\u{2764}\u{fe0f}",
+            "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}",
+            "\u{1f44d}\u{1f3fd}",
+            "1\u{fe0f}\u{20e3}",
+            "\u{1f1f5}\u{1f1f1}",
+            "\u{1f3f4}\u{e0067}\u{e0062}\u{e0073}\u{e0063}\u{e0074}\u{e007f}",
+        ] {
+            assert_eq!(normalize_name(name.as_bytes()).unwrap(), *name);
+        }
+        // Joiners, VS16 and tags outside emoji sequences look like nothing
+        for name in &[
+            "nebula\u{200d}",
+            "\u{200d}nebula",
+            "\u{1f525}\u{200d}",
+            "neb\u{200d}ula",
+            "nebula\u{fe0f}",
+            "nebula\u{e0061}\u{e007f}",
+            "\u{1f3f4}\u{e0067}",
+            "nebula\u{e007f}",
+        ] {
+            assert_eq!(
+                normalize_name(name.as_bytes()),
+                Err(NameError::InvalidCharacter)
+            );
+        }
+        // Zero width characters other than ZWJ are still rejected
+        assert_eq!(
+            normalize_name("br\u{200b}uh".as_bytes()),
+            Err(NameError::InvalidCharacter)
+        );
+    }
+    #[test]
+    fn test_pack_name() {
+        let mut head = [0u8; PADDED_NAME_SIZE];
+        let mut extension = [0u8; MAX_NAME_EXTENSION_LEN];
+        // Short names only use the padded head
+        pack_name(b"bruh", &mut head, &mut extension);
+        assert_eq!(&head[..5], b"bruh\0");
+        assert_eq!(extension[0], 0);
+        assert_eq!(unpack_name(&head, &extension).unwrap(), b"bruh");
+        assert_eq!(unpack_name(&head, &[]).unwrap(), b"bruh");
+        assert_eq!(name_extension_len(b"bruh"), 0);
+
+        let name = "\u{00f3}".repeat(MAX_NAME_LENGTH / 2);
+        let name = normalize_name(name.as_bytes()).unwrap();
+        assert_eq!(name_extension_len(name.as_bytes()), MAX_NAME_EXTENSION_LEN);
+        pack_name(name.as_bytes(), &mut head, &mut extension);
+        assert_eq!(unpack_name(&head, &extension).unwrap(), name.as_bytes());
+        assert_eq!(unpack_name(&head, &extension[..10]), None);
+        assert_eq!(
+            normalize_name("\u{00f3}".repeat(MAX_NAME_LENGTH / 2 + 1).as_bytes()),
+            Err(NameError::TooLong)
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use crate::{
+    create_pda_account, error::CommonError, find_config_address, find_reserved_name_address,
+    find_treasury_address, reserved_name_hash, Beneficiary, ReservedName, FEE_TIERS,
+    MAX_BENEFICIARIES, RESERVED_DATA_SIZE, RESERVED_SEED, TOTAL_SHARES,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    log,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    sysvar::{rent::Rent, Sysvar},
+};
+
+/// Program wide settings managed by the admin. The config, treasury and
+/// reserved name instructions both name services share are provided here
+pub trait ServiceConfig: Pack + IsInitialized + Sized {
+    /// Program error the shared failures are reported as
+    type Error: From<CommonError> + Into<ProgramError>;
+
+    fn admin(&self) -> &Pubkey;
+    fn beneficiaries(&self) -> &[Beneficiary; MAX_BENEFICIARIES];
+    fn fees(&self) -> &[u64; FEE_TIERS];
+    fn paused(&self) -> bool;
+    fn set_paused(&mut self, paused: bool);
+
+    /// `error` as reported by the program
+    fn error(error: CommonError) -> ProgramError {
+        Self::Error::from(error).into()
+    }
+
+    /// Fee for a name `name_length` characters long, names longer than
+    /// the last tier pay the last tier price
+    // usize::clamp needs Rust 1.50, newer than the BPF toolchain
+    #[allow(clippy::manual_clamp)]
+    fn fee_for(&self, name_length: usize) -> u64 {
+        self.fees()[name_length.max(1).min(FEE_TIERS) - 1]
+    }
+
+    /// Reads the initialized program config from `config_account`
+    fn load(program_id: &Pubkey, config_account: &AccountInfo) -> Result<Self, ProgramError> {
+        if *config_account.key != find_config_address(program_id).0 {
+            return Err(Self::error(CommonError::InvalidConfigAddress));
+        }
+        if config_account.owner != program_id {
+            return Err(Self::error(CommonError::IncorrectAccountOwner));
+        }
+        let config_data = config_account.try_borrow_data()?;
+        if config_data.len().lt(&Self::LEN) {
+            return Err(Self::error(CommonError::AccountDataTooSmall));
+        }
+        let config = Self::unpack_from_slice(&config_data)?;
+        if !config.is_initialized() {
+            return Err(Self::error(CommonError::ConfigNotInitialized));
+        }
+        Ok(config)
+    }
+
+    /// Fails unless `admin` is the signing admin of the program config
+    fn check_admin(&self, admin: &AccountInfo) -> ProgramResult {
+        if *self.admin() != *admin.key {
+            return Err(Self::error(CommonError::NotAdmin));
+        }
+        if !admin.is_signer {
+            log::sol_log("Transaction need to be signed by admin");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+
+    /// Fails while the program is paused. The config account is found by its
+    /// address, so instructions that already read it need no extra account
+    fn check_not_paused(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let (config_address, _) = find_config_address(program_id);
+        let config_account = accounts
+            .iter()
+            .find(|account| *account.key == config_address)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if Self::load(program_id, config_account)?.paused() {
+            return Err(Self::error(CommonError::ProgramPaused));
+        }
+        Ok(())
+    }
+
+    /// Fails unless shares add up to `TOTAL_SHARES` and every beneficiary with
+    /// a share has an address
+    fn check_beneficiaries(beneficiaries: &[Beneficiary]) -> ProgramResult {
+        let mut total: u16 = 0;
+        for beneficiary in beneficiaries {
+            if beneficiary.share != 0 && beneficiary.address == Pubkey::default() {
+                return Err(Self::error(CommonError::InvalidBeneficiaries));
+            }
+            total = total
+                .checked_add(beneficiary.share)
+                .ok_or_else(|| Self::error(CommonError::InvalidBeneficiaries))?;
+        }
+        if total != TOTAL_SHARES {
+            return Err(Self::error(CommonError::InvalidBeneficiaries));
+        }
+        Ok(())
+    }
+
+    /// Fails when the normalized `name` is reserved and its authority did not
+    /// sign. `reserved_account` only exists once the name has been reserved
+    fn check_reserved_name(
+        program_id: &Pubkey,
+        name: &[u8],
+        reserved_account: &AccountInfo,
+        authority: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        let name_hash = reserved_name_hash(name);
+        if *reserved_account.key != find_reserved_name_address(&name_hash, program_id).0 {
+            return Err(Self::error(CommonError::InvalidReservedAddress));
+        }
+        if reserved_account.owner != program_id || reserved_account.data_len() < RESERVED_DATA_SIZE
+        {
+            return Ok(());
+        }
+        let reserved_name = ReservedName::unpack_from_slice(&reserved_account.try_borrow_data()?)?;
+        if !reserved_name.is_initialized {
+            return Ok(());
+        }
+        let authority = authority.ok_or_else(|| Self::error(CommonError::NameReserved))?;
+        if *authority.key != reserved_name.authority {
+            return Err(Self::error(CommonError::NameReserved));
+        }
+        if !authority.is_signer {
+            log::sol_log("Transaction need to be signed by reserved name authority");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+
+    fn process_set_paused(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        paused: bool,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config_account = next_account_info(accounts_iter)?;
+        let admin = next_account_info(accounts_iter)?;
+        let mut config = Self::load(program_id, config_account)?;
+        config.check_admin(admin)?;
+        config.set_paused(paused);
+        config.pack_into_slice(&mut config_account.try_borrow_mut_data()?);
+        Ok(())
+    }
+
+    fn process_reserve_name(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        name_hash: [u8; 32],
+        authority: Pubkey,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config = Self::load(program_id, next_account_info(accounts_iter)?)?;
+        let admin = next_account_info(accounts_iter)?;
+        let reserved_account = next_account_info(accounts_iter)?;
+        let system_program = next_account_info(accounts_iter)?;
+        let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+        config.check_admin(admin)?;
+        let (reserved_address, bump_seed) = find_reserved_name_address(&name_hash, program_id);
+        if *reserved_account.key != reserved_address {
+            return Err(Self::error(CommonError::InvalidReservedAddress));
+        }
+        if reserved_account.data_is_empty() {
+            create_pda_account(
+                program_id,
+                admin,
+                reserved_account,
+                system_program,
+                rent.minimum_balance(RESERVED_DATA_SIZE),
+                RESERVED_DATA_SIZE,
+                &[RESERVED_SEED, &name_hash, &[bump_seed]],
+            )?;
+        }
+        if reserved_account.owner != program_id {
+            return Err(Self::error(CommonError::IncorrectAccountOwner));
+        }
+        let reserved_name = ReservedName {
+            is_initialized: true,
+            name_hash,
+            authority,
+        };
+        ReservedName::pack_into_slice(&reserved_name, &mut reserved_account.try_borrow_mut_data()?);
+        Ok(())
+    }
+
+    /// Closes the reservation, its rent goes to the destination account
+    fn process_release_name(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        name_hash: [u8; 32],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config = Self::load(program_id, next_account_info(accounts_iter)?)?;
+        let admin = next_account_info(accounts_iter)?;
+        let reserved_account = next_account_info(accounts_iter)?;
+        let destination = next_account_info(accounts_iter)?;
+        config.check_admin(admin)?;
+        if *reserved_account.key != find_reserved_name_address(&name_hash, program_id).0 {
+            return Err(Self::error(CommonError::InvalidReservedAddress));
+        }
+        if reserved_account.owner != program_id {
+            return Err(Self::error(CommonError::IncorrectAccountOwner));
+        }
+        let mut reserved_data = reserved_account.try_borrow_mut_data()?;
+        if !ReservedName::unpack_from_slice(&reserved_data)?.is_initialized {
+            return Err(Self::error(CommonError::NameNotReserved));
+        }
+        for byte in reserved_data.iter_mut() {
+            *byte = 0;
+        }
+        let mut reserved_balance = reserved_account.try_borrow_mut_lamports()?;
+        let mut destination_balance = destination.try_borrow_mut_lamports()?;
+        **destination_balance = destination_balance
+            .checked_add(**reserved_balance)
+            .ok_or_else(|| Self::error(CommonError::Overflow))?;
+        **reserved_balance = 0;
+        Ok(())
+    }
+
+    fn process_withdraw(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let config = Self::load(program_id, next_account_info(accounts_iter)?)?;
+        let treasury = next_account_info(accounts_iter)?;
+        let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+        if *treasury.key != find_treasury_address(program_id).0 {
+            return Err(Self::error(CommonError::InvalidPaymentAccount));
+        }
+        if treasury.owner != program_id {
+            return Err(Self::error(CommonError::IncorrectAccountOwner));
+        }
+        let shares: Vec<&Beneficiary> = config
+            .beneficiaries()
+            .iter()
+            .filter(|beneficiary| beneficiary.share != 0)
+            .collect();
+        let recipients: Vec<&AccountInfo> = accounts_iter.collect();
+        if recipients.len() != shares.len()
+            || recipients
+                .iter()
+                .zip(&shares)
+                .any(|(recipient, beneficiary)| *recipient.key != beneficiary.address)
+        {
+            return Err(Self::error(CommonError::InvalidBeneficiaryAccount));
+        }
+        // Treasury keeps its rent, rounding leftovers wait for the next withdraw
+        let available = treasury
+            .lamports()
+            .saturating_sub(rent.minimum_balance(treasury.data_len()));
+        let mut treasury_balance = treasury.try_borrow_mut_lamports()?;
+        for (recipient, beneficiary) in recipients.iter().zip(&shares) {
+            let amount =
+                (available as u128 * beneficiary.share as u128 / TOTAL_SHARES as u128) as u64;
+            let mut recipient_balance = recipient.try_borrow_mut_lamports()?;
+            **treasury_balance = treasury_balance
+                .checked_sub(amount)
+                .ok_or_else(|| Self::error(CommonError::Overflow))?;
+            **recipient_balance = recipient_balance
+                .checked_add(amount)
+                .ok_or_else(|| Self::error(CommonError::Overflow))?;
+        }
+        Ok(())
+    }
+}
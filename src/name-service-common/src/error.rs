@@ -0,0 +1,18 @@
+/// Failures of the shared helpers, each program reports them as its own
+/// error, see `ServiceConfig::Error`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CommonError {
+    InvalidPaymentAccount,
+    IncorrectAccountOwner,
+    AccountDataTooSmall,
+    InvalidConfigAddress,
+    ConfigNotInitialized,
+    NotAdmin,
+    Overflow,
+    InvalidBeneficiaries,
+    InvalidBeneficiaryAccount,
+    ProgramPaused,
+    InvalidReservedAddress,
+    NameReserved,
+    NameNotReserved,
+}
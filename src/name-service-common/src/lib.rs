@@ -0,0 +1,207 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    hash::hash,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+    system_instruction,
+};
+
+pub mod config;
+pub mod error;
+
+/// Key the programs are deployed with, kept apart from the admin and
+/// treasury wallets. Only it can initialize the configs and migrate legacy
+/// pointers, the client signs with it from deployer.json
+pub static DEPLOYER_ADDRESS: &str = "25DVS9CFoVcr73UWT1a7nEp5WaYYMsMF8yH9zp8k8Bb6";
+/// Fee tiers for names of 1, 2, 3, 4 and 5 or more characters
+pub const FEE_TIERS: usize = 5;
+/// Most accounts the treasury can be split between
+pub const MAX_BENEFICIARIES: usize = 4;
+/// Shares of all beneficiaries add up to this many basis points
+pub const TOTAL_SHARES: u16 = 10_000;
+/// Packed size of all beneficiaries, address and u16 share each
+pub const BENEFICIARIES_SIZE: usize = 34 * MAX_BENEFICIARIES;
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const TREASURY_SEED: &[u8] = b"treasury";
+pub const RESERVED_SEED: &[u8] = b"reserved";
+pub const RESERVED_DATA_SIZE: usize = 65;
+/// Account receiving `share` basis points of every treasury withdrawal,
+/// unused slots have a zero share
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Beneficiary {
+    pub address: Pubkey,
+    pub share: u16,
+}
+/// Name set aside by the admin, stored under the hash of the name
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReservedName {
+    pub is_initialized: bool,
+    pub name_hash: [u8; 32],
+    /// Only signer able to register the name
+    pub authority: Pubkey,
+}
+impl Sealed for ReservedName {}
+impl IsInitialized for ReservedName {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for ReservedName {
+    const LEN: usize = RESERVED_DATA_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, RESERVED_DATA_SIZE];
+        let (is_initialized, name_hash, authority) = array_refs![src, 1, 32, 32];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(ReservedName {
+            is_initialized,
+            name_hash: *name_hash,
+            authority: Pubkey::new_from_array(*authority),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, RESERVED_DATA_SIZE];
+        let (is_initialized_dst, name_hash_dst, authority_dst) = mut_array_refs![dst, 1, 32, 32];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        *name_hash_dst = self.name_hash;
+        *authority_dst = self.authority.to_bytes();
+    }
+}
+
+/// Reads `FEE_TIERS` little endian u64 values
+pub fn unpack_fees(input: &[u8; 8 * FEE_TIERS]) -> [u64; FEE_TIERS] {
+    let mut fees = [0u64; FEE_TIERS];
+    for (fee, src) in fees.iter_mut().zip(input.chunks_exact(8)) {
+        *fee = u64::from_le_bytes(*array_ref![src, 0, 8]);
+    }
+    fees
+}
+
+/// Writes fees in the layout read by `unpack_fees`
+pub fn pack_fees(fees: &[u64; FEE_TIERS], dst: &mut [u8; 8 * FEE_TIERS]) {
+    for (fee, dst) in fees.iter().zip(dst.chunks_exact_mut(8)) {
+        dst.copy_from_slice(&fee.to_le_bytes());
+    }
+}
+
+/// Reads `MAX_BENEFICIARIES` addresses, each followed by a little endian
+/// u16 share
+pub fn unpack_beneficiaries(input: &[u8; BENEFICIARIES_SIZE]) -> [Beneficiary; MAX_BENEFICIARIES] {
+    let mut beneficiaries = [Beneficiary::default(); MAX_BENEFICIARIES];
+    for (beneficiary, src) in beneficiaries.iter_mut().zip(input.chunks_exact(34)) {
+        let (address, share) = array_refs![array_ref![src, 0, 34], 32, 2];
+        beneficiary.address = Pubkey::new_from_array(*address);
+        beneficiary.share = u16::from_le_bytes(*share);
+    }
+    beneficiaries
+}
+
+/// Writes beneficiaries in the layout read by `unpack_beneficiaries`
+pub fn pack_beneficiaries(
+    beneficiaries: &[Beneficiary; MAX_BENEFICIARIES],
+    dst: &mut [u8; BENEFICIARIES_SIZE],
+) {
+    for (beneficiary, dst) in beneficiaries.iter().zip(dst.chunks_exact_mut(34)) {
+        dst[..32].copy_from_slice(&beneficiary.address.to_bytes());
+        dst[32..].copy_from_slice(&beneficiary.share.to_le_bytes());
+    }
+}
+
+/// Address of the program config account
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+/// Address of the program owned account collecting registration fees
+pub fn find_treasury_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TREASURY_SEED], program_id)
+}
+/// Hash a name is reserved under, `name` has to be normalized
+pub fn reserved_name_hash(name: &[u8]) -> [u8; 32] {
+    hash(name).to_bytes()
+}
+/// Address of the account reserving the name hashing to `name_hash`
+pub fn find_reserved_name_address(name_hash: &[u8; 32], program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RESERVED_SEED, name_hash], program_id)
+}
+/// Creates an account owned by the program at a program derived address.
+/// Tops the balance up to `lamports` rather than using `create_account`,
+/// which fails once anyone funded the address first
+pub fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    new_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    lamports: u64,
+    space: usize,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let required_lamports = lamports.saturating_sub(new_account.lamports());
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, new_account.key, required_lamports),
+            &[payer.clone(), new_account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(new_account.key, space as u64),
+        &[new_account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(new_account.key, program_id),
+        &[new_account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )
+}
+
+// tests
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let beneficiaries = [
+            Beneficiary {
+                address: Pubkey::new_unique(),
+                share: 7_500,
+            },
+            Beneficiary {
+                address: Pubkey::new_unique(),
+                share: 2_500,
+            },
+            Beneficiary::default(),
+            Beneficiary::default(),
+        ];
+        let mut data = [0u8; BENEFICIARIES_SIZE];
+        pack_beneficiaries(&beneficiaries, &mut data);
+        assert_eq!(unpack_beneficiaries(&data), beneficiaries);
+        let fees = [5, 4, 3, 2, 1];
+        let mut data = [0u8; 8 * FEE_TIERS];
+        pack_fees(&fees, &mut data);
+        assert_eq!(unpack_fees(&data), fees);
+        let reserved_name = ReservedName {
+            is_initialized: true,
+            name_hash: reserved_name_hash(b"nebula"),
+            authority: Pubkey::new_unique(),
+        };
+        let mut data = [0u8; RESERVED_DATA_SIZE];
+        reserved_name.pack_into_slice(&mut data);
+        assert_eq!(
+            ReservedName::unpack_from_slice(&data).unwrap(),
+            reserved_name
+        );
+        data[0] = 2;
+        assert_eq!(
+            ReservedName::unpack_from_slice(&data).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+    }
+}
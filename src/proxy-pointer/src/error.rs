@@ -0,0 +1,64 @@
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    log,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+/// Errors returned by the proxy pointer program, surfaced to clients as
+/// `ProgramError::Custom(code)` where code is the variant index
+#[derive(Clone, Copy, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum PointerError {
+    #[error("Account must be owned by program")]
+    IncorrectAccountOwner,
+    #[error("Account data is not empty")]
+    AccountNotEmpty,
+    #[error("Account data field is insufficient")]
+    AccountDataTooSmall,
+    #[error("Pointer is not initialized")]
+    PointerNotInitialized,
+    #[error("Pointer account does not match creator and seed")]
+    InvalidPointerAddress,
+    #[error("Lamports arithmetic overflowed")]
+    Overflow,
+    #[error("You are not authority of this pointer")]
+    NotAuthority,
+    #[error("Pointer or registry already has a pending update")]
+    UpdatePending,
+    #[error("Pointer or registry has no pending update")]
+    NoPendingUpdate,
+    #[error("Pending update can not be executed yet")]
+    UpdateTimelocked,
+    #[error("Registry is not initialized")]
+    RegistryNotInitialized,
+    #[error("Registry account does not match creator and seed")]
+    InvalidRegistryAddress,
+    #[error("Pointer has the legacy layout and has to be migrated first")]
+    LegacyPointer,
+    #[error("Pointer does not have the legacy layout")]
+    NotLegacyPointer,
+    #[error("Only the deployer can migrate legacy pointers")]
+    NotDeployer,
+    #[error("Closing has to be proposed as an update to the default address")]
+    CloseNotProposed,
+}
+impl From<PointerError> for ProgramError {
+    fn from(e: PointerError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+impl<T> DecodeError<T> for PointerError {
+    fn type_of() -> &'static str {
+        "PointerError"
+    }
+}
+impl PrintProgramError for PointerError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        log::sol_log(&self.to_string());
+    }
+}
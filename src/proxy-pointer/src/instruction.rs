@@ -0,0 +1,321 @@
+use arrayref::{array_ref, array_refs};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use solana_sdk::{program_error::ProgramError, pubkey::Pubkey};
+
+/// Well-known keys of a registry, each has one entry
+#[derive(Clone, Copy, Debug, Eq, FromPrimitive, PartialEq)]
+#[repr(u8)]
+pub enum RegistryKey {
+    Counter,
+    Treasury,
+    Config,
+    FeeOracle,
+}
+// Deriving it with `#[default]` needs Rust 1.62, newer than the BPF toolchain
+#[allow(clippy::derivable_impls)]
+impl Default for RegistryKey {
+    fn default() -> Self {
+        RegistryKey::Counter
+    }
+}
+/// Number of `RegistryKey` variants
+pub const REGISTRY_KEYS: usize = 4;
+
+/// Instructions supported by the program, the first byte of instruction
+/// data selects the variant
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PointerInstruction {
+    /// Create a pointer at the address derived from creator and seed and
+    /// point it at an address, the creator becomes its authority
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Pointer account, derived from creator and seed
+    /// 1. `[writable, signer]` Creator, funds the pointer account
+    /// 2. `[]` System program
+    /// 3. `[]` Rent sysvar
+    Initialize {
+        token_address: Pubkey,
+        seed: [u8; 32],
+    },
+    /// Delete a pointer, signed by its authority. Closing has to be proposed
+    /// as an update to the default address and wait `UPDATE_DELAY` like any
+    /// other update, since the pointer can be initialized again right after.
+    /// Its lamports go to the destination
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Pointer account
+    /// 1. `[signer]` Authority
+    /// 2. `[writable]` Destination
+    /// 3. `[]` Clock sysvar
+    Close,
+    /// Propose pointing an initialized pointer at another address, it can be
+    /// executed `UPDATE_DELAY` slots later
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Pointer account
+    /// 1. `[signer]` Authority
+    /// 2. `[]` Clock sysvar
+    ProposeUpdate { token_address: Pubkey },
+    /// Hand the pointer over to a new authority
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Pointer account
+    /// 1. `[signer]` Current authority
+    SetAuthority { new_authority: Pubkey },
+    /// Apply the pending update once its delay passed, the replaced address
+    /// is kept in the pointer history. Anyone can call it
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Pointer account
+    /// 1. `[]` Clock sysvar
+    ExecuteUpdate,
+    /// Drop the pending update
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Pointer account
+    /// 1. `[signer]` Authority
+    CancelUpdate,
+    /// Create a registry at the address derived from creator and seed with
+    /// its initial entries, the creator becomes its authority. Later changes
+    /// go through `ProposeRegistryEntry`
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Registry account, derived from creator and seed
+    /// 1. `[writable, signer]` Creator, funds the registry account
+    /// 2. `[]` System program
+    /// 3. `[]` Rent sysvar
+    InitializeRegistry {
+        seed: [u8; 32],
+        entries: [Pubkey; REGISTRY_KEYS],
+    },
+    /// Propose pointing the registry entry of `key` at an address, it can be
+    /// executed `UPDATE_DELAY` slots later
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Registry account
+    /// 1. `[signer]` Authority
+    /// 2. `[]` Clock sysvar
+    ProposeRegistryEntry { key: RegistryKey, address: Pubkey },
+    /// Move a pointer with the legacy 33 byte layout to a new pointer at the
+    /// address derived from the deployer and seed, then close it. Legacy
+    /// pointers have no authority, so the deployer signs and becomes the
+    /// authority of the new pointer
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Legacy pointer account
+    /// 1. `[writable]` New pointer account, derived from deployer and seed
+    /// 2. `[writable, signer]` Deployer, funds the new pointer and receives
+    ///    the lamports of the legacy one
+    /// 3. `[]` System program
+    /// 4. `[]` Rent sysvar
+    Migrate { seed: [u8; 32] },
+    /// Apply the pending registry entry once its delay passed, the replaced
+    /// address is kept in the registry history. Anyone can call it
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Registry account
+    /// 1. `[]` Clock sysvar
+    ExecuteRegistryEntry,
+    /// Drop the pending registry entry
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Registry account
+    /// 1. `[signer]` Authority
+    CancelRegistryEntry,
+}
+impl PointerInstruction {
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let instruction = match tag {
+            0 => {
+                if rest.len() != 64 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let (token_address, seed) = array_refs![array_ref![rest, 0, 64], 32, 32];
+                PointerInstruction::Initialize {
+                    token_address: Pubkey::new_from_array(*token_address),
+                    seed: *seed,
+                }
+            }
+            1 if rest.is_empty() => PointerInstruction::Close,
+            2 => PointerInstruction::ProposeUpdate {
+                token_address: unpack_pubkey(rest)?,
+            },
+            3 => PointerInstruction::SetAuthority {
+                new_authority: unpack_pubkey(rest)?,
+            },
+            4 if rest.is_empty() => PointerInstruction::ExecuteUpdate,
+            5 if rest.is_empty() => PointerInstruction::CancelUpdate,
+            6 => {
+                if rest.len() != 32 + 32 * REGISTRY_KEYS {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let mut entries = [Pubkey::default(); REGISTRY_KEYS];
+                for (entry, src) in entries.iter_mut().zip(rest[32..].chunks_exact(32)) {
+                    *entry = Pubkey::new(src);
+                }
+                PointerInstruction::InitializeRegistry {
+                    seed: *array_ref![rest, 0, 32],
+                    entries,
+                }
+            }
+            7 => {
+                let (&key, address) = rest
+                    .split_first()
+                    .ok_or(ProgramError::InvalidInstructionData)?;
+                PointerInstruction::ProposeRegistryEntry {
+                    key: RegistryKey::from_u8(key).ok_or(ProgramError::InvalidInstructionData)?,
+                    address: unpack_pubkey(address)?,
+                }
+            }
+            8 => {
+                if rest.len() != 32 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                PointerInstruction::Migrate {
+                    seed: *array_ref![rest, 0, 32],
+                }
+            }
+            9 if rest.is_empty() => PointerInstruction::ExecuteRegistryEntry,
+            10 if rest.is_empty() => PointerInstruction::CancelRegistryEntry,
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+        Ok(instruction)
+    }
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(65);
+        match self {
+            PointerInstruction::Initialize {
+                token_address,
+                seed,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(&token_address.to_bytes());
+                buf.extend_from_slice(seed);
+            }
+            PointerInstruction::Close => buf.push(1),
+            PointerInstruction::ProposeUpdate { token_address } => {
+                buf.push(2);
+                buf.extend_from_slice(&token_address.to_bytes());
+            }
+            PointerInstruction::SetAuthority { new_authority } => {
+                buf.push(3);
+                buf.extend_from_slice(&new_authority.to_bytes());
+            }
+            PointerInstruction::ExecuteUpdate => buf.push(4),
+            PointerInstruction::CancelUpdate => buf.push(5),
+            PointerInstruction::InitializeRegistry { seed, entries } => {
+                buf.push(6);
+                buf.extend_from_slice(seed);
+                for entry in entries {
+                    buf.extend_from_slice(&entry.to_bytes());
+                }
+            }
+            PointerInstruction::ProposeRegistryEntry { key, address } => {
+                buf.push(7);
+                buf.push(*key as u8);
+                buf.extend_from_slice(&address.to_bytes());
+            }
+            PointerInstruction::Migrate { seed } => {
+                buf.push(8);
+                buf.extend_from_slice(seed);
+            }
+            PointerInstruction::ExecuteRegistryEntry => buf.push(9),
+            PointerInstruction::CancelRegistryEntry => buf.push(10),
+        }
+        buf
+    }
+}
+fn unpack_pubkey(input: &[u8]) -> Result<Pubkey, ProgramError> {
+    if input.len() != 32 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(Pubkey::new_from_array(*array_ref![input, 0, 32]))
+}
+
+// tests
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let instruction = PointerInstruction::Initialize {
+            token_address: Pubkey::new_unique(),
+            seed: [7; 32],
+        };
+        let packed = instruction.pack();
+        assert_eq!(PointerInstruction::unpack(&packed).unwrap(), instruction);
+        assert_eq!(
+            PointerInstruction::unpack(&packed[1..]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            PointerInstruction::unpack(&[]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            PointerInstruction::unpack(&[1]).unwrap(),
+            PointerInstruction::Close
+        );
+        assert_eq!(
+            PointerInstruction::unpack(&[1, 0]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            PointerInstruction::unpack(&[4]).unwrap(),
+            PointerInstruction::ExecuteUpdate
+        );
+        assert_eq!(
+            PointerInstruction::unpack(&[5]).unwrap(),
+            PointerInstruction::CancelUpdate
+        );
+        assert_eq!(
+            PointerInstruction::unpack(&[9]).unwrap(),
+            PointerInstruction::ExecuteRegistryEntry
+        );
+        assert_eq!(
+            PointerInstruction::unpack(&[10, 0]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        let instructions = vec![
+            PointerInstruction::ProposeUpdate {
+                token_address: Pubkey::new_unique(),
+            },
+            PointerInstruction::SetAuthority {
+                new_authority: Pubkey::new_unique(),
+            },
+            PointerInstruction::InitializeRegistry {
+                seed: [3; 32],
+                entries: [Pubkey::new_unique(); REGISTRY_KEYS],
+            },
+            PointerInstruction::Migrate { seed: [5; 32] },
+            PointerInstruction::ProposeRegistryEntry {
+                key: RegistryKey::FeeOracle,
+                address: Pubkey::new_unique(),
+            },
+        ];
+        for instruction in instructions {
+            let packed = instruction.pack();
+            assert_eq!(PointerInstruction::unpack(&packed).unwrap(), instruction);
+            assert_eq!(
+                PointerInstruction::unpack(&packed[..32]),
+                Err(ProgramError::InvalidInstructionData)
+            );
+        }
+        // Keys past the last variant are rejected
+        let mut packed = PointerInstruction::ProposeRegistryEntry {
+            key: RegistryKey::Counter,
+            address: Pubkey::new_unique(),
+        }
+        .pack();
+        packed[1] = REGISTRY_KEYS as u8;
+        assert_eq!(
+            PointerInstruction::unpack(&packed),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+}
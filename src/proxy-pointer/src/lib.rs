@@ -1,20 +1,83 @@
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use name_service_common::{create_pda_account, DEPLOYER_ADDRESS};
+use num_traits::FromPrimitive;
 use solana_program::{
+    clock::Clock,
     entrypoint,
     entrypoint::ProgramResult,
     log,
     program_pack::{IsInitialized, Pack, Sealed},
+    sysvar::{rent::Rent, Sysvar},
 };
 use solana_sdk::{
     account_info::{next_account_info, AccountInfo},
-    program_error::ProgramError,
+    program_error::{PrintProgramError, ProgramError},
     pubkey::Pubkey,
 };
-const STORAGE_DATA_SIZE: usize = 33;
+use std::str::FromStr;
+
+pub mod error;
+pub mod instruction;
+use error::PointerError;
+use instruction::{PointerInstruction, RegistryKey, REGISTRY_KEYS};
+
+/// Pointers from before authorities, an address and the initialized flag
+const LEGACY_STORAGE_DATA_SIZE: usize = 33;
+/// Previous addresses kept by a pointer, older ones are overwritten
+pub const HISTORY_LENGTH: usize = 8;
+const HISTORY_ENTRY_SIZE: usize = 48;
+const STORAGE_DATA_SIZE: usize = 114 + HISTORY_ENTRY_SIZE * HISTORY_LENGTH;
+/// Slots between proposing an update and executing it, about a day
+pub const UPDATE_DELAY: u64 = 216_000;
+const POINTER_SEED: &[u8] = b"pointer";
+const REGISTRY_HISTORY_ENTRY_SIZE: usize = 49;
+const REGISTRY_DATA_SIZE: usize =
+    83 + 32 * REGISTRY_KEYS + REGISTRY_HISTORY_ENTRY_SIZE * HISTORY_LENGTH;
+const REGISTRY_SEED: &[u8] = b"registry";
+/// Address a pointer held before an update
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HistoryEntry {
+    /// Version of the pointer the address was replaced in, starting at 1
+    pub version: u64,
+    pub token_address: Pubkey,
+    /// Slot of the update that replaced the address
+    pub slot: u64,
+}
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Pointer {
     pub token_address: Pubkey,
     pub is_initialized: bool,
+    /// Only signer able to change or close the pointer
+    pub authority: Pubkey,
+    /// Number of updates so far
+    pub version: u64,
+    /// Ring buffer of replaced addresses, version `v` is at `(v - 1) % HISTORY_LENGTH`
+    pub history: [HistoryEntry; HISTORY_LENGTH],
+    /// Whether an update waits for its delay to pass
+    pub has_pending_update: bool,
+    /// Address the pending update points at
+    pub pending_address: Pubkey,
+    /// First slot the pending update can be executed in
+    pub pending_executable_slot: u64,
+}
+impl Pointer {
+    /// Points at `token_address`, remembering the current address
+    pub fn update(&mut self, token_address: Pubkey, slot: u64) {
+        self.version += 1;
+        self.history[(self.version - 1) as usize % HISTORY_LENGTH] = HistoryEntry {
+            version: self.version,
+            token_address: self.token_address,
+            slot,
+        };
+        self.token_address = token_address;
+    }
+    /// Kept history, newest entry first
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        let kept = (self.version as usize).min(HISTORY_LENGTH) as u64;
+        (0..kept)
+            .map(|age| self.history[((self.version - 1 - age) as usize) % HISTORY_LENGTH])
+            .collect()
+    }
 }
 impl Sealed for Pointer {}
 impl IsInitialized for Pointer {
@@ -26,29 +89,309 @@ impl Pack for Pointer {
     const LEN: usize = STORAGE_DATA_SIZE;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, STORAGE_DATA_SIZE];
-        let (token_address, is_initialized) = array_refs![src, 32, 1];
+        let (
+            token_address,
+            is_initialized,
+            authority,
+            version,
+            history_src,
+            has_pending_update,
+            pending_address,
+            pending_executable_slot,
+        ) = array_refs![
+            src,
+            32,
+            1,
+            32,
+            8,
+            HISTORY_ENTRY_SIZE * HISTORY_LENGTH,
+            1,
+            32,
+            8
+        ];
         let token_address = Pubkey::new_from_array(*token_address);
         let is_initialized = match is_initialized {
             [0] => false,
             [1] => true,
             _ => return Err(ProgramError::InvalidAccountData),
         };
+        let has_pending_update = match has_pending_update {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let mut history = [HistoryEntry::default(); HISTORY_LENGTH];
+        for (entry, src) in history
+            .iter_mut()
+            .zip(history_src.chunks_exact(HISTORY_ENTRY_SIZE))
+        {
+            let (version, token_address, slot) =
+                array_refs![array_ref![src, 0, HISTORY_ENTRY_SIZE], 8, 32, 8];
+            *entry = HistoryEntry {
+                version: u64::from_le_bytes(*version),
+                token_address: Pubkey::new_from_array(*token_address),
+                slot: u64::from_le_bytes(*slot),
+            };
+        }
         Ok(Pointer {
             token_address,
             is_initialized,
+            authority: Pubkey::new_from_array(*authority),
+            version: u64::from_le_bytes(*version),
+            history,
+            has_pending_update,
+            pending_address: Pubkey::new_from_array(*pending_address),
+            pending_executable_slot: u64::from_le_bytes(*pending_executable_slot),
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let dst = array_mut_ref![dst, 0, STORAGE_DATA_SIZE];
-        let (token_address_dst, is_initialized_dst) = mut_array_refs![dst, 32, 1];
+        let (
+            token_address_dst,
+            is_initialized_dst,
+            authority_dst,
+            version_dst,
+            history_dst,
+            has_pending_update_dst,
+            pending_address_dst,
+            pending_executable_slot_dst,
+        ) = mut_array_refs![
+            dst,
+            32,
+            1,
+            32,
+            8,
+            HISTORY_ENTRY_SIZE * HISTORY_LENGTH,
+            1,
+            32,
+            8
+        ];
         let &Pointer {
             ref token_address,
             is_initialized,
+            ref authority,
+            version,
+            ref history,
+            has_pending_update,
+            ref pending_address,
+            pending_executable_slot,
         } = self;
+        has_pending_update_dst[0] = has_pending_update as u8;
+        *pending_address_dst = pending_address.to_bytes();
+        *pending_executable_slot_dst = pending_executable_slot.to_le_bytes();
         is_initialized_dst[0] = is_initialized as u8;
         *token_address_dst = token_address.to_bytes();
+        *authority_dst = authority.to_bytes();
+        *version_dst = version.to_le_bytes();
+        for (entry, dst) in history
+            .iter()
+            .zip(history_dst.chunks_exact_mut(HISTORY_ENTRY_SIZE))
+        {
+            dst[..8].copy_from_slice(&entry.version.to_le_bytes());
+            dst[8..40].copy_from_slice(&entry.token_address.to_bytes());
+            dst[40..].copy_from_slice(&entry.slot.to_le_bytes());
+        }
+    }
+}
+/// Address a registry entry held before an update
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RegistryHistoryEntry {
+    /// Version of the registry the address was replaced in, starting at 1
+    pub version: u64,
+    pub key: RegistryKey,
+    pub address: Pubkey,
+    /// Slot of the update that replaced the address
+    pub slot: u64,
+}
+/// Addresses of several well-known keys behind one account
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Registry {
+    pub is_initialized: bool,
+    /// Account allowed to change the entries
+    pub authority: Pubkey,
+    /// Entry of `key` is at index `key as usize`, unset entries are zeroed
+    pub entries: [Pubkey; REGISTRY_KEYS],
+    /// Number of entry updates so far
+    pub version: u64,
+    /// Ring buffer of replaced entries, version `v` is at `(v - 1) % HISTORY_LENGTH`
+    pub history: [RegistryHistoryEntry; HISTORY_LENGTH],
+    /// Whether an entry update waits for its delay to pass
+    pub has_pending_update: bool,
+    /// Entry the pending update changes
+    pub pending_key: RegistryKey,
+    /// Address the pending update points the entry at
+    pub pending_address: Pubkey,
+    /// First slot the pending update can be executed in
+    pub pending_executable_slot: u64,
+}
+impl Registry {
+    pub fn get(&self, key: RegistryKey) -> Pubkey {
+        self.entries[key as usize]
+    }
+    /// Points the entry of `key` at `address`, remembering the current address
+    pub fn update(&mut self, key: RegistryKey, address: Pubkey, slot: u64) {
+        self.version += 1;
+        self.history[(self.version - 1) as usize % HISTORY_LENGTH] = RegistryHistoryEntry {
+            version: self.version,
+            key,
+            address: self.get(key),
+            slot,
+        };
+        self.entries[key as usize] = address;
+    }
+    /// Kept history, newest entry first
+    pub fn history(&self) -> Vec<RegistryHistoryEntry> {
+        let kept = (self.version as usize).min(HISTORY_LENGTH) as u64;
+        (0..kept)
+            .map(|age| self.history[((self.version - 1 - age) as usize) % HISTORY_LENGTH])
+            .collect()
+    }
+}
+impl Sealed for Registry {}
+impl IsInitialized for Registry {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for Registry {
+    const LEN: usize = REGISTRY_DATA_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, REGISTRY_DATA_SIZE];
+        let (
+            is_initialized,
+            authority,
+            entries_src,
+            version,
+            history_src,
+            has_pending_update,
+            pending_key,
+            pending_address,
+            pending_executable_slot,
+        ) = array_refs![
+            src,
+            1,
+            32,
+            32 * REGISTRY_KEYS,
+            8,
+            REGISTRY_HISTORY_ENTRY_SIZE * HISTORY_LENGTH,
+            1,
+            1,
+            32,
+            8
+        ];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let has_pending_update = match has_pending_update {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let mut entries = [Pubkey::default(); REGISTRY_KEYS];
+        for (entry, src) in entries.iter_mut().zip(entries_src.chunks_exact(32)) {
+            *entry = Pubkey::new(src);
+        }
+        let mut history = [RegistryHistoryEntry::default(); HISTORY_LENGTH];
+        for (entry, src) in history
+            .iter_mut()
+            .zip(history_src.chunks_exact(REGISTRY_HISTORY_ENTRY_SIZE))
+        {
+            let (version, key, address, slot) =
+                array_refs![array_ref![src, 0, REGISTRY_HISTORY_ENTRY_SIZE], 8, 1, 32, 8];
+            *entry = RegistryHistoryEntry {
+                version: u64::from_le_bytes(*version),
+                key: RegistryKey::from_u8(key[0]).ok_or(ProgramError::InvalidAccountData)?,
+                address: Pubkey::new_from_array(*address),
+                slot: u64::from_le_bytes(*slot),
+            };
+        }
+        Ok(Registry {
+            is_initialized,
+            authority: Pubkey::new_from_array(*authority),
+            entries,
+            version: u64::from_le_bytes(*version),
+            history,
+            has_pending_update,
+            pending_key: RegistryKey::from_u8(pending_key[0])
+                .ok_or(ProgramError::InvalidAccountData)?,
+            pending_address: Pubkey::new_from_array(*pending_address),
+            pending_executable_slot: u64::from_le_bytes(*pending_executable_slot),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, REGISTRY_DATA_SIZE];
+        let (
+            is_initialized_dst,
+            authority_dst,
+            entries_dst,
+            version_dst,
+            history_dst,
+            has_pending_update_dst,
+            pending_key_dst,
+            pending_address_dst,
+            pending_executable_slot_dst,
+        ) = mut_array_refs![
+            dst,
+            1,
+            32,
+            32 * REGISTRY_KEYS,
+            8,
+            REGISTRY_HISTORY_ENTRY_SIZE * HISTORY_LENGTH,
+            1,
+            1,
+            32,
+            8
+        ];
+        let &Registry {
+            is_initialized,
+            ref authority,
+            ref entries,
+            version,
+            ref history,
+            has_pending_update,
+            pending_key,
+            ref pending_address,
+            pending_executable_slot,
+        } = self;
+        is_initialized_dst[0] = is_initialized as u8;
+        *authority_dst = authority.to_bytes();
+        for (entry, dst) in entries.iter().zip(entries_dst.chunks_exact_mut(32)) {
+            dst.copy_from_slice(&entry.to_bytes());
+        }
+        *version_dst = version.to_le_bytes();
+        for (entry, dst) in history
+            .iter()
+            .zip(history_dst.chunks_exact_mut(REGISTRY_HISTORY_ENTRY_SIZE))
+        {
+            dst[..8].copy_from_slice(&entry.version.to_le_bytes());
+            dst[8] = entry.key as u8;
+            dst[9..41].copy_from_slice(&entry.address.to_bytes());
+            dst[41..].copy_from_slice(&entry.slot.to_le_bytes());
+        }
+        has_pending_update_dst[0] = has_pending_update as u8;
+        pending_key_dst[0] = pending_key as u8;
+        *pending_address_dst = pending_address.to_bytes();
+        *pending_executable_slot_dst = pending_executable_slot.to_le_bytes();
     }
 }
+/// Address of the pointer created by `creator` with `seed`
+pub fn find_pointer_address(
+    creator: &Pubkey,
+    seed: &[u8; 32],
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POINTER_SEED, &creator.to_bytes(), seed], program_id)
+}
+/// Address of the registry created by `creator` with `seed`
+pub fn find_registry_address(
+    creator: &Pubkey,
+    seed: &[u8; 32],
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REGISTRY_SEED, &creator.to_bytes(), seed], program_id)
+}
 entrypoint!(process_instruction);
 
 // Program entrypoint's implementation
@@ -58,27 +401,87 @@ fn process_instruction(
     instruction_data: &[u8],
 ) -> ProgramResult {
     log::sol_log("Rust program entrypoint");
+    let result = match PointerInstruction::unpack(instruction_data)? {
+        PointerInstruction::Initialize {
+            token_address,
+            seed,
+        } => process_initialize(program_id, accounts, token_address, seed),
+        PointerInstruction::ProposeUpdate { token_address } => {
+            process_propose_update(program_id, accounts, token_address)
+        }
+        PointerInstruction::ExecuteUpdate => process_execute_update(program_id, accounts),
+        PointerInstruction::CancelUpdate => process_cancel_update(program_id, accounts),
+        PointerInstruction::SetAuthority { new_authority } => {
+            process_set_authority(program_id, accounts, new_authority)
+        }
+        PointerInstruction::Close => process_close(program_id, accounts),
+        PointerInstruction::InitializeRegistry { seed, entries } => {
+            process_initialize_registry(program_id, accounts, seed, entries)
+        }
+        PointerInstruction::ProposeRegistryEntry { key, address } => {
+            process_propose_registry_entry(program_id, accounts, key, address)
+        }
+        PointerInstruction::Migrate { seed } => process_migrate(program_id, accounts, seed),
+        PointerInstruction::ExecuteRegistryEntry => {
+            process_execute_registry_entry(program_id, accounts)
+        }
+        PointerInstruction::CancelRegistryEntry => {
+            process_cancel_registry_entry(program_id, accounts)
+        }
+    };
+    if let Err(error) = &result {
+        error.print::<PointerError>();
+    }
+    result
+}
+
+fn process_initialize(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    address_to_point: Pubkey,
+    seed: [u8; 32],
+) -> ProgramResult {
     let accounts_iter = &mut accounts.iter();
     let storage_account = next_account_info(accounts_iter)?;
+    let creator = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    if !creator.is_signer {
+        log::sol_log("Transaction need to be signed by creator");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // Pointer must live at the address derived from creator and seed
+    let (pointer_address, bump_seed) = find_pointer_address(creator.key, &seed, program_id);
+    if *storage_account.key != pointer_address {
+        return Err(PointerError::InvalidPointerAddress.into());
+    }
+    if storage_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            creator,
+            storage_account,
+            system_program,
+            rent.minimum_balance(STORAGE_DATA_SIZE),
+            STORAGE_DATA_SIZE,
+            &[POINTER_SEED, &creator.key.to_bytes(), &seed, &[bump_seed]],
+        )?;
+    }
     // Check if programs owns account where we store data
-    let address_to_point = array_ref![instruction_data, 0, 32];
     let mut storage_account_data = storage_account.try_borrow_mut_data()?;
     if storage_account.owner != program_id {
-        log::sol_log("storage_account must be owned by program");
-        return Err(ProgramError::InvalidAccountData);
-    }
-    // I should add special field but is good enough for now
-    if storage_account_data[0] != 0 {
-        log::sol_log("Account data is not empty.");
-        return Err(ProgramError::InvalidAccountData);
+        return Err(PointerError::IncorrectAccountOwner.into());
     }
     if storage_account_data.len().lt(&STORAGE_DATA_SIZE) {
-        log::sol_log("Account data field is insufficient");
-        return Err(ProgramError::InvalidAccountData);
+        return Err(PointerError::AccountDataTooSmall.into());
+    }
+    if Pointer::unpack_from_slice(&storage_account_data)?.is_initialized {
+        return Err(PointerError::AccountNotEmpty.into());
     }
     let pointer = Pointer {
         is_initialized: true,
-        token_address: Pubkey::new_from_array(*address_to_point),
+        token_address: address_to_point,
+        authority: *creator.key,
+        ..Pointer::default()
     };
     // Store data
     Pointer::pack_into_slice(&pointer, &mut storage_account_data);
@@ -86,21 +489,379 @@ fn process_instruction(
     Ok(())
 }
 
+fn process_propose_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    address_to_point: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    let mut pointer = load_pointer(program_id, storage_account)?;
+    check_authority(&pointer.authority, authority)?;
+    // A proposal has to be cancelled before another one restarts the delay
+    if pointer.has_pending_update {
+        return Err(PointerError::UpdatePending.into());
+    }
+    pointer.has_pending_update = true;
+    pointer.pending_address = address_to_point;
+    pointer.pending_executable_slot = clock
+        .slot
+        .checked_add(UPDATE_DELAY)
+        .ok_or(PointerError::Overflow)?;
+    Pointer::pack_into_slice(&pointer, &mut storage_account.try_borrow_mut_data()?);
+    Ok(())
+}
+
+fn process_execute_update(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    let mut pointer = load_pointer(program_id, storage_account)?;
+    if !pointer.has_pending_update {
+        return Err(PointerError::NoPendingUpdate.into());
+    }
+    if clock.slot < pointer.pending_executable_slot {
+        return Err(PointerError::UpdateTimelocked.into());
+    }
+    pointer.update(pointer.pending_address, clock.slot);
+    clear_pending_update(&mut pointer);
+    Pointer::pack_into_slice(&pointer, &mut storage_account.try_borrow_mut_data()?);
+    Ok(())
+}
+
+fn process_cancel_update(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let mut pointer = load_pointer(program_id, storage_account)?;
+    check_authority(&pointer.authority, authority)?;
+    if !pointer.has_pending_update {
+        return Err(PointerError::NoPendingUpdate.into());
+    }
+    clear_pending_update(&mut pointer);
+    Pointer::pack_into_slice(&pointer, &mut storage_account.try_borrow_mut_data()?);
+    Ok(())
+}
+
+fn clear_pending_update(pointer: &mut Pointer) {
+    pointer.has_pending_update = false;
+    pointer.pending_address = Pubkey::default();
+    pointer.pending_executable_slot = 0;
+}
+
+fn process_set_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_authority: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let mut pointer = load_pointer(program_id, storage_account)?;
+    check_authority(&pointer.authority, authority)?;
+    pointer.authority = new_authority;
+    Pointer::pack_into_slice(&pointer, &mut storage_account.try_borrow_mut_data()?);
+    Ok(())
+}
+
+fn process_close(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+    let pointer = load_pointer(program_id, storage_account)?;
+    check_authority(&pointer.authority, authority)?;
+    // Closing frees the address for a new pointer, so it waits like an update
+    if !pointer.has_pending_update || pointer.pending_address != Pubkey::default() {
+        return Err(PointerError::CloseNotProposed.into());
+    }
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    if clock.slot < pointer.pending_executable_slot {
+        return Err(PointerError::UpdateTimelocked.into());
+    }
+    let mut storage_account_data = storage_account.try_borrow_mut_data()?;
+    for byte in storage_account_data.iter_mut() {
+        *byte = 0;
+    }
+    let mut storage_account_balance = storage_account.try_borrow_mut_lamports()?;
+    let mut destination_balance = destination.try_borrow_mut_lamports()?;
+    **destination_balance = destination_balance
+        .checked_add(**storage_account_balance)
+        .ok_or(PointerError::Overflow)?;
+    **storage_account_balance = 0;
+    Ok(())
+}
+
+/// Reads the initialized pointer stored in `storage_account`
+fn load_pointer(
+    program_id: &Pubkey,
+    storage_account: &AccountInfo,
+) -> Result<Pointer, ProgramError> {
+    if storage_account.owner != program_id {
+        return Err(PointerError::IncorrectAccountOwner.into());
+    }
+    let storage_account_data = storage_account.try_borrow_data()?;
+    if storage_account_data.len() == LEGACY_STORAGE_DATA_SIZE {
+        return Err(PointerError::LegacyPointer.into());
+    }
+    if storage_account_data.len().lt(&STORAGE_DATA_SIZE) {
+        return Err(PointerError::PointerNotInitialized.into());
+    }
+    let pointer = Pointer::unpack_from_slice(&storage_account_data)?;
+    if !pointer.is_initialized {
+        return Err(PointerError::PointerNotInitialized.into());
+    }
+    Ok(pointer)
+}
+
+fn process_migrate(program_id: &Pubkey, accounts: &[AccountInfo], seed: [u8; 32]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let legacy_account = next_account_info(accounts_iter)?;
+    let storage_account = next_account_info(accounts_iter)?;
+    let deployer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    if !deployer.is_signer {
+        log::sol_log("Transaction need to be signed by deployer");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *deployer.key != Pubkey::from_str(DEPLOYER_ADDRESS).unwrap() {
+        return Err(PointerError::NotDeployer.into());
+    }
+    if legacy_account.owner != program_id {
+        return Err(PointerError::IncorrectAccountOwner.into());
+    }
+    let token_address = {
+        let legacy_data = legacy_account.try_borrow_data()?;
+        if legacy_data.len() != LEGACY_STORAGE_DATA_SIZE {
+            return Err(PointerError::NotLegacyPointer.into());
+        }
+        let (token_address, is_initialized) =
+            array_refs![array_ref![legacy_data, 0, LEGACY_STORAGE_DATA_SIZE], 32, 1];
+        if *is_initialized != [1] {
+            return Err(PointerError::PointerNotInitialized.into());
+        }
+        Pubkey::new_from_array(*token_address)
+    };
+    let (pointer_address, bump_seed) = find_pointer_address(deployer.key, &seed, program_id);
+    if *storage_account.key != pointer_address {
+        return Err(PointerError::InvalidPointerAddress.into());
+    }
+    if storage_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            deployer,
+            storage_account,
+            system_program,
+            rent.minimum_balance(STORAGE_DATA_SIZE),
+            STORAGE_DATA_SIZE,
+            &[POINTER_SEED, &deployer.key.to_bytes(), &seed, &[bump_seed]],
+        )?;
+    }
+    let mut storage_account_data = storage_account.try_borrow_mut_data()?;
+    if storage_account.owner != program_id {
+        return Err(PointerError::IncorrectAccountOwner.into());
+    }
+    if storage_account_data.len().lt(&STORAGE_DATA_SIZE) {
+        return Err(PointerError::AccountDataTooSmall.into());
+    }
+    if Pointer::unpack_from_slice(&storage_account_data)?.is_initialized {
+        return Err(PointerError::AccountNotEmpty.into());
+    }
+    let pointer = Pointer {
+        is_initialized: true,
+        token_address,
+        authority: *deployer.key,
+        ..Pointer::default()
+    };
+    Pointer::pack_into_slice(&pointer, &mut storage_account_data);
+
+    // Close the legacy pointer so only the new one resolves
+    for byte in legacy_account.try_borrow_mut_data()?.iter_mut() {
+        *byte = 0;
+    }
+    let mut legacy_balance = legacy_account.try_borrow_mut_lamports()?;
+    let mut deployer_balance = deployer.try_borrow_mut_lamports()?;
+    **deployer_balance = deployer_balance
+        .checked_add(**legacy_balance)
+        .ok_or(PointerError::Overflow)?;
+    **legacy_balance = 0;
+    Ok(())
+}
+
+fn process_initialize_registry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    seed: [u8; 32],
+    entries: [Pubkey; REGISTRY_KEYS],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let registry_account = next_account_info(accounts_iter)?;
+    let creator = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    if !creator.is_signer {
+        log::sol_log("Transaction need to be signed by creator");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // Registry must live at the address derived from creator and seed
+    let (registry_address, bump_seed) = find_registry_address(creator.key, &seed, program_id);
+    if *registry_account.key != registry_address {
+        return Err(PointerError::InvalidRegistryAddress.into());
+    }
+    if registry_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            creator,
+            registry_account,
+            system_program,
+            rent.minimum_balance(REGISTRY_DATA_SIZE),
+            REGISTRY_DATA_SIZE,
+            &[REGISTRY_SEED, &creator.key.to_bytes(), &seed, &[bump_seed]],
+        )?;
+    }
+    let mut registry_account_data = registry_account.try_borrow_mut_data()?;
+    if registry_account.owner != program_id {
+        return Err(PointerError::IncorrectAccountOwner.into());
+    }
+    if registry_account_data.len() != REGISTRY_DATA_SIZE {
+        return Err(PointerError::AccountDataTooSmall.into());
+    }
+    if Registry::unpack_from_slice(&registry_account_data)?.is_initialized {
+        return Err(PointerError::AccountNotEmpty.into());
+    }
+    let registry = Registry {
+        is_initialized: true,
+        authority: *creator.key,
+        entries,
+        ..Registry::default()
+    };
+    Registry::pack_into_slice(&registry, &mut registry_account_data);
+    Ok(())
+}
+
+fn process_propose_registry_entry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    key: RegistryKey,
+    address: Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let registry_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    let mut registry = load_registry(program_id, registry_account)?;
+    check_authority(&registry.authority, authority)?;
+    // A proposal has to be cancelled before another one restarts the delay
+    if registry.has_pending_update {
+        return Err(PointerError::UpdatePending.into());
+    }
+    registry.has_pending_update = true;
+    registry.pending_key = key;
+    registry.pending_address = address;
+    registry.pending_executable_slot = clock
+        .slot
+        .checked_add(UPDATE_DELAY)
+        .ok_or(PointerError::Overflow)?;
+    Registry::pack_into_slice(&registry, &mut registry_account.try_borrow_mut_data()?);
+    Ok(())
+}
+
+fn process_execute_registry_entry(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let registry_account = next_account_info(accounts_iter)?;
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    let mut registry = load_registry(program_id, registry_account)?;
+    if !registry.has_pending_update {
+        return Err(PointerError::NoPendingUpdate.into());
+    }
+    if clock.slot < registry.pending_executable_slot {
+        return Err(PointerError::UpdateTimelocked.into());
+    }
+    registry.update(registry.pending_key, registry.pending_address, clock.slot);
+    clear_pending_registry_entry(&mut registry);
+    Registry::pack_into_slice(&registry, &mut registry_account.try_borrow_mut_data()?);
+    Ok(())
+}
+
+fn process_cancel_registry_entry(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let registry_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let mut registry = load_registry(program_id, registry_account)?;
+    check_authority(&registry.authority, authority)?;
+    if !registry.has_pending_update {
+        return Err(PointerError::NoPendingUpdate.into());
+    }
+    clear_pending_registry_entry(&mut registry);
+    Registry::pack_into_slice(&registry, &mut registry_account.try_borrow_mut_data()?);
+    Ok(())
+}
+
+fn clear_pending_registry_entry(registry: &mut Registry) {
+    registry.has_pending_update = false;
+    registry.pending_key = RegistryKey::default();
+    registry.pending_address = Pubkey::default();
+    registry.pending_executable_slot = 0;
+}
+
+/// Reads the initialized registry stored in `registry_account`
+fn load_registry(
+    program_id: &Pubkey,
+    registry_account: &AccountInfo,
+) -> Result<Registry, ProgramError> {
+    if registry_account.owner != program_id {
+        return Err(PointerError::IncorrectAccountOwner.into());
+    }
+    // Pointers are owned by the program too, only the size tells them apart
+    let registry_account_data = registry_account.try_borrow_data()?;
+    if registry_account_data.len() != REGISTRY_DATA_SIZE {
+        return Err(PointerError::RegistryNotInitialized.into());
+    }
+    let registry = Registry::unpack_from_slice(&registry_account_data)?;
+    if !registry.is_initialized {
+        return Err(PointerError::RegistryNotInitialized.into());
+    }
+    Ok(registry)
+}
+
+/// Fails unless `authority` is the signing `expected` authority
+fn check_authority(expected: &Pubkey, authority: &AccountInfo) -> ProgramResult {
+    if *expected != *authority.key {
+        return Err(PointerError::NotAuthority.into());
+    }
+    if !authority.is_signer {
+        log::sol_log("Transaction need to be signed by authority");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
 // tests
 #[cfg(test)]
 mod test {
     use std::str::FromStr;
 
     use super::*;
-    use solana_sdk::clock::Epoch;
+    use solana_sdk::{clock::Epoch, system_program, sysvar};
+
+    const SEED: [u8; 32] = [7; 32];
 
     #[test]
     fn test_flow() {
         let program_id = Pubkey::default();
         let mut balance = 0;
-        let mut empty_data = vec![0; 33];
-        let storage_address =
-            Pubkey::from_str("4NGtJoZ8wy7mwtzWi8JByPMWbTAQHicHKAfcCbsx1yra").unwrap();
+        let mut balance_creator = 0;
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut empty_data = vec![0; STORAGE_DATA_SIZE];
+        let mut empty_data2 = vec![0; 0];
+        let mut empty_data3 = vec![0; 0];
+        let mut rent_data = vec![0; Rent::size_of()];
+        let creator_address = Pubkey::new_unique();
+        let (storage_address, _) = find_pointer_address(&creator_address, &SEED, &program_id);
         let storage_account = AccountInfo::new(
             &storage_address, // account pubkey
             false,            // is_signer
@@ -111,16 +872,748 @@ mod test {
             false,            // is_executable
             Epoch::default(), // rent_epoch
         );
-        let accounts = vec![storage_account];
+        let creator = AccountInfo::new(
+            &creator_address,     // account pubkey
+            true,                 // is_signer
+            true,                 // is_writable
+            &mut balance_creator, // balance in lamports
+            &mut empty_data2,     // storage
+            &program_id,          // owner pubkey
+            false,                // is_executable
+            Epoch::default(),     // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance,  // balance in lamports
+            &mut empty_data3,   // storage
+            &program_id,        // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut rent_data,     // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let mut accounts = vec![storage_account, creator, system_program, rent_account];
         let token_address =
             Pubkey::from_str("FJNj5YDJVT3pbtiZsMMeRPJumM35iF71rUzDQWC7CXqq").unwrap();
-        let instruction_data = token_address.to_bytes();
+        // Pointer has to be derived from creator and seed
+        let instruction_data = PointerInstruction::Initialize {
+            token_address,
+            seed: [1; 32],
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(PointerError::InvalidPointerAddress.into())
+        );
+        let instruction_data = PointerInstruction::Initialize {
+            token_address,
+            seed: SEED,
+        }
+        .pack();
+        accounts[1].is_signer = false;
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        accounts[1].is_signer = true;
         process_instruction(&program_id, &accounts, &instruction_data).unwrap();
-        
+
         // Check if data stored is correct
         let data_stored = accounts[0].data.borrow();
         let pointer = Pointer::unpack_from_slice(&data_stored).unwrap();
         assert_eq!(pointer.token_address, token_address);
         assert_eq!(pointer.is_initialized, true);
+        assert_eq!(pointer.authority, creator_address);
+        drop(data_stored);
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(PointerError::AccountNotEmpty.into())
+        );
+
+        // Authority points it somewhere else, the old address is kept
+        let mut zero_balance3 = 0;
+        let mut clock_data = vec![0; Clock::size_of()];
+        let clock_id = sysvar::clock::id();
+        let mut clock_account = AccountInfo::new(
+            &clock_id,          // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut clock_data,    // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let clock = Clock {
+            slot: 5,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut clock_account).unwrap();
+        let mut update_accounts = vec![accounts[0].clone(), accounts[1].clone(), clock_account];
+        let new_token_address = Pubkey::new_unique();
+        let instruction_data = PointerInstruction::ProposeUpdate {
+            token_address: new_token_address,
+        }
+        .pack();
+        let execute_data = PointerInstruction::ExecuteUpdate.pack();
+        let cancel_data = PointerInstruction::CancelUpdate.pack();
+        let execute_accounts = vec![update_accounts[0].clone(), update_accounts[2].clone()];
+        assert_eq!(
+            process_instruction(&program_id, &execute_accounts, &execute_data),
+            Err(PointerError::NoPendingUpdate.into())
+        );
+        assert_eq!(
+            process_instruction(&program_id, &accounts[..2], &cancel_data),
+            Err(PointerError::NoPendingUpdate.into())
+        );
+
+        // Cancelled proposal leaves the pointer untouched
+        process_instruction(&program_id, &update_accounts, &instruction_data).unwrap();
+        process_instruction(&program_id, &accounts[..2], &cancel_data).unwrap();
+        let pointer = Pointer::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(pointer.token_address, token_address);
+        assert!(!pointer.has_pending_update);
+
+        // Proposal is visible but only executable after the delay
+        process_instruction(&program_id, &update_accounts, &instruction_data).unwrap();
+        let pointer = Pointer::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(pointer.token_address, token_address);
+        assert!(pointer.has_pending_update);
+        assert_eq!(pointer.pending_address, new_token_address);
+        assert_eq!(pointer.pending_executable_slot, 5 + UPDATE_DELAY);
+        assert_eq!(
+            process_instruction(&program_id, &update_accounts, &instruction_data),
+            Err(PointerError::UpdatePending.into())
+        );
+        assert_eq!(
+            process_instruction(&program_id, &execute_accounts, &execute_data),
+            Err(PointerError::UpdateTimelocked.into())
+        );
+        let clock = Clock {
+            slot: 5 + UPDATE_DELAY,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut update_accounts[2]).unwrap();
+        process_instruction(&program_id, &execute_accounts, &execute_data).unwrap();
+        let pointer = Pointer::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(pointer.token_address, new_token_address);
+        assert!(!pointer.has_pending_update);
+        assert_eq!(
+            pointer.history(),
+            vec![HistoryEntry {
+                version: 1,
+                token_address,
+                slot: 5 + UPDATE_DELAY,
+            }]
+        );
+
+        // Authority hands the pointer over and loses access
+        let new_authority_address = Pubkey::new_unique();
+        let set_authority_data = PointerInstruction::SetAuthority {
+            new_authority: new_authority_address,
+        }
+        .pack();
+        process_instruction(&program_id, &accounts[..2], &set_authority_data).unwrap();
+        let pointer = Pointer::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(pointer.authority, new_authority_address);
+        assert_eq!(pointer.token_address, new_token_address);
+        assert_eq!(
+            process_instruction(&program_id, &update_accounts, &instruction_data),
+            Err(PointerError::NotAuthority.into())
+        );
+        update_accounts[1].key = &new_authority_address;
+        update_accounts[1].is_signer = false;
+        assert_eq!(
+            process_instruction(&program_id, &update_accounts, &instruction_data),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+    #[test]
+    fn test_history() {
+        let addresses: Vec<Pubkey> = (0..HISTORY_LENGTH + 3)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        let mut pointer = Pointer {
+            is_initialized: true,
+            token_address: addresses[0],
+            ..Pointer::default()
+        };
+        assert!(pointer.history().is_empty());
+        for (slot, address) in addresses.iter().enumerate().skip(1) {
+            pointer.update(*address, slot as u64 * 10);
+        }
+        // Only the latest updates are kept, newest first
+        let history = pointer.history();
+        assert_eq!(history.len(), HISTORY_LENGTH);
+        assert_eq!(
+            history[0],
+            HistoryEntry {
+                version: HISTORY_LENGTH as u64 + 2,
+                token_address: addresses[HISTORY_LENGTH + 1],
+                slot: (HISTORY_LENGTH as u64 + 2) * 10,
+            }
+        );
+        assert_eq!(history[HISTORY_LENGTH - 1].version, 3);
+        assert_eq!(history[HISTORY_LENGTH - 1].token_address, addresses[2]);
+        assert_eq!(pointer.token_address, addresses[HISTORY_LENGTH + 2]);
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        Pointer::pack_into_slice(&pointer, &mut storage_data);
+        assert_eq!(Pointer::unpack_from_slice(&storage_data).unwrap(), pointer);
+    }
+    #[test]
+    fn test_registry() {
+        let program_id = Pubkey::default();
+        let mut balance = 0;
+        let mut balance_creator = 0;
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut registry_data = vec![0; REGISTRY_DATA_SIZE];
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut rent_data = vec![0; Rent::size_of()];
+        let creator_address = Pubkey::new_unique();
+        let (registry_address, _) = find_registry_address(&creator_address, &SEED, &program_id);
+        let registry_account = AccountInfo::new(
+            &registry_address,  // account pubkey
+            false,              // is_signer
+            true,               // is_writable
+            &mut balance,       // balance in lamports
+            &mut registry_data, // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let creator = AccountInfo::new(
+            &creator_address,     // account pubkey
+            true,                 // is_signer
+            true,                 // is_writable
+            &mut balance_creator, // balance in lamports
+            &mut empty_data,      // storage
+            &program_id,          // owner pubkey
+            false,                // is_executable
+            Epoch::default(),     // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance,  // balance in lamports
+            &mut empty_data2,   // storage
+            &program_id,        // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut rent_data,     // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let accounts = vec![registry_account, creator, system_program, rent_account];
+        let mut clock_data = vec![0; Clock::size_of()];
+        let mut zero_balance3 = 0;
+        let clock_id = sysvar::clock::id();
+        let mut clock_account = AccountInfo::new(
+            &clock_id,          // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut clock_data,    // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let clock = Clock {
+            slot: 5,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut clock_account).unwrap();
+        let mut propose_accounts = vec![
+            accounts[0].clone(),
+            accounts[1].clone(),
+            clock_account.clone(),
+        ];
+        let execute_accounts = vec![accounts[0].clone(), clock_account];
+        let counter_address = Pubkey::new_unique();
+        let propose_counter_data = PointerInstruction::ProposeRegistryEntry {
+            key: RegistryKey::Counter,
+            address: counter_address,
+        }
+        .pack();
+        let execute_data = PointerInstruction::ExecuteRegistryEntry.pack();
+        let cancel_data = PointerInstruction::CancelRegistryEntry.pack();
+        assert_eq!(
+            process_instruction(&program_id, &propose_accounts, &propose_counter_data),
+            Err(PointerError::RegistryNotInitialized.into())
+        );
+        // Registry has to be derived from creator and seed
+        assert_eq!(
+            process_instruction(
+                &program_id,
+                &accounts,
+                &PointerInstruction::InitializeRegistry {
+                    seed: [1; 32],
+                    entries: [Pubkey::default(); REGISTRY_KEYS],
+                }
+                .pack()
+            ),
+            Err(PointerError::InvalidRegistryAddress.into())
+        );
+        // Initial entries are set right away
+        let treasury_address = Pubkey::new_unique();
+        let mut entries = [Pubkey::default(); REGISTRY_KEYS];
+        entries[RegistryKey::Treasury as usize] = treasury_address;
+        let instruction_data = PointerInstruction::InitializeRegistry {
+            seed: SEED,
+            entries,
+        }
+        .pack();
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let registry = Registry::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert!(registry.is_initialized);
+        assert_eq!(registry.authority, creator_address);
+        assert_eq!(registry.get(RegistryKey::Counter), Pubkey::default());
+        assert_eq!(registry.get(RegistryKey::Treasury), treasury_address);
+        assert!(registry.history().is_empty());
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(PointerError::AccountNotEmpty.into())
+        );
+        assert_eq!(
+            process_instruction(&program_id, &execute_accounts, &execute_data),
+            Err(PointerError::NoPendingUpdate.into())
+        );
+        assert_eq!(
+            process_instruction(&program_id, &accounts[..2], &cancel_data),
+            Err(PointerError::NoPendingUpdate.into())
+        );
+
+        // Cancelled proposal leaves the entries untouched
+        process_instruction(&program_id, &propose_accounts, &propose_counter_data).unwrap();
+        process_instruction(&program_id, &accounts[..2], &cancel_data).unwrap();
+        let registry = Registry::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(registry.get(RegistryKey::Counter), Pubkey::default());
+        assert!(!registry.has_pending_update);
+
+        // Proposal is visible but only executable after the delay
+        process_instruction(&program_id, &propose_accounts, &propose_counter_data).unwrap();
+        let registry = Registry::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(registry.get(RegistryKey::Counter), Pubkey::default());
+        assert!(registry.has_pending_update);
+        assert_eq!(registry.pending_key, RegistryKey::Counter);
+        assert_eq!(registry.pending_address, counter_address);
+        assert_eq!(registry.pending_executable_slot, 5 + UPDATE_DELAY);
+        assert_eq!(
+            process_instruction(&program_id, &propose_accounts, &propose_counter_data),
+            Err(PointerError::UpdatePending.into())
+        );
+        assert_eq!(
+            process_instruction(&program_id, &execute_accounts, &execute_data),
+            Err(PointerError::UpdateTimelocked.into())
+        );
+        let clock = Clock {
+            slot: 5 + UPDATE_DELAY,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut propose_accounts[2]).unwrap();
+        process_instruction(&program_id, &execute_accounts, &execute_data).unwrap();
+
+        // Entries change independently of each other, replaced ones are kept
+        let fee_oracle_address = Pubkey::new_unique();
+        process_instruction(
+            &program_id,
+            &propose_accounts,
+            &PointerInstruction::ProposeRegistryEntry {
+                key: RegistryKey::FeeOracle,
+                address: fee_oracle_address,
+            }
+            .pack(),
+        )
+        .unwrap();
+        let clock = Clock {
+            slot: 5 + 2 * UPDATE_DELAY,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut propose_accounts[2]).unwrap();
+        process_instruction(&program_id, &execute_accounts, &execute_data).unwrap();
+        let registry = Registry::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(registry.get(RegistryKey::Counter), counter_address);
+        assert_eq!(registry.get(RegistryKey::Treasury), treasury_address);
+        assert_eq!(registry.get(RegistryKey::Config), Pubkey::default());
+        assert_eq!(registry.get(RegistryKey::FeeOracle), fee_oracle_address);
+        assert!(!registry.has_pending_update);
+        assert_eq!(
+            registry.history(),
+            vec![
+                RegistryHistoryEntry {
+                    version: 2,
+                    key: RegistryKey::FeeOracle,
+                    address: Pubkey::default(),
+                    slot: 5 + 2 * UPDATE_DELAY,
+                },
+                RegistryHistoryEntry {
+                    version: 1,
+                    key: RegistryKey::Counter,
+                    address: Pubkey::default(),
+                    slot: 5 + UPDATE_DELAY,
+                }
+            ]
+        );
+
+        // Only the signing authority can propose or cancel
+        let mut wrong_accounts = vec![
+            accounts[0].clone(),
+            accounts[2].clone(),
+            propose_accounts[2].clone(),
+        ];
+        wrong_accounts[1].is_signer = true;
+        assert_eq!(
+            process_instruction(&program_id, &wrong_accounts, &propose_counter_data),
+            Err(PointerError::NotAuthority.into())
+        );
+        assert_eq!(
+            process_instruction(&program_id, &wrong_accounts[..2], &cancel_data),
+            Err(PointerError::NotAuthority.into())
+        );
+        propose_accounts[1].is_signer = false;
+        assert_eq!(
+            process_instruction(&program_id, &propose_accounts, &propose_counter_data),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+    #[test]
+    fn test_migrate() {
+        let program_id = Pubkey::default();
+        let mut balance_legacy = 5;
+        let mut balance = 0;
+        let mut balance_deployer = 0;
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut legacy_data = vec![0; LEGACY_STORAGE_DATA_SIZE];
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut rent_data = vec![0; Rent::size_of()];
+        let token_address = Pubkey::new_unique();
+        legacy_data[..32].copy_from_slice(&token_address.to_bytes());
+        legacy_data[32] = 1;
+        let legacy_address = Pubkey::new_unique();
+        let legacy_account = AccountInfo::new(
+            &legacy_address,     // account pubkey
+            false,               // is_signer
+            true,                // is_writable
+            &mut balance_legacy, // balance in lamports
+            &mut legacy_data,    // storage
+            &program_id,         // owner pubkey
+            false,               // is_executable
+            Epoch::default(),    // rent_epoch
+        );
+        let deployer_address = Pubkey::from_str(DEPLOYER_ADDRESS).unwrap();
+        let (storage_address, _) = find_pointer_address(&deployer_address, &SEED, &program_id);
+        let storage_account = AccountInfo::new(
+            &storage_address,  // account pubkey
+            false,             // is_signer
+            true,              // is_writable
+            &mut balance,      // balance in lamports
+            &mut storage_data, // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let deployer = AccountInfo::new(
+            &deployer_address,     // account pubkey
+            true,                  // is_signer
+            true,                  // is_writable
+            &mut balance_deployer, // balance in lamports
+            &mut empty_data,       // storage
+            &program_id,           // owner pubkey
+            false,                 // is_executable
+            Epoch::default(),      // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance,  // balance in lamports
+            &mut empty_data2,   // storage
+            &program_id,        // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut rent_data,     // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let mut accounts = vec![
+            legacy_account,
+            storage_account,
+            deployer,
+            system_program,
+            rent_account,
+        ];
+
+        // Legacy pointers can not be used until migrated
+        let close_accounts = vec![
+            accounts[0].clone(),
+            accounts[2].clone(),
+            accounts[2].clone(),
+        ];
+        assert_eq!(
+            process_instruction(
+                &program_id,
+                &close_accounts,
+                &PointerInstruction::Close.pack()
+            ),
+            Err(PointerError::LegacyPointer.into())
+        );
+
+        // Only the deployer can migrate
+        let instruction_data = PointerInstruction::Migrate { seed: SEED }.pack();
+        let intruder_address = Pubkey::new_unique();
+        let mut wrong_accounts = accounts.clone();
+        wrong_accounts[2].key = &intruder_address;
+        assert_eq!(
+            process_instruction(&program_id, &wrong_accounts, &instruction_data),
+            Err(PointerError::NotDeployer.into())
+        );
+        accounts[2].is_signer = false;
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        accounts[2].is_signer = true;
+        let mut wrong_accounts = accounts.clone();
+        wrong_accounts[0] = accounts[1].clone();
+        assert_eq!(
+            process_instruction(&program_id, &wrong_accounts, &instruction_data),
+            Err(PointerError::NotLegacyPointer.into())
+        );
+
+        // New pointer keeps the address, the legacy one is closed
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let pointer = Pointer::unpack_from_slice(&accounts[1].data.borrow()).unwrap();
+        assert!(pointer.is_initialized);
+        assert_eq!(pointer.token_address, token_address);
+        assert_eq!(pointer.authority, deployer_address);
+        assert_eq!(pointer.version, 0);
+        assert!(accounts[0].data.borrow().iter().all(|byte| *byte == 0));
+        assert_eq!(**accounts[0].lamports.borrow(), 0);
+        assert_eq!(**accounts[2].lamports.borrow(), 5);
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(PointerError::PointerNotInitialized.into())
+        );
+    }
+    #[test]
+    fn test_close() {
+        let program_id = Pubkey::default();
+        let mut balance = 5;
+        let mut balance_creator = 0;
+        let mut balance_destination = 0;
+        let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let creator_address = Pubkey::new_unique();
+        let pointer = Pointer {
+            is_initialized: true,
+            token_address: Pubkey::new_unique(),
+            authority: creator_address,
+            ..Pointer::default()
+        };
+        Pointer::pack_into_slice(&pointer, &mut storage_data);
+        let (storage_address, _) = find_pointer_address(&creator_address, &SEED, &program_id);
+        let storage_account = AccountInfo::new(
+            &storage_address,  // account pubkey
+            false,             // is_signer
+            true,              // is_writable
+            &mut balance,      // balance in lamports
+            &mut storage_data, // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let creator = AccountInfo::new(
+            &creator_address,     // account pubkey
+            true,                 // is_signer
+            false,                // is_writable
+            &mut balance_creator, // balance in lamports
+            &mut empty_data2,     // storage
+            &program_id,          // owner pubkey
+            false,                // is_executable
+            Epoch::default(),     // rent_epoch
+        );
+        let destination_address = Pubkey::new_unique();
+        let destination = AccountInfo::new(
+            &destination_address,     // account pubkey
+            false,                    // is_signer
+            true,                     // is_writable
+            &mut balance_destination, // balance in lamports
+            &mut empty_data,          // storage
+            &program_id,              // owner pubkey
+            false,                    // is_executable
+            Epoch::default(),         // rent_epoch
+        );
+        let mut empty_data3 = vec![0; 0];
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut zero_balance3 = 0;
+        let mut rent_data = vec![0; Rent::size_of()];
+        let mut clock_data = vec![0; Clock::size_of()];
+        let clock_id = sysvar::clock::id();
+        let mut clock_account = AccountInfo::new(
+            &clock_id,         // account pubkey
+            false,             // is_signer
+            false,             // is_writable
+            &mut zero_balance, // balance in lamports
+            &mut clock_data,   // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let clock = Clock {
+            slot: 5,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut clock_account).unwrap();
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut empty_data3,   // storage
+            &program_id,        // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut rent_data,     // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let mut accounts = vec![storage_account, creator, destination, clock_account];
+        let initialize_accounts = vec![
+            accounts[0].clone(),
+            accounts[1].clone(),
+            system_program,
+            rent_account,
+        ];
+        let propose_accounts = vec![
+            accounts[0].clone(),
+            accounts[1].clone(),
+            accounts[3].clone(),
+        ];
+        // Only the authority of the pointer can close it
+        let mut wrong_accounts = accounts.clone();
+        wrong_accounts[1] = accounts[2].clone();
+        wrong_accounts[1].is_signer = true;
+        let instruction_data = PointerInstruction::Close.pack();
+        assert_eq!(
+            process_instruction(&program_id, &wrong_accounts, &instruction_data),
+            Err(PointerError::NotAuthority.into())
+        );
+
+        // Closing and initializing again can not retarget the pointer at once
+        let retarget_data = PointerInstruction::Initialize {
+            token_address: Pubkey::new_unique(),
+            seed: SEED,
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(PointerError::CloseNotProposed.into())
+        );
+        assert_eq!(
+            process_instruction(&program_id, &initialize_accounts, &retarget_data),
+            Err(PointerError::AccountNotEmpty.into())
+        );
+        assert_eq!(
+            Pointer::unpack_from_slice(&accounts[0].data.borrow()).unwrap(),
+            pointer
+        );
+        // Proposals of other addresses do not allow closing either
+        process_instruction(
+            &program_id,
+            &propose_accounts,
+            &PointerInstruction::ProposeUpdate {
+                token_address: Pubkey::new_unique(),
+            }
+            .pack(),
+        )
+        .unwrap();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(PointerError::CloseNotProposed.into())
+        );
+        process_instruction(
+            &program_id,
+            &accounts[..2],
+            &PointerInstruction::CancelUpdate.pack(),
+        )
+        .unwrap();
+
+        // Closing waits for the delay of its proposal
+        process_instruction(
+            &program_id,
+            &propose_accounts,
+            &PointerInstruction::ProposeUpdate {
+                token_address: Pubkey::default(),
+            }
+            .pack(),
+        )
+        .unwrap();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(PointerError::UpdateTimelocked.into())
+        );
+        let clock = Clock {
+            slot: 5 + UPDATE_DELAY,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut accounts[3]).unwrap();
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        assert_eq!(**accounts[0].lamports.borrow(), 0);
+        assert_eq!(**accounts[2].lamports.borrow(), 5);
+        assert!(accounts[0].data.borrow().iter().all(|byte| *byte == 0));
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(PointerError::PointerNotInitialized.into())
+        );
     }
 }
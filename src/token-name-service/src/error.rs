@@ -0,0 +1,100 @@
+use name_service_common::error::CommonError;
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    log,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+/// Errors returned by the token name service, surfaced to clients as
+/// `ProgramError::Custom(code)` where code is the variant index
+#[derive(Clone, Copy, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum NameServiceError {
+    #[error("Invalid payment account")]
+    InvalidPaymentAccount,
+    #[error("Token name is not valid")]
+    InvalidTokenName,
+    #[error("Token account data is invalid")]
+    InvalidTokenAccount,
+    #[error("You are not minter of this token")]
+    NotTokenMinter,
+    #[error("Account must be owned by program")]
+    IncorrectAccountOwner,
+    #[error("Account data is not empty")]
+    AccountNotEmpty,
+    #[error("Account data field is insufficient")]
+    AccountDataTooSmall,
+    #[error("Insufficient funds to pay registration fee")]
+    InsufficientFunds,
+    #[error("Config account does not match the program")]
+    InvalidConfigAddress,
+    #[error("Config is already initialized")]
+    ConfigAlreadyInitialized,
+    #[error("Config is not initialized")]
+    ConfigNotInitialized,
+    #[error("You are not admin of this program")]
+    NotAdmin,
+    #[error("Token name is not registered")]
+    NameNotRegistered,
+    #[error("Storage account holds the name of another token")]
+    InvalidStorageAccount,
+    #[error("Storage account does not match the token")]
+    InvalidRecordAddress,
+    #[error("Account balance is below rent exemption")]
+    NotRentExempt,
+    #[error("Lamports arithmetic overflowed")]
+    Overflow,
+    #[error("Beneficiary shares must add up to 10000 basis points")]
+    InvalidBeneficiaries,
+    #[error("Beneficiary accounts do not match the config")]
+    InvalidBeneficiaryAccount,
+    #[error("Program is paused")]
+    ProgramPaused,
+    #[error("Reserved name account does not match the name")]
+    InvalidReservedAddress,
+    #[error("Token name is reserved")]
+    NameReserved,
+    #[error("Token name is not reserved")]
+    NameNotReserved,
+    #[error("Only the deployer can initialize the config")]
+    NotDeployer,
+}
+impl From<CommonError> for NameServiceError {
+    fn from(e: CommonError) -> Self {
+        match e {
+            CommonError::InvalidPaymentAccount => NameServiceError::InvalidPaymentAccount,
+            CommonError::IncorrectAccountOwner => NameServiceError::IncorrectAccountOwner,
+            CommonError::AccountDataTooSmall => NameServiceError::AccountDataTooSmall,
+            CommonError::InvalidConfigAddress => NameServiceError::InvalidConfigAddress,
+            CommonError::ConfigNotInitialized => NameServiceError::ConfigNotInitialized,
+            CommonError::NotAdmin => NameServiceError::NotAdmin,
+            CommonError::Overflow => NameServiceError::Overflow,
+            CommonError::InvalidBeneficiaries => NameServiceError::InvalidBeneficiaries,
+            CommonError::InvalidBeneficiaryAccount => NameServiceError::InvalidBeneficiaryAccount,
+            CommonError::ProgramPaused => NameServiceError::ProgramPaused,
+            CommonError::InvalidReservedAddress => NameServiceError::InvalidReservedAddress,
+            CommonError::NameReserved => NameServiceError::NameReserved,
+            CommonError::NameNotReserved => NameServiceError::NameNotReserved,
+        }
+    }
+}
+impl From<NameServiceError> for ProgramError {
+    fn from(e: NameServiceError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+impl<T> DecodeError<T> for NameServiceError {
+    fn type_of() -> &'static str {
+        "NameServiceError"
+    }
+}
+impl PrintProgramError for NameServiceError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        log::sol_log(&self.to_string());
+    }
+}
@@ -0,0 +1,317 @@
+use arrayref::{array_ref, array_refs};
+use name_service_common::{
+    pack_beneficiaries, unpack_beneficiaries, unpack_fees, Beneficiary, BENEFICIARIES_SIZE,
+    FEE_TIERS, MAX_BENEFICIARIES,
+};
+use solana_sdk::{program_error::ProgramError, pubkey::Pubkey};
+
+/// Instructions supported by the program, the first byte of instruction
+/// data selects the variant. Registrations fail while the program is
+/// paused, see `is_pausable`
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenNameInstruction {
+    /// Register a name for a token, signed by the minter of the token. Name
+    /// is encoded as one byte of length followed by the name
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config account
+    /// 1. `[writable]` Treasury, derived from the program id
+    /// 2. `[]` Token mint
+    /// 3. `[signer]` Minter of the token
+    /// 4. `[writable]` Storage account, derived from the token
+    /// 5. `[writable, signer]` Payer, funds the storage account
+    /// 6. `[]` System program
+    /// 7. `[]` Rent sysvar
+    /// 8. `[]` Reserved name account, derived from the name hash
+    /// 9. `[signer]` Authority of the reserved name, read only when the
+    ///    name is reserved
+    Register { token_name: Vec<u8> },
+    /// Create the program config and treasury, the signer becomes the admin
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Config account, derived from the program id
+    /// 1. `[writable, signer]` Admin, has to be the deployer key
+    /// 2. `[]` System program
+    /// 3. `[]` Rent sysvar
+    /// 4. `[writable]` Treasury, derived from the program id
+    InitializeConfig {
+        beneficiaries: [Beneficiary; MAX_BENEFICIARIES],
+        fees: [u64; FEE_TIERS],
+    },
+    /// Replace admin, beneficiaries and fees of the program config
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Config account
+    /// 1. `[signer]` Current admin
+    UpdateConfig {
+        admin: Pubkey,
+        beneficiaries: [Beneficiary; MAX_BENEFICIARIES],
+        fees: [u64; FEE_TIERS],
+    },
+    /// Delete the name of a token, signed by the minter of the token. The
+    /// lamports of the storage account go to the destination
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Storage account
+    /// 1. `[]` Token mint
+    /// 2. `[signer]` Minter of the token
+    /// 3. `[writable]` Destination
+    Close,
+    /// Split the treasury balance above rent between the beneficiaries by
+    /// their shares, anyone can call it
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config account
+    /// 1. `[writable]` Treasury, derived from the program id
+    /// 2. `[]` Rent sysvar
+    /// 3. `[writable]` Beneficiaries with a share, in config order
+    Withdraw,
+    /// Stop or resume registrations of token names
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Config account
+    /// 1. `[signer]` Admin
+    SetPaused { paused: bool },
+    /// Reserve the token name hashing to `name_hash`, see
+    /// `reserved_name_hash`. Only `authority` can register it afterwards,
+    /// reserving it again replaces the authority
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config account
+    /// 1. `[writable, signer]` Admin, funds the reserved name account
+    /// 2. `[writable]` Reserved name account, derived from the name hash
+    /// 3. `[]` System program
+    /// 4. `[]` Rent sysvar
+    ReserveName {
+        name_hash: [u8; 32],
+        authority: Pubkey,
+    },
+    /// Make a reserved token name available to everyone again
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config account
+    /// 1. `[signer]` Admin
+    /// 2. `[writable]` Reserved name account
+    /// 3. `[writable]` Destination of the reserved name account lamports
+    ReleaseName { name_hash: [u8; 32] },
+}
+impl TokenNameInstruction {
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let instruction = match tag {
+            0 => {
+                let (&length, token_name) = rest
+                    .split_first()
+                    .ok_or(ProgramError::InvalidInstructionData)?;
+                if token_name.len() != length as usize {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                TokenNameInstruction::Register {
+                    token_name: token_name.to_vec(),
+                }
+            }
+            1 => {
+                if rest.len() != BENEFICIARIES_SIZE + 8 * FEE_TIERS {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let (beneficiaries, fees) = array_refs![
+                    array_ref![rest, 0, BENEFICIARIES_SIZE + 8 * FEE_TIERS],
+                    BENEFICIARIES_SIZE,
+                    8 * FEE_TIERS
+                ];
+                TokenNameInstruction::InitializeConfig {
+                    beneficiaries: unpack_beneficiaries(beneficiaries),
+                    fees: unpack_fees(fees),
+                }
+            }
+            2 => {
+                if rest.len() != 32 + BENEFICIARIES_SIZE + 8 * FEE_TIERS {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let (admin, beneficiaries, fees) = array_refs![
+                    array_ref![rest, 0, 32 + BENEFICIARIES_SIZE + 8 * FEE_TIERS],
+                    32,
+                    BENEFICIARIES_SIZE,
+                    8 * FEE_TIERS
+                ];
+                TokenNameInstruction::UpdateConfig {
+                    admin: Pubkey::new_from_array(*admin),
+                    beneficiaries: unpack_beneficiaries(beneficiaries),
+                    fees: unpack_fees(fees),
+                }
+            }
+            3 if rest.is_empty() => TokenNameInstruction::Close,
+            4 if rest.is_empty() => TokenNameInstruction::Withdraw,
+            5 => match rest {
+                [0] => TokenNameInstruction::SetPaused { paused: false },
+                [1] => TokenNameInstruction::SetPaused { paused: true },
+                _ => return Err(ProgramError::InvalidInstructionData),
+            },
+            6 => {
+                if rest.len() != 64 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let (name_hash, authority) = array_refs![array_ref![rest, 0, 64], 32, 32];
+                TokenNameInstruction::ReserveName {
+                    name_hash: *name_hash,
+                    authority: Pubkey::new_from_array(*authority),
+                }
+            }
+            7 => {
+                if rest.len() != 32 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                TokenNameInstruction::ReleaseName {
+                    name_hash: *array_ref![rest, 0, 32],
+                }
+            }
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+        Ok(instruction)
+    }
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(1 + 32 + BENEFICIARIES_SIZE + 8 * FEE_TIERS);
+        match self {
+            TokenNameInstruction::Register { token_name } => {
+                buf.push(0);
+                buf.push(token_name.len() as u8);
+                buf.extend_from_slice(token_name);
+            }
+            TokenNameInstruction::InitializeConfig {
+                beneficiaries,
+                fees,
+            } => {
+                buf.push(1);
+                let mut beneficiaries_dst = [0u8; BENEFICIARIES_SIZE];
+                pack_beneficiaries(beneficiaries, &mut beneficiaries_dst);
+                buf.extend_from_slice(&beneficiaries_dst);
+                for fee in fees {
+                    buf.extend_from_slice(&fee.to_le_bytes());
+                }
+            }
+            TokenNameInstruction::UpdateConfig {
+                admin,
+                beneficiaries,
+                fees,
+            } => {
+                buf.push(2);
+                buf.extend_from_slice(&admin.to_bytes());
+                let mut beneficiaries_dst = [0u8; BENEFICIARIES_SIZE];
+                pack_beneficiaries(beneficiaries, &mut beneficiaries_dst);
+                buf.extend_from_slice(&beneficiaries_dst);
+                for fee in fees {
+                    buf.extend_from_slice(&fee.to_le_bytes());
+                }
+            }
+            TokenNameInstruction::Close => buf.push(3),
+            TokenNameInstruction::Withdraw => buf.push(4),
+            TokenNameInstruction::SetPaused { paused } => {
+                buf.push(5);
+                buf.push(*paused as u8);
+            }
+            TokenNameInstruction::ReserveName {
+                name_hash,
+                authority,
+            } => {
+                buf.push(6);
+                buf.extend_from_slice(name_hash);
+                buf.extend_from_slice(&authority.to_bytes());
+            }
+            TokenNameInstruction::ReleaseName { name_hash } => {
+                buf.push(7);
+                buf.extend_from_slice(name_hash);
+            }
+        }
+        buf
+    }
+    /// Whether the instruction is refused while the program is paused
+    pub fn is_pausable(&self) -> bool {
+        matches!(self, TokenNameInstruction::Register { .. })
+    }
+}
+
+// tests
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let instruction = TokenNameInstruction::Register {
+            token_name: b"some super random token name xxx".to_vec(),
+        };
+        let packed = instruction.pack();
+        assert_eq!(packed.len(), 2 + 32);
+        assert_eq!(TokenNameInstruction::unpack(&packed).unwrap(), instruction);
+        assert_eq!(
+            TokenNameInstruction::unpack(&packed[1..]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            TokenNameInstruction::unpack(&[]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        let instructions = vec![
+            TokenNameInstruction::Register {
+                token_name: "a token name longer than thirty two bytes \u{1f980}"
+                    .as_bytes()
+                    .to_vec(),
+            },
+            TokenNameInstruction::InitializeConfig {
+                beneficiaries: [
+                    Beneficiary {
+                        address: Pubkey::new_unique(),
+                        share: 7_000,
+                    },
+                    Beneficiary {
+                        address: Pubkey::new_unique(),
+                        share: 3_000,
+                    },
+                    Beneficiary::default(),
+                    Beneficiary::default(),
+                ],
+                fees: [5, 4, 3, 2, 1],
+            },
+            TokenNameInstruction::UpdateConfig {
+                admin: Pubkey::new_unique(),
+                beneficiaries: [Beneficiary {
+                    address: Pubkey::new_unique(),
+                    share: 2_500,
+                }; MAX_BENEFICIARIES],
+                fees: [42; FEE_TIERS],
+            },
+            TokenNameInstruction::SetPaused { paused: true },
+            TokenNameInstruction::ReserveName {
+                name_hash: [3; 32],
+                authority: Pubkey::new_unique(),
+            },
+            TokenNameInstruction::ReleaseName { name_hash: [3; 32] },
+        ];
+        for instruction in instructions {
+            let packed = instruction.pack();
+            assert_eq!(TokenNameInstruction::unpack(&packed).unwrap(), instruction);
+            assert_eq!(
+                TokenNameInstruction::unpack(&packed[..packed.len() - 1]),
+                Err(ProgramError::InvalidInstructionData)
+            );
+        }
+        assert_eq!(
+            TokenNameInstruction::unpack(&[3]).unwrap(),
+            TokenNameInstruction::Close
+        );
+        assert_eq!(
+            TokenNameInstruction::unpack(&[3, 0]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            TokenNameInstruction::unpack(&[4]).unwrap(),
+            TokenNameInstruction::Withdraw
+        );
+        assert_eq!(
+            TokenNameInstruction::unpack(&[5, 2]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+}
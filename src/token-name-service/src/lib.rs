@@ -1,54 +1,166 @@
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use name_normalization::{
+    name_extension_len, normalize_name, pack_name, unpack_name, PADDED_NAME_SIZE,
+};
+use name_service_common::{
+    config::ServiceConfig, create_pda_account, find_config_address, find_treasury_address,
+    pack_beneficiaries, pack_fees, unpack_beneficiaries, unpack_fees, Beneficiary,
+    BENEFICIARIES_SIZE, CONFIG_SEED, DEPLOYER_ADDRESS, FEE_TIERS, MAX_BENEFICIARIES, TREASURY_SEED,
+};
 use solana_program::{
     entrypoint,
     entrypoint::ProgramResult,
     log,
+    program::invoke,
     program_pack::{IsInitialized, Pack, Sealed},
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
 };
 use solana_sdk::{
     account_info::{next_account_info, AccountInfo},
-    program_error::ProgramError,
+    program_error::{PrintProgramError, ProgramError},
     pubkey::Pubkey,
 };
-use std::{str::from_utf8, str::FromStr};
+use std::str::FromStr;
+
+pub mod error;
+pub mod instruction;
+use error::NameServiceError;
+use instruction::TokenNameInstruction;
 
-static PAYMENT_ACCOUNT_ADDRESS: &'static str = "Gsun7cGFrSUm3N8TEBq7Uu9xz4c9cE4pKdbtETQiSgZX";
-static REGISTRATION_FEE: u64 = 1_000_000_000;
 const STORAGE_DATA_SIZE: usize = 64;
-const MAX_DATA_SIZE: usize = 32;
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
+const CONFIG_DATA_SIZE: usize = 34 + BENEFICIARIES_SIZE + 8 * FEE_TIERS;
+const TOKEN_SEED: &[u8] = b"token";
+// Names longer than the padded head continue after it
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Register {
     pub token_address: Pubkey,
-    pub token_name: [u8; 32],
+    pub token_name: Vec<u8>,
+}
+impl Register {
+    /// Size of the account holding this record
+    pub fn packed_len(&self) -> usize {
+        STORAGE_DATA_SIZE + name_extension_len(&self.token_name)
+    }
 }
 impl Sealed for Register {}
 impl IsInitialized for Register {
+    /// Registered records always hold their token, closed ones are zeroed
     fn is_initialized(&self) -> bool {
-        true
+        self.token_address != Pubkey::default()
     }
 }
 impl Pack for Register {
     const LEN: usize = STORAGE_DATA_SIZE;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < STORAGE_DATA_SIZE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let extension = &src[STORAGE_DATA_SIZE..];
         let src = array_ref![src, 0, STORAGE_DATA_SIZE];
-        let (token_address, token_name) = array_refs![src, 32, 32];
+        let (token_address, token_name) = array_refs![src, 32, PADDED_NAME_SIZE];
         let token_address = Pubkey::new_from_array(*token_address);
+        let token_name =
+            unpack_name(token_name, extension).ok_or(ProgramError::InvalidAccountData)?;
         Ok(Register {
             token_address,
-            token_name: *token_name,
+            token_name,
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
+        let (dst, extension_dst) = dst.split_at_mut(STORAGE_DATA_SIZE);
         let dst = array_mut_ref![dst, 0, STORAGE_DATA_SIZE];
-        let (token_address_dst, token_name_dst) = mut_array_refs![dst, 32, 32];
-        let &Register {
-            ref token_address,
+        let (token_address_dst, token_name_dst) = mut_array_refs![dst, 32, PADDED_NAME_SIZE];
+        let Register {
+            token_address,
             token_name,
         } = self;
-        *token_name_dst = token_name;
+        pack_name(token_name, token_name_dst, extension_dst);
         *token_address_dst = token_address.to_bytes();
     }
 }
+// Program wide settings, managed by admin
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Config {
+    pub is_initialized: bool,
+    pub admin: Pubkey,
+    /// Accounts the treasury is split between on withdraw
+    pub beneficiaries: [Beneficiary; MAX_BENEFICIARIES],
+    /// Registration fee in lamports, indexed by name length, see `fee_for`
+    pub fees: [u64; FEE_TIERS],
+    /// Registrations are refused while set
+    pub paused: bool,
+}
+impl ServiceConfig for Config {
+    type Error = NameServiceError;
+    fn admin(&self) -> &Pubkey {
+        &self.admin
+    }
+    fn beneficiaries(&self) -> &[Beneficiary; MAX_BENEFICIARIES] {
+        &self.beneficiaries
+    }
+    fn fees(&self) -> &[u64; FEE_TIERS] {
+        &self.fees
+    }
+    fn paused(&self) -> bool {
+        self.paused
+    }
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused
+    }
+}
+impl Sealed for Config {}
+impl IsInitialized for Config {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for Config {
+    const LEN: usize = CONFIG_DATA_SIZE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, CONFIG_DATA_SIZE];
+        let (is_initialized, admin, beneficiaries, fees, paused) =
+            array_refs![src, 1, 32, BENEFICIARIES_SIZE, 8 * FEE_TIERS, 1];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let paused = match paused {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Config {
+            is_initialized,
+            admin: Pubkey::new_from_array(*admin),
+            beneficiaries: unpack_beneficiaries(beneficiaries),
+            fees: unpack_fees(fees),
+            paused,
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, CONFIG_DATA_SIZE];
+        let (is_initialized_dst, admin_dst, beneficiaries_dst, fees_dst, paused_dst) =
+            mut_array_refs![dst, 1, 32, BENEFICIARIES_SIZE, 8 * FEE_TIERS, 1];
+        let &Config {
+            is_initialized,
+            ref admin,
+            ref beneficiaries,
+            fees,
+            paused,
+        } = self;
+        is_initialized_dst[0] = is_initialized as u8;
+        *admin_dst = admin.to_bytes();
+        pack_beneficiaries(beneficiaries, beneficiaries_dst);
+        pack_fees(&fees, fees_dst);
+        paused_dst[0] = paused as u8;
+    }
+}
+/// Address of the account holding the name of `token`
+pub fn find_token_record_address(token: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TOKEN_SEED, &token.to_bytes()], program_id)
+}
 entrypoint!(process_instruction);
 
 // Program entrypoint's implementation
@@ -58,89 +170,299 @@ fn process_instruction(
     instruction_data: &[u8],
 ) -> ProgramResult {
     log::sol_log("Rust program entrypoint");
-    let data_to_save =
-        from_utf8(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let instruction = TokenNameInstruction::unpack(instruction_data)?;
+    if instruction.is_pausable() {
+        Config::check_not_paused(program_id, accounts)?;
+    }
+    let result = match instruction {
+        TokenNameInstruction::Register { token_name } => {
+            process_register(program_id, accounts, token_name)
+        }
+        TokenNameInstruction::InitializeConfig {
+            beneficiaries,
+            fees,
+        } => process_initialize_config(program_id, accounts, beneficiaries, fees),
+        TokenNameInstruction::UpdateConfig {
+            admin,
+            beneficiaries,
+            fees,
+        } => process_update_config(program_id, accounts, admin, beneficiaries, fees),
+        TokenNameInstruction::Close => process_close(program_id, accounts),
+        TokenNameInstruction::Withdraw => Config::process_withdraw(program_id, accounts),
+        TokenNameInstruction::SetPaused { paused } => {
+            Config::process_set_paused(program_id, accounts, paused)
+        }
+        TokenNameInstruction::ReserveName {
+            name_hash,
+            authority,
+        } => Config::process_reserve_name(program_id, accounts, name_hash, authority),
+        TokenNameInstruction::ReleaseName { name_hash } => {
+            Config::process_release_name(program_id, accounts, name_hash)
+        }
+    };
+    if let Err(error) = &result {
+        error.print::<NameServiceError>();
+    }
+    result
+}
+
+fn process_register(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    token_name: Vec<u8>,
+) -> ProgramResult {
+    let token_name = normalize_name(&token_name).map_err(|_| NameServiceError::InvalidTokenName)?;
+    // Shorter names are more expensive, length is counted in characters
+    let name_length = token_name.chars().count();
 
     let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let config = Config::load(program_id, config_account)?;
+    let fee = config.fee_for(name_length);
     let payment_account = next_account_info(accounts_iter)?;
 
     // Validate payment account
-    if payment_account.key.to_bytes()
-        != Pubkey::from_str(PAYMENT_ACCOUNT_ADDRESS)
-            .unwrap()
-            .to_bytes()
-    {
-        log::sol_log("Invalid payment_account");
-        return Err(ProgramError::InvalidAccountData);
+    if *payment_account.key != find_treasury_address(program_id).0 {
+        return Err(NameServiceError::InvalidPaymentAccount.into());
     }
     let token = next_account_info(accounts_iter)?;
     let minter_of_token = next_account_info(accounts_iter)?;
-    let test_token_data = token.try_borrow_data()?;
-    let owner_of_token_from_data = &test_token_data[4..36];
-    // Check if user is minter of token SPL-token standard
-    if owner_of_token_from_data != minter_of_token.key.to_bytes() {
-        log::sol_log("You are not minter of this token");
-        return Err(ProgramError::InvalidAccountData);
+    check_minter(token, minter_of_token)?;
+    let data_to_store = Register {
+        token_address: *token.key,
+        token_name: token_name.into_bytes(),
+    };
+    // Record must live at the address derived from the token
+    let storage_account = next_account_info(accounts_iter)?;
+    let payer = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    let reserved_account = next_account_info(accounts_iter)?;
+    if !payer.is_signer {
+        log::sol_log("Transaction need to be signed by payer");
+        return Err(ProgramError::MissingRequiredSignature);
     }
-    // Check if minter sends transaction
-    if minter_of_token.is_signer == false {
-        log::sol_log("Transaction need to be send from minter account");
-        return Err(ProgramError::InvalidAccountData);
+    Config::check_reserved_name(
+        program_id,
+        &data_to_store.token_name,
+        reserved_account,
+        accounts_iter.next(),
+    )?;
+    let (record_address, bump_seed) = find_token_record_address(token.key, program_id);
+    if *storage_account.key != record_address {
+        return Err(NameServiceError::InvalidRecordAddress.into());
+    }
+    if storage_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            payer,
+            storage_account,
+            system_program,
+            rent.minimum_balance(data_to_store.packed_len()),
+            data_to_store.packed_len(),
+            &[TOKEN_SEED, &token.key.to_bytes(), &[bump_seed]],
+        )?;
     }
-    let storage_account = next_account_info(accounts_iter)?;
     // Check if programs owns account where we store data
     if storage_account.owner != program_id {
-        log::sol_log("storage_account must be owned by program");
-        return Err(ProgramError::InvalidAccountData);
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    if !rent.is_exempt(storage_account.lamports(), storage_account.data_len()) {
+        return Err(NameServiceError::NotRentExempt.into());
     }
-    let mut storage_account_balance = storage_account.try_borrow_mut_lamports()?;
-    if **storage_account_balance < REGISTRATION_FEE {
-        log::sol_log("InsufficientFunds in storage_account");
-        return Err(ProgramError::InsufficientFunds);
-    }
-    let mut payment_account_balance = payment_account.try_borrow_mut_lamports()?;
-    // Transfer fee
-    **storage_account_balance = storage_account_balance.wrapping_sub(REGISTRATION_FEE);
-    **payment_account_balance = payment_account_balance.wrapping_add(REGISTRATION_FEE);
     let mut storage_account_data = storage_account.try_borrow_mut_data()?;
     // log::sol_log(&storage_account_data.len().to_string());
     // log::sol_log(&STORAGE_DATA_SIZE.to_string());
 
-    // I should add special field but is good enough for now
-    if storage_account_data[0] != 0 {
-        log::sol_log("Account data is not empty.");
-        return Err(ProgramError::InvalidAccountData);
+    if storage_account_data.len().lt(&data_to_store.packed_len()) {
+        return Err(NameServiceError::AccountDataTooSmall.into());
     }
+    if Register::unpack_from_slice(&storage_account_data)?.is_initialized() {
+        return Err(NameServiceError::AccountNotEmpty.into());
+    }
+
+    charge_fee(payer, payment_account, system_program, fee)?;
+    // Store data
+    data_to_store.pack_into_slice(&mut storage_account_data);
+    Ok(())
+}
+
+fn process_close(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let storage_account = next_account_info(accounts_iter)?;
+    let token = next_account_info(accounts_iter)?;
+    let minter_of_token = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+    check_minter(token, minter_of_token)?;
+    if storage_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let mut storage_account_data = storage_account.try_borrow_mut_data()?;
     if storage_account_data.len().lt(&STORAGE_DATA_SIZE) {
-        log::sol_log("Account data field is insufficient");
-        return Err(ProgramError::InvalidAccountData);
+        return Err(NameServiceError::NameNotRegistered.into());
+    }
+    let register = Register::unpack_from_slice(&storage_account_data)?;
+    if !register.is_initialized() {
+        return Err(NameServiceError::NameNotRegistered.into());
     }
-    if data_to_save.len().gt(&MAX_DATA_SIZE) {
-        log::sol_log("Account data field is insufficient");
-        return Err(ProgramError::InvalidAccountData);
+    if register.token_address != *token.key {
+        return Err(NameServiceError::InvalidStorageAccount.into());
     }
-    if instruction_data.len() != 32 {
-        log::sol_log("Inavlid  instruction data");
-        return Err(ProgramError::InvalidInstructionData);
+    for byte in storage_account_data.iter_mut() {
+        *byte = 0;
     }
-    let token_name = array_ref![instruction_data, 0, 32];
+    let mut storage_account_balance = storage_account.try_borrow_mut_lamports()?;
+    let mut destination_balance = destination.try_borrow_mut_lamports()?;
+    **destination_balance = destination_balance
+        .checked_add(**storage_account_balance)
+        .ok_or(NameServiceError::Overflow)?;
+    **storage_account_balance = 0;
+    Ok(())
+}
 
-    let data_to_store = Register {
-        token_address: *token.key,
-        token_name: *token_name,
+/// Moves `fee` from signing `payer` to `to` through the system program,
+/// fails early when the payer can not cover it
+fn charge_fee<'a>(
+    payer: &AccountInfo<'a>,
+    to: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    fee: u64,
+) -> ProgramResult {
+    if fee == 0 {
+        return Ok(());
+    }
+    if payer.lamports() < fee {
+        return Err(NameServiceError::InsufficientFunds.into());
+    }
+    invoke(
+        &system_instruction::transfer(payer.key, to.key, fee),
+        &[payer.clone(), to.clone(), system_program.clone()],
+    )
+}
+
+/// Fails unless `minter_of_token` is the signing mint authority of `token`
+fn check_minter(token: &AccountInfo, minter_of_token: &AccountInfo) -> ProgramResult {
+    let test_token_data = token.try_borrow_data()?;
+    if test_token_data.len() < 36 {
+        return Err(NameServiceError::InvalidTokenAccount.into());
+    }
+    let owner_of_token_from_data = &test_token_data[4..36];
+    // Check if user is minter of token SPL-token standard
+    if owner_of_token_from_data != minter_of_token.key.to_bytes() {
+        return Err(NameServiceError::NotTokenMinter.into());
+    }
+    // Check if minter sends transaction
+    if !minter_of_token.is_signer {
+        log::sol_log("Transaction need to be send from minter account");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+fn process_initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    beneficiaries: [Beneficiary; MAX_BENEFICIARIES],
+    fees: [u64; FEE_TIERS],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+    let treasury = next_account_info(accounts_iter)?;
+    if !admin.is_signer {
+        log::sol_log("Transaction need to be signed by admin");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *admin.key != Pubkey::from_str(DEPLOYER_ADDRESS).unwrap() {
+        return Err(NameServiceError::NotDeployer.into());
+    }
+    Config::check_beneficiaries(&beneficiaries)?;
+    let (config_address, bump_seed) = find_config_address(program_id);
+    if *config_account.key != config_address {
+        return Err(NameServiceError::InvalidConfigAddress.into());
+    }
+    let (treasury_address, treasury_bump_seed) = find_treasury_address(program_id);
+    if *treasury.key != treasury_address {
+        return Err(NameServiceError::InvalidPaymentAccount.into());
+    }
+    // Treasury holds no data, the program owns it to pay out withdrawals
+    if treasury.owner != program_id {
+        create_pda_account(
+            program_id,
+            admin,
+            treasury,
+            system_program,
+            rent.minimum_balance(0),
+            0,
+            &[TREASURY_SEED, &[treasury_bump_seed]],
+        )?;
+    }
+    if config_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            admin,
+            config_account,
+            system_program,
+            rent.minimum_balance(CONFIG_DATA_SIZE),
+            CONFIG_DATA_SIZE,
+            &[CONFIG_SEED, &[bump_seed]],
+        )?;
+    }
+    if config_account.owner != program_id {
+        return Err(NameServiceError::IncorrectAccountOwner.into());
+    }
+    let mut config_data = config_account.try_borrow_mut_data()?;
+    if config_data.len().lt(&CONFIG_DATA_SIZE) {
+        return Err(NameServiceError::AccountDataTooSmall.into());
+    }
+    if Config::unpack_from_slice(&config_data)?.is_initialized {
+        return Err(NameServiceError::ConfigAlreadyInitialized.into());
+    }
+    let config = Config {
+        is_initialized: true,
+        admin: *admin.key,
+        beneficiaries,
+        fees,
+        paused: false,
     };
-    // Store data
-    data_to_store.pack_into_slice(&mut storage_account_data);
+    Config::pack_into_slice(&config, &mut config_data);
+    Ok(())
+}
+
+fn process_update_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_admin: Pubkey,
+    beneficiaries: [Beneficiary; MAX_BENEFICIARIES],
+    fees: [u64; FEE_TIERS],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let mut config = Config::load(program_id, config_account)?;
+    config.check_admin(admin)?;
+    Config::check_beneficiaries(&beneficiaries)?;
+    config.admin = new_admin;
+    config.beneficiaries = beneficiaries;
+    config.fees = fees;
+    Config::pack_into_slice(&config, &mut config_account.try_borrow_mut_data()?);
     Ok(())
 }
 
 // tests
 #[cfg(test)]
 mod test {
-    use std::str::FromStr;
+    use std::str::{from_utf8, FromStr};
 
     use super::*;
-    use solana_sdk::clock::Epoch;
+    use name_service_common::{
+        find_reserved_name_address, reserved_name_hash, ReservedName, RESERVED_DATA_SIZE,
+    };
+    use solana_sdk::{clock::Epoch, system_program, sysvar};
+
+    const REGISTRATION_FEE: u64 = 1_000_000_000;
 
     #[test]
     fn test_struct() {
@@ -151,7 +473,7 @@ mod test {
         let data = [token_address.to_bytes(), token_name].concat();
         let register_data = Register {
             token_address,
-            token_name,
+            token_name: token_name.to_vec(),
         };
         let register_data_from_slice = Register::unpack_from_slice(&data).unwrap();
         assert_eq!(register_data_from_slice, register_data);
@@ -160,6 +482,27 @@ mod test {
             from_utf8(&register_data_from_slice.token_name).unwrap(),
             "some super random token name xxx"
         );
+        // Long names continue after the padded head
+        let long_data = Register {
+            token_address,
+            token_name: "some super random token name that is \u{1f980}"
+                .as_bytes()
+                .to_vec(),
+        };
+        let mut data = vec![0; long_data.packed_len()];
+        long_data.pack_into_slice(&mut data);
+        assert_eq!(Register::unpack_from_slice(&data).unwrap(), long_data);
+        // Tokens whose address starts with a zero byte are still registered
+        let mut zero_led = [1; 32];
+        zero_led[0] = 0;
+        let zero_led_data = Register {
+            token_address: Pubkey::new_from_array(zero_led),
+            ..long_data
+        };
+        assert!(zero_led_data.is_initialized());
+        assert!(!Register::unpack_from_slice(&[0; STORAGE_DATA_SIZE])
+            .unwrap()
+            .is_initialized());
     }
     #[test]
     fn test_flow() {
@@ -172,8 +515,10 @@ mod test {
         let mut balance_payment_account = 0;
         let mut balance_token = 0;
         let mut balance_minter_token = 0;
-        let mut balance_storage_account = REGISTRATION_FEE + 1;
+        let mut balance_storage_account = 1;
         let mut storage_data = vec![0; STORAGE_DATA_SIZE];
+        let mut balance_config_account = 0;
+        let mut config_data = vec![0; CONFIG_DATA_SIZE];
 
         let mut test_token_data: [u8; 82] = [
             0x01, 0x00, 0x00, 0x00, 0x32, 0x06, 0x37, 0xf2, 0xb0, 0x59, 0x03, 0x06, 0x66, 0x34,
@@ -190,8 +535,27 @@ mod test {
         let token_address =
             Pubkey::from_str("FJNj5YDJVT3pbtiZsMMeRPJumM35iF71rUzDQWC7CXqq").unwrap();
 
+        let (payment_account_key, _) = find_treasury_address(&program_id);
+        let config = Config {
+            is_initialized: true,
+            admin: Pubkey::new_unique(),
+            fees: [10, 8, 6, 4, REGISTRATION_FEE],
+            ..Default::default()
+        };
+        Config::pack_into_slice(&config, &mut config_data);
+
         let owner = Pubkey::default();
-        let payment_account_key = Pubkey::from_str(PAYMENT_ACCOUNT_ADDRESS).unwrap();
+        let (config_address, _) = find_config_address(&program_id);
+        let config_account = AccountInfo::new(
+            &config_address,             // account pubkey
+            false,                       // is_signer
+            false,                       // is_writable
+            &mut balance_config_account, // balance in lamports
+            &mut config_data,            // storage
+            &program_id,                 // owner pubkey
+            false,                       // is_executable
+            Epoch::default(),            // rent_epoch
+        );
         let payment_account = AccountInfo::new(
             &payment_account_key,         // account pubkey
             false,                        // is_signer
@@ -222,9 +586,9 @@ mod test {
             false,                     // is_executable
             Epoch::default(),          // rent_epoch
         );
-        let random_address = Pubkey::default();
+        let (storage_address, _) = find_token_record_address(&token_address, &program_id);
         let storage_account = AccountInfo::new(
-            &random_address,              // account pubkey
+            &storage_address,             // account pubkey
             false,                        // is_signer
             true,                         // is_writable
             &mut balance_storage_account, // balance in lamports
@@ -233,18 +597,484 @@ mod test {
             false,                        // is_executable
             Epoch::default(),             // rent_epoch
         );
-        let accounts = vec![payment_account, token, minter_of_token, storage_account];
-
+        let mut empty_data4 = vec![0; 0];
+        let mut zero_balance = 0;
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance,  // balance in lamports
+            &mut empty_data4,   // storage
+            &program_id,        // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let mut rent_data = vec![0; Rent::size_of()];
+        let mut zero_balance2 = 0;
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut rent_data,     // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
         let token_name: [u8; 32] = *b"some super random token name xxx";
-        let instruction_data = token_name;
+        // Name is reserved for the minter of the token
+        let mut zero_balance3 = 0;
+        let mut reserved_data = vec![0; RESERVED_DATA_SIZE];
+        let name_hash = reserved_name_hash(&token_name);
+        let reserved_name = ReservedName {
+            is_initialized: true,
+            name_hash,
+            authority: owner_of_token_address,
+        };
+        ReservedName::pack_into_slice(&reserved_name, &mut reserved_data);
+        let (reserved_address, _) = find_reserved_name_address(&name_hash, &program_id);
+        let reserved_account = AccountInfo::new(
+            &reserved_address,  // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut reserved_data, // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        // Minter pays for the storage account
+        let mut accounts = vec![
+            config_account,
+            payment_account,
+            token,
+            minter_of_token.clone(),
+            storage_account,
+            minter_of_token.clone(),
+            system_program,
+            rent_account,
+            reserved_account,
+        ];
+
+        let instruction_data = TokenNameInstruction::Register {
+            token_name: token_name.to_vec(),
+        }
+        .pack();
+        // Nothing is registered while the program is paused
+        let paused_config = Config {
+            paused: true,
+            ..config
+        };
+        Config::pack_into_slice(&paused_config, &mut accounts[0].data.borrow_mut());
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::ProgramPaused.into())
+        );
+        Config::pack_into_slice(&config, &mut accounts[0].data.borrow_mut());
+        // Reserved name needs its authority to sign
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NameReserved.into())
+        );
+        accounts.push(minter_of_token);
+        // Record has to stay rent exempt
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NotRentExempt.into())
+        );
+        **accounts[4].lamports.borrow_mut() = REGISTRATION_FEE;
+        // Payer has to cover the fee
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InsufficientFunds.into())
+        );
+        **accounts[5].lamports.borrow_mut() = REGISTRATION_FEE;
         process_instruction(&program_id, &accounts, &instruction_data).unwrap();
-        // Check if fund are moved
-        let payment_target = accounts[0].lamports.borrow();
-        assert_eq!(**payment_target, REGISTRATION_FEE);
+        // Fee is moved from the payer by the system program, the record
+        // keeps its balance
+        assert_eq!(**accounts[4].lamports.borrow(), REGISTRATION_FEE);
         // Check if data stored is correct
-        let data_stored = accounts[3].data.borrow();
+        let data_stored = accounts[4].data.borrow();
         let registered_data = Register::unpack_from_slice(&data_stored).unwrap();
         assert_eq!(registered_data.token_address, token_address);
         assert_eq!(registered_data.token_name, token_name);
+        drop(data_stored);
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::AccountNotEmpty.into())
+        );
+        // Names with control characters are rejected
+        let instruction_data = TokenNameInstruction::Register {
+            token_name: b"some super random token name\n".to_vec(),
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InvalidTokenName.into())
+        );
+        // Storage account has to be derived from the token
+        let mut wrong_accounts = accounts.clone();
+        wrong_accounts[4] = accounts[1].clone();
+        let instruction_data = TokenNameInstruction::Register {
+            token_name: token_name.to_vec(),
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &wrong_accounts, &instruction_data),
+            Err(NameServiceError::InvalidRecordAddress.into())
+        );
+
+        // Minter closes the name and gets the storage lamports back
+        let mut empty_data3 = vec![0; 0];
+        let mut balance_destination = 0;
+        let destination_address = Pubkey::new_unique();
+        let destination = AccountInfo::new(
+            &destination_address,     // account pubkey
+            false,                    // is_signer
+            true,                     // is_writable
+            &mut balance_destination, // balance in lamports
+            &mut empty_data3,         // storage
+            &owner,                   // owner pubkey
+            false,                    // is_executable
+            Epoch::default(),         // rent_epoch
+        );
+        let close_accounts = vec![
+            accounts[4].clone(),
+            accounts[2].clone(),
+            accounts[3].clone(),
+            destination,
+        ];
+        let instruction_data = TokenNameInstruction::Close.pack();
+        process_instruction(&program_id, &close_accounts, &instruction_data).unwrap();
+        assert_eq!(**close_accounts[3].lamports.borrow(), REGISTRATION_FEE);
+        assert_eq!(**close_accounts[0].lamports.borrow(), 0);
+        assert!(close_accounts[0]
+            .data
+            .borrow()
+            .iter()
+            .all(|byte| *byte == 0));
+        assert_eq!(
+            process_instruction(&program_id, &close_accounts, &instruction_data),
+            Err(NameServiceError::NameNotRegistered.into())
+        );
+    }
+    #[test]
+    fn test_config() {
+        let program_id = Pubkey::default();
+
+        let mut empty_data = vec![0; 0];
+        let mut empty_data2 = vec![0; 0];
+        let mut zero_balance = 0;
+        let mut zero_balance2 = 0;
+        let mut zero_balance3 = 0;
+        let mut zero_balance4 = 0;
+        let mut config_data = vec![0; CONFIG_DATA_SIZE];
+        let mut rent_data = vec![0; Rent::size_of()];
+        let mut empty_data3 = vec![0; 0];
+        let mut empty_data4 = vec![0; 0];
+        let mut empty_data5 = vec![0; 0];
+        let mut balance_treasury = Rent::default().minimum_balance(0) + 1_001;
+        let mut zero_balance5 = 0;
+        let mut zero_balance6 = 0;
+        // Prefunded, account creation is not run in tests
+        let mut balance_reserved = Rent::default().minimum_balance(RESERVED_DATA_SIZE);
+        let mut reserved_data = vec![0; RESERVED_DATA_SIZE];
+
+        let admin_address = Pubkey::from_str(DEPLOYER_ADDRESS).unwrap();
+        let intruder_address = Pubkey::new_unique();
+        let partner_address = Pubkey::new_unique();
+        let beneficiaries = [
+            Beneficiary {
+                address: admin_address,
+                share: 7_000,
+            },
+            Beneficiary {
+                address: partner_address,
+                share: 3_000,
+            },
+            Beneficiary::default(),
+            Beneficiary::default(),
+        ];
+        let (config_address, _) = find_config_address(&program_id);
+        let config_account = AccountInfo::new(
+            &config_address,   // account pubkey
+            false,             // is_signer
+            true,              // is_writable
+            &mut zero_balance, // balance in lamports
+            &mut config_data,  // storage
+            &program_id,       // owner pubkey
+            false,             // is_executable
+            Epoch::default(),  // rent_epoch
+        );
+        let admin = AccountInfo::new(
+            &admin_address,     // account pubkey
+            true,               // is_signer
+            true,               // is_writable
+            &mut zero_balance2, // balance in lamports
+            &mut empty_data,    // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let system_program_id = system_program::id();
+        let system_program = AccountInfo::new(
+            &system_program_id, // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance3, // balance in lamports
+            &mut empty_data2,   // storage
+            &program_id,        // owner pubkey
+            true,               // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let rent_id = sysvar::rent::id();
+        let mut rent_account = AccountInfo::new(
+            &rent_id,           // account pubkey
+            false,              // is_signer
+            false,              // is_writable
+            &mut zero_balance4, // balance in lamports
+            &mut rent_data,     // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        Rent::default().to_account_info(&mut rent_account).unwrap();
+        let (treasury_address, _) = find_treasury_address(&program_id);
+        let treasury = AccountInfo::new(
+            &treasury_address,     // account pubkey
+            false,                 // is_signer
+            true,                  // is_writable
+            &mut balance_treasury, // balance in lamports
+            &mut empty_data3,      // storage
+            &program_id,           // owner pubkey
+            false,                 // is_executable
+            Epoch::default(),      // rent_epoch
+        );
+        let partner = AccountInfo::new(
+            &partner_address,   // account pubkey
+            false,              // is_signer
+            true,               // is_writable
+            &mut zero_balance5, // balance in lamports
+            &mut empty_data4,   // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        let mut accounts = vec![
+            config_account,
+            admin,
+            system_program,
+            rent_account,
+            treasury,
+        ];
+        // Shares have to add up to the whole
+        let mut uneven = beneficiaries;
+        uneven[1].share = 2_000;
+        let instruction_data = TokenNameInstruction::InitializeConfig {
+            beneficiaries: uneven,
+            fees: [10, 8, 6, 4, REGISTRATION_FEE],
+        }
+        .pack();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::InvalidBeneficiaries.into())
+        );
+        let instruction_data = TokenNameInstruction::InitializeConfig {
+            beneficiaries,
+            fees: [10, 8, 6, 4, REGISTRATION_FEE],
+        }
+        .pack();
+        // Only the deployer can claim the config
+        accounts[1].key = &intruder_address;
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::NotDeployer.into())
+        );
+        accounts[1].key = &admin_address;
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let config = Config::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                is_initialized: true,
+                admin: admin_address,
+                beneficiaries,
+                fees: [10, 8, 6, 4, REGISTRATION_FEE],
+                paused: false,
+            }
+        );
+        // Config can be initialized only once
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &instruction_data),
+            Err(NameServiceError::ConfigAlreadyInitialized.into())
+        );
+
+        // Withdraw splits the treasury above rent by shares
+        let withdraw_data = TokenNameInstruction::Withdraw.pack();
+        let withdraw_accounts = vec![
+            accounts[0].clone(),
+            accounts[4].clone(),
+            accounts[3].clone(),
+            partner.clone(),
+            accounts[1].clone(),
+        ];
+        assert_eq!(
+            process_instruction(&program_id, &withdraw_accounts, &withdraw_data),
+            Err(NameServiceError::InvalidBeneficiaryAccount.into())
+        );
+        let withdraw_accounts = vec![
+            accounts[0].clone(),
+            accounts[4].clone(),
+            accounts[3].clone(),
+            accounts[1].clone(),
+            partner,
+        ];
+        process_instruction(&program_id, &withdraw_accounts, &withdraw_data).unwrap();
+        assert_eq!(**withdraw_accounts[3].lamports.borrow(), 700);
+        assert_eq!(**withdraw_accounts[4].lamports.borrow(), 300);
+        // Rounding leftovers stay for the next withdraw
+        assert_eq!(
+            **withdraw_accounts[1].lamports.borrow(),
+            Rent::default().minimum_balance(0) + 1
+        );
+        let mut stranger = withdraw_accounts.clone();
+        let stranger_address = Pubkey::new_unique();
+        stranger[4] = AccountInfo::new(
+            &stranger_address,  // account pubkey
+            false,              // is_signer
+            true,               // is_writable
+            &mut zero_balance6, // balance in lamports
+            &mut empty_data5,   // storage
+            &program_id,        // owner pubkey
+            false,              // is_executable
+            Epoch::default(),   // rent_epoch
+        );
+        assert_eq!(
+            process_instruction(&program_id, &stranger, &withdraw_data),
+            Err(NameServiceError::InvalidBeneficiaryAccount.into())
+        );
+        // Only the treasury derived from the program pays out
+        stranger[1] = stranger[4].clone();
+        assert_eq!(
+            process_instruction(&program_id, &stranger, &withdraw_data),
+            Err(NameServiceError::InvalidPaymentAccount.into())
+        );
+
+        // Admin reserves a token name for an authority and releases it later
+        let name_hash = reserved_name_hash(b"usdc");
+        let (reserved_address, _) = find_reserved_name_address(&name_hash, &program_id);
+        let reserved_account = AccountInfo::new(
+            &reserved_address,     // account pubkey
+            false,                 // is_signer
+            true,                  // is_writable
+            &mut balance_reserved, // balance in lamports
+            &mut reserved_data,    // storage
+            &program_id,           // owner pubkey
+            false,                 // is_executable
+            Epoch::default(),      // rent_epoch
+        );
+        let authority_address = Pubkey::new_unique();
+        let reserve_data = TokenNameInstruction::ReserveName {
+            name_hash,
+            authority: authority_address,
+        }
+        .pack();
+        let reserve_accounts = vec![
+            accounts[0].clone(),
+            accounts[1].clone(),
+            reserved_account.clone(),
+            accounts[2].clone(),
+            accounts[3].clone(),
+        ];
+        process_instruction(&program_id, &reserve_accounts, &reserve_data).unwrap();
+        assert_eq!(
+            ReservedName::unpack_from_slice(&reserved_account.data.borrow()).unwrap(),
+            ReservedName {
+                is_initialized: true,
+                name_hash,
+                authority: authority_address,
+            }
+        );
+        let release_data = TokenNameInstruction::ReleaseName { name_hash }.pack();
+        let release_accounts = vec![
+            accounts[0].clone(),
+            accounts[1].clone(),
+            reserved_account.clone(),
+            accounts[4].clone(),
+        ];
+        process_instruction(&program_id, &release_accounts, &release_data).unwrap();
+        assert_eq!(**reserved_account.lamports.borrow(), 0);
+        assert_eq!(
+            process_instruction(&program_id, &release_accounts, &release_data),
+            Err(NameServiceError::NameNotReserved.into())
+        );
+
+        // Admin pauses and resumes the program
+        let pause_data = TokenNameInstruction::SetPaused { paused: true }.pack();
+        process_instruction(&program_id, &accounts[..2], &pause_data).unwrap();
+        assert!(
+            Config::unpack_from_slice(&accounts[0].data.borrow())
+                .unwrap()
+                .paused
+        );
+        let resume_data = TokenNameInstruction::SetPaused { paused: false }.pack();
+        process_instruction(&program_id, &accounts[..2], &resume_data).unwrap();
+        assert!(
+            !Config::unpack_from_slice(&accounts[0].data.borrow())
+                .unwrap()
+                .paused
+        );
+
+        // Admin changes the fees and hands config over
+        let new_admin_address = Pubkey::new_unique();
+        let instruction_data = TokenNameInstruction::UpdateConfig {
+            admin: new_admin_address,
+            beneficiaries,
+            fees: [42; FEE_TIERS],
+        }
+        .pack();
+        process_instruction(&program_id, &accounts[..2], &instruction_data).unwrap();
+        let config = Config::unpack_from_slice(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(config.admin, new_admin_address);
+        assert_eq!(config.fees, [42; FEE_TIERS]);
+        // Previous admin lost access
+        assert_eq!(
+            process_instruction(&program_id, &accounts[..2], &instruction_data),
+            Err(NameServiceError::NotAdmin.into())
+        );
+        assert_eq!(
+            process_instruction(&program_id, &accounts[..2], &pause_data),
+            Err(NameServiceError::NotAdmin.into())
+        );
+        accounts[1].key = &new_admin_address;
+        accounts[1].is_signer = false;
+        assert_eq!(
+            process_instruction(&program_id, &accounts[..2], &instruction_data),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+    #[test]
+    fn test_fee_for() {
+        let config = Config {
+            is_initialized: true,
+            admin: Pubkey::new_unique(),
+            beneficiaries: [Beneficiary {
+                address: Pubkey::new_unique(),
+                share: 2_500,
+            }; MAX_BENEFICIARIES],
+            fees: [10, 8, 6, 4, REGISTRATION_FEE],
+            paused: true,
+        };
+        assert_eq!(config.fee_for(1), 10);
+        assert_eq!(config.fee_for(3), 6);
+        assert_eq!(config.fee_for(4), 4);
+        assert_eq!(config.fee_for(6), REGISTRATION_FEE);
+        // Packed schedule keeps tier order
+        let mut config_data = vec![0; CONFIG_DATA_SIZE];
+        Config::pack_into_slice(&config, &mut config_data);
+        assert_eq!(Config::unpack_from_slice(&config_data).unwrap(), config);
     }
 }